@@ -2,13 +2,70 @@
 
 #[ink::contract]
 mod escrow_smart_contract {
+    use ink::env::call::{ build_call, ExecutionInput, Selector };
+    use ink::env::hash::{ Blake2x256, HashOutput };
+    use ink::prelude::vec::Vec;
     use ink::storage::Mapping;
 
     /// Unique identifier for escrow transactions
     type EscrowId = u64;
 
+    /// Unique identifier for escrow templates
+    type TemplateId = u64;
+
+    /// Unique identifier for admin proposals
+    type ProposalId = u64;
+
+    /// The outcome of processing a single escrow in `process_due`.
+    type ProcessOutcome = (EscrowId, Result<EscrowState, Error>);
+
+    /// Maximum number of escrows scanned or processed by a single paginated or batch call.
+    const MAX_BATCH_SIZE: u32 = 100;
+
+    /// Maximum number of recipients of a split escrow.
+    const MAX_RECIPIENTS: usize = 16;
+
+    /// Basis points in one whole, used for percentage-based terms.
+    const MAX_BPS: u16 = 10_000;
+
+    /// Maximum number of admins confirming sensitive owner actions.
+    const MAX_ADMINS: usize = 16;
+
+    /// Maximum number of sellers on a buyer's allowlist.
+    const MAX_ALLOWED_SELLERS: usize = 64;
+
+    /// Maximum length in bytes of an escrow's memo.
+    const MAX_MEMO_LEN: usize = 64;
+
+    /// Maximum number of amendments applied to a single escrow.
+    const MAX_AMENDMENTS: usize = 16;
+    /// The maximum number of state changes kept per escrow; older entries are dropped.
+    const MAX_HISTORY: usize = 16;
+    /// Passed to `set_fee_override` to remove an account's fee override.
+    const FEE_OVERRIDE_REMOVED: u16 = u16::MAX;
+    /// The number of escrow IDs covered by one word of a state's index.
+    const STATE_WORD_BITS: EscrowId = 128;
+    /// The semantic version of the contract logic, taken from the crate version.
+    const VERSION: (u16, u16, u16) = (
+        parse_version_part(env!("CARGO_PKG_VERSION_MAJOR")),
+        parse_version_part(env!("CARGO_PKG_VERSION_MINOR")),
+        parse_version_part(env!("CARGO_PKG_VERSION_PATCH")),
+    );
+
+    /// Parses one numeric part of a version string at compile time.
+    const fn parse_version_part(part: &str) -> u16 {
+        let digits = part.as_bytes();
+        let mut value = 0;
+        let mut i = 0;
+        while i < digits.len() {
+            value = value * 10 + (digits[i] - b'0') as u16;
+            i += 1;
+        }
+        value
+    }
+
     /// Represents the possible states of an escrow transaction.
-    #[derive(Debug, PartialEq, Eq, scale::Encode, scale::Decode)]
+    #[derive(Debug, Clone, Copy, PartialEq, Eq, scale::Encode, scale::Decode)]
     #[cfg_attr(feature = "std", derive(scale_info::TypeInfo, ink::storage::traits::StorageLayout))]
     pub enum EscrowState {
         /// The escrow has been created but no funds have been deposited.
@@ -19,6 +76,35 @@ mod escrow_smart_contract {
         Completed = 2,
         /// The escrow has been canceled, and the funds (if any) have been returned to the buyer.
         Canceled = 3,
+        /// A party raised a dispute, and the funds await the arbiter's decision.
+        Disputed = 4,
+        /// The completed escrow was returned within its return window, and the seller's
+        /// repayment was forwarded to the buyer.
+        Returned = 5,
+    }
+
+    /// Converts an escrow state into its numeric code, which matches its discriminant.
+    impl From<EscrowState> for u8 {
+        fn from(value: EscrowState) -> Self {
+            value as u8
+        }
+    }
+
+    /// Converts a numeric code back into an escrow state, returning the code if it is unknown.
+    impl TryFrom<u8> for EscrowState {
+        type Error = u8;
+
+        fn try_from(code: u8) -> Result<Self, Self::Error> {
+            match code {
+                0 => Ok(EscrowState::Created),
+                1 => Ok(EscrowState::Funded),
+                2 => Ok(EscrowState::Completed),
+                3 => Ok(EscrowState::Canceled),
+                4 => Ok(EscrowState::Disputed),
+                5 => Ok(EscrowState::Returned),
+                code => Err(code),
+            }
+        }
     }
 
     /// Represents the possible errors that can occur during escrow operations.
@@ -29,7 +115,7 @@ mod escrow_smart_contract {
         Unauthorized = 0,
         /// The escrow is in an invalid state for the requested operation.
         InvalidState = 1,
-        /// The deposited amount is not equal to the agreed amount.
+        /// The deposited amount is not equal to the agreed amount, or the agreed amount is zero.
         InvalidAmount = 2,
         /// The party has already approved the transaction.
         AlreadyApproved = 3,
@@ -41,6 +127,113 @@ mod escrow_smart_contract {
         NotFound = 6,
         /// The escrow ID counter overflowed.
         IdOverflow = 7,
+        /// The deadline for the requested operation has not been reached yet.
+        DeadlineNotReached = 8,
+        /// The caller is not the buyer of the escrow.
+        NotBuyer = 9,
+        /// The caller is not the seller of the escrow.
+        NotSeller = 10,
+        /// The block height required for release has not been reached yet.
+        BlockNotReached = 11,
+        /// The requested batch or page exceeds the maximum size.
+        BatchTooLarge = 12,
+        /// A split escrow was initiated without recipients.
+        NoRecipients = 13,
+        /// The shares of a split escrow do not add up to its amount.
+        ShareSumMismatch = 14,
+        /// The zero account was given where a real account is required.
+        ZeroAddress = 15,
+        /// The amendment does not match the one proposed by the other party.
+        AmendmentMismatch = 16,
+        /// A basis-point value exceeds 10000.
+        InvalidFee = 17,
+        /// Returned if refund splits are not made of real accounts whose basis points sum to 10_000.
+        InvalidSplit = 18,
+        /// An arithmetic operation overflowed.
+        Overflow = 19,
+        /// Returned if the escrow template does not exist.
+        TemplateNotFound = 20,
+        /// Returned if the deadline for the operation has passed.
+        DeadlineExceeded = 21,
+        /// Returned if the buyer already has the maximum number of open escrows.
+        TooManyOpenEscrows = 22,
+        /// The escrow is no longer in the state the caller expected.
+        StateChanged = 23,
+        /// Registering the contract with a registry failed.
+        RegistrationFailed = 24,
+        /// A high-value escrow must be confirmed by the buyer before it is released.
+        ConfirmationRequired = 25,
+        /// The contract has been shut down by the owner.
+        ShutDown = 26,
+        /// The contract has not been shut down.
+        NotShutDown = 27,
+        /// The confirmation threshold is zero or exceeds the number of admins.
+        InvalidThreshold = 28,
+        /// The admin proposal has fewer confirmations than the threshold.
+        NotEnoughConfirmations = 29,
+        /// The oracle of the escrow has not confirmed delivery.
+        DeliveryNotConfirmed = 30,
+        /// The memo is longer than `MAX_MEMO_LEN` bytes.
+        MemoTooLong = 31,
+        /// Replacing the contract code failed.
+        UpgradeFailed = 32,
+        /// The buyer only deals with sellers on its allowlist, which does not include the seller.
+        SellerNotAllowed = 33,
+        /// A party of the disputed escrow has not posted its dispute deposit.
+        DisputeDepositMissing = 34,
+    }
+
+    /// Converts an error into its numeric code, which matches its discriminant.
+    impl From<Error> for u8 {
+        fn from(value: Error) -> Self {
+            value as u8
+        }
+    }
+
+    /// Converts a numeric code back into an error, returning the code if it is unknown.
+    impl TryFrom<u8> for Error {
+        type Error = u8;
+
+        fn try_from(code: u8) -> Result<Self, Self::Error> {
+            match code {
+                0 => Ok(Error::Unauthorized),
+                1 => Ok(Error::InvalidState),
+                2 => Ok(Error::InvalidAmount),
+                3 => Ok(Error::AlreadyApproved),
+                4 => Ok(Error::InvalidParticipants),
+                5 => Ok(Error::TransferFailed),
+                6 => Ok(Error::NotFound),
+                7 => Ok(Error::IdOverflow),
+                8 => Ok(Error::DeadlineNotReached),
+                9 => Ok(Error::NotBuyer),
+                10 => Ok(Error::NotSeller),
+                11 => Ok(Error::BlockNotReached),
+                12 => Ok(Error::BatchTooLarge),
+                13 => Ok(Error::NoRecipients),
+                14 => Ok(Error::ShareSumMismatch),
+                15 => Ok(Error::ZeroAddress),
+                16 => Ok(Error::AmendmentMismatch),
+                17 => Ok(Error::InvalidFee),
+                18 => Ok(Error::InvalidSplit),
+                19 => Ok(Error::Overflow),
+                20 => Ok(Error::TemplateNotFound),
+                21 => Ok(Error::DeadlineExceeded),
+                22 => Ok(Error::TooManyOpenEscrows),
+                23 => Ok(Error::StateChanged),
+                24 => Ok(Error::RegistrationFailed),
+                25 => Ok(Error::ConfirmationRequired),
+                26 => Ok(Error::ShutDown),
+                27 => Ok(Error::NotShutDown),
+                28 => Ok(Error::InvalidThreshold),
+                29 => Ok(Error::NotEnoughConfirmations),
+                30 => Ok(Error::DeliveryNotConfirmed),
+                31 => Ok(Error::MemoTooLong),
+                32 => Ok(Error::UpgradeFailed),
+                33 => Ok(Error::SellerNotAllowed),
+                34 => Ok(Error::DisputeDepositMissing),
+                code => Err(code),
+            }
+        }
     }
 
     /// The main contract struct that holds the escrow data.
@@ -50,6 +243,104 @@ mod escrow_smart_contract {
         escrows: Mapping<EscrowId, Escrow>,
         /// The next available escrow ID.
         next_id: EscrowId,
+        /// The sum of the amounts of all funded escrows.
+        total_locked: Balance,
+        /// Whether events carry a commitment hash instead of the raw amount.
+        obfuscate_amounts: bool,
+        /// Amendments proposed by one party and awaiting the other's signature.
+        pending_amendments: Mapping<EscrowId, Amendment>,
+        /// Amendments applied to each escrow, oldest first.
+        amendment_history: Mapping<EscrowId, Vec<Amendment>>,
+        /// The escrows in each state, one bit per ID in words of `STATE_WORD_BITS` IDs.
+        state_words: Mapping<(EscrowState, EscrowId), u128>,
+        /// The number of escrows in each state's index.
+        state_counts: Mapping<EscrowState, u32>,
+        /// The most recent state changes of each escrow, oldest first.
+        history: Mapping<EscrowId, Vec<StateChange>>,
+        /// The number of completed and canceled funded escrows of each seller.
+        reputation: Mapping<AccountId, (u64, u64)>,
+        /// Settlements proposed by one party, awaiting the other party's acceptance.
+        pending_settlements: Mapping<EscrowId, Settlement>,
+        /// Requested release deadline extensions and the party that requested them.
+        pending_extensions: Mapping<EscrowId, (u64, AccountId)>,
+        /// The arbiter resolving disputes of all escrows, if one was set at deployment.
+        default_arbiter: Option<AccountId>,
+        /// Refunds that could not be transferred on cancellation, awaiting `claim_refund`.
+        pending_refunds: Mapping<AccountId, Balance>,
+        /// The sum of the refunds awaiting `claim_refund`.
+        total_pending_refunds: Balance,
+        /// The sum of the seller stakes and dispute deposits the contract holds, included in `total_locked`.
+        total_bonds: Balance,
+        /// The account that deployed the contract and manages platform fees.
+        owner: AccountId,
+        /// The platform fee in basis points, taken from payouts to the seller.
+        fee_bps: u16,
+        /// Platform fees in basis points for buyers who pay a different fee than `fee_bps`.
+        fee_overrides: Mapping<AccountId, u16>,
+        /// The XOR of the hashes of all `(account, fee_bps)` fee overrides, so `config_hash` covers
+        /// them without iterating the mapping.
+        fee_override_digest: [u8; 32],
+        /// Escrow templates, by ID.
+        templates: Mapping<TemplateId, Template>,
+        /// The ID assigned to the next template.
+        next_template_id: TemplateId,
+        /// The IDs of the escrows of each buyer, in creation order.
+        buyer_escrows: Mapping<(AccountId, u32), EscrowId>,
+        /// The number of escrows listed for each buyer.
+        buyer_counts: Mapping<AccountId, u32>,
+        /// The latest position of each escrow in its buyer's listing.
+        buyer_positions: Mapping<(AccountId, EscrowId), u32>,
+        /// The IDs of the escrows of each seller, in creation order.
+        seller_escrows: Mapping<(AccountId, u32), EscrowId>,
+        /// The number of escrows listed for each seller.
+        seller_counts: Mapping<AccountId, u32>,
+        /// The position of each escrow in its seller's listing.
+        seller_positions: Mapping<(AccountId, EscrowId), u32>,
+        /// The maximum number of open escrows per buyer, or 0 for no limit.
+        max_open_per_buyer: u32,
+        /// The number of escrows of each buyer that are not completed or canceled.
+        open_counts: Mapping<AccountId, u32>,
+        /// The sequence number given to the next emitted event.
+        seq: u64,
+        /// Proposed arbiters and the party that proposed them.
+        pending_arbiters: Mapping<EscrowId, (AccountId, AccountId)>,
+        /// Whether IDs of discarded escrows are reused for new escrows.
+        recycle_ids: bool,
+        /// IDs of discarded escrows awaiting reuse, as a stack.
+        free_ids: Mapping<u32, EscrowId>,
+        /// The number of IDs in `free_ids`.
+        free_count: u32,
+        /// Escrows of at least this amount need the buyer's extra confirmation before release, or 0 to disable.
+        high_value_threshold: Balance,
+        /// How long after funding the buyer cannot cancel, in each escrow's deadline kind.
+        refund_lock_period: u64,
+        /// Settled escrows with their settlement timestamps, in settlement order.
+        settled: Mapping<u32, (u64, EscrowId)>,
+        /// The number of entries in `settled`.
+        settled_count: u32,
+        /// Whether the owner has shut the contract down, freezing funds until they are drained.
+        shut_down: bool,
+        /// The ID up to which the emergency drain has checked created escrows for deposits.
+        drain_cursor: EscrowId,
+        /// The admins confirming sensitive owner actions.
+        admins: Mapping<AccountId, ()>,
+        /// The number of admin confirmations an action needs, or 0 while no admins are set.
+        admin_threshold: u32,
+        /// Admin actions proposed with `propose_admin_action`.
+        proposals: Mapping<ProposalId, AdminProposal>,
+        /// The admins that confirmed each proposal.
+        proposal_confirmations: Mapping<(ProposalId, AccountId), ()>,
+        /// The ID assigned to the next admin proposal.
+        next_proposal_id: ProposalId,
+        /// The hash of the admins given to `set_admins`, so `config_hash` covers them without
+        /// iterating the mapping.
+        admins_digest: [u8; 32],
+        /// The code hash last set with `set_code_hash`, or `None` while the deployed code runs.
+        code_hash: Option<Hash>,
+        /// The sellers each buyer allows, at most `MAX_ALLOWED_SELLERS`.
+        allowed_sellers: Mapping<AccountId, Vec<AccountId>>,
+        /// The buyers that only deal with sellers on their allowlist.
+        allowlist_enabled: Mapping<AccountId, ()>,
     }
 
     //----------------------------------
@@ -61,6 +352,54 @@ mod escrow_smart_contract {
             Self {
                 next_id: 0,
                 escrows: Mapping::new(),
+                total_locked: 0,
+                obfuscate_amounts: false,
+                pending_amendments: Mapping::default(),
+                amendment_history: Mapping::default(),
+                state_words: Mapping::default(),
+                state_counts: Mapping::default(),
+                history: Mapping::default(),
+                reputation: Mapping::default(),
+                pending_settlements: Mapping::default(),
+                pending_extensions: Mapping::default(),
+                default_arbiter: None,
+                pending_refunds: Mapping::default(),
+                total_pending_refunds: 0,
+                total_bonds: 0,
+                owner: AccountId::from([0u8; 32]),
+                fee_bps: 0,
+                fee_overrides: Mapping::default(),
+                fee_override_digest: [0u8; 32],
+                templates: Mapping::default(),
+                next_template_id: 0,
+                buyer_escrows: Mapping::default(),
+                buyer_counts: Mapping::default(),
+                buyer_positions: Mapping::default(),
+                seller_escrows: Mapping::default(),
+                seller_counts: Mapping::default(),
+                seller_positions: Mapping::default(),
+                max_open_per_buyer: 0,
+                open_counts: Mapping::default(),
+                seq: 0,
+                pending_arbiters: Mapping::default(),
+                recycle_ids: false,
+                free_ids: Mapping::default(),
+                free_count: 0,
+                high_value_threshold: 0,
+                refund_lock_period: 0,
+                settled: Mapping::default(),
+                settled_count: 0,
+                shut_down: false,
+                drain_cursor: 0,
+                admins: Mapping::default(),
+                admin_threshold: 0,
+                proposals: Mapping::default(),
+                proposal_confirmations: Mapping::default(),
+                next_proposal_id: 0,
+                admins_digest: [0u8; 32],
+                code_hash: None,
+                allowed_sellers: Mapping::default(),
+                allowlist_enabled: Mapping::default(),
             }
         }
     }
@@ -81,6 +420,287 @@ mod escrow_smart_contract {
         seller_approved: bool,
         /// The current state of the escrow.
         state: EscrowState,
+        /// The optional terms agreed at initiation.
+        terms: EscrowTerms,
+        /// When the buyer approved the transaction, if they have, measured in the escrow's deadline kind.
+        buyer_approved_at: Option<u64>,
+        /// When the seller approved the transaction, if they have, measured in the escrow's deadline kind.
+        seller_approved_at: Option<u64>,
+        /// A new amount proposed by the seller, pending the buyer's acceptance.
+        proposed_amount: Option<Balance>,
+        /// How the payout is split among several recipients. Empty when the seller receives it all.
+        recipients: Vec<(AccountId, Balance)>,
+        /// How many deposits were rejected by `deposit_assets_or_return` for not matching the amount.
+        deposit_mismatches: u32,
+        /// Where refunds are sent instead of the buyer, if set.
+        refund_address: Option<AccountId>,
+        /// How refunds are split among several accounts, in basis points. Takes precedence over `refund_address`.
+        refund_splits: Vec<(AccountId, u16)>,
+        /// Whether funds were ever deposited into the escrow.
+        ever_funded: bool,
+        /// When the escrow was funded, if it was, measured in the escrow's deadline kind.
+        funded_at: Option<u64>,
+        /// The arbiter both parties agreed on after creation, overriding the default arbiter.
+        arbiter: Option<AccountId>,
+        /// The amount deposited so far, equal to `amount` once funded.
+        deposited: Balance,
+        /// An alternate account receiving the seller's payouts.
+        payout_address: Option<AccountId>,
+        /// When the escrow was last disputed, in its deadline kind.
+        disputed_at: Option<u64>,
+        /// Whether the buyer confirmed the release of a high-value escrow.
+        high_value_confirmed: bool,
+        /// The stake the seller deposits in a mutual-stake escrow, or 0 if the seller stakes nothing.
+        seller_amount: Balance,
+        /// Whether the seller deposited its stake.
+        seller_deposited: bool,
+        /// The block timestamp at which the escrow was completed or canceled.
+        settled_at: Option<u64>,
+        /// Whether the oracle confirmed delivery.
+        delivery_confirmed: bool,
+        /// The end of the return window, set when the escrow completes with a `return_window` term.
+        return_deadline: Option<u64>,
+        /// Whether the buyer requested a return.
+        return_requested: bool,
+        /// What the seller's recipients received on completion, net of the platform fee.
+        seller_received: Balance,
+        /// Whether the escrow counts towards the buyer's open escrows, which an invoice only
+        /// does once the buyer deposits.
+        open_counted: bool,
+        /// Whether the buyer posted its dispute deposit.
+        buyer_dispute_deposited: bool,
+        /// Whether the seller posted its dispute deposit.
+        seller_dispute_deposited: bool,
+    }
+
+    /// A reusable seller and amount from which its owner can initiate escrows.
+    #[derive(Debug, Clone, PartialEq, Eq, scale::Encode, scale::Decode)]
+    #[cfg_attr(feature = "std", derive(scale_info::TypeInfo, ink::storage::traits::StorageLayout))]
+    pub struct Template {
+        /// The account that created the template and acts as buyer.
+        owner: AccountId,
+        /// The account ID of the seller.
+        seller: AccountId,
+        /// The agreed amount to be transferred.
+        amount: Balance,
+    }
+
+    /// A sensitive owner action that requires the admins' confirmations once admins are set.
+    #[derive(Debug, Clone, Copy, PartialEq, Eq, scale::Encode, scale::Decode)]
+    #[cfg_attr(feature = "std", derive(scale_info::TypeInfo, ink::storage::traits::StorageLayout))]
+    pub enum AdminAction {
+        /// Sets the platform fee, as `set_fee`.
+        SetFee(u16),
+        /// Shuts the contract down, as `shutdown`.
+        Shutdown,
+        /// Drains a batch of escrows of the shut-down contract to an account, as `emergency_drain`.
+        EmergencyDrain(AccountId, u32),
+        /// Replaces the contract code, as `set_code_hash`.
+        SetCodeHash(Hash),
+        /// Sets or removes a buyer's fee override, as `set_fee_override`.
+        SetFeeOverride(AccountId, u16),
+        /// Sets the open escrow limit per buyer, as `set_max_open_per_buyer`.
+        SetMaxOpenPerBuyer(u32),
+        /// Enables or disables ID recycling, as `set_id_recycling`.
+        SetIdRecycling(bool),
+        /// Sets the high-value threshold, as `set_high_value_threshold`.
+        SetHighValueThreshold(Balance),
+        /// Sets the refund lock period, as `set_refund_lock_period`.
+        SetRefundLockPeriod(u64),
+    }
+
+    /// An admin action awaiting confirmations.
+    #[derive(Debug, Clone, PartialEq, Eq, scale::Encode, scale::Decode)]
+    #[cfg_attr(feature = "std", derive(scale_info::TypeInfo, ink::storage::traits::StorageLayout))]
+    pub struct AdminProposal {
+        /// The proposed action.
+        action: AdminAction,
+        /// The number of admins that confirmed the action, including the proposer.
+        confirmations: u32,
+        /// Whether the action has been executed.
+        executed: bool,
+    }
+
+    /// A recorded transition of an escrow from one state to another.
+    #[derive(Debug, Clone, PartialEq, Eq, scale::Encode, scale::Decode)]
+    #[cfg_attr(feature = "std", derive(scale_info::TypeInfo, ink::storage::traits::StorageLayout))]
+    pub struct StateChange {
+        /// The state before the transition.
+        from_state: EscrowState,
+        /// The state after the transition.
+        to_state: EscrowState,
+        /// The block timestamp of the transition.
+        timestamp: Timestamp,
+        /// The account whose call caused the transition.
+        actor: AccountId,
+    }
+
+    /// A proposal to settle an escrow by splitting its amount between the buyer and the seller.
+    #[derive(Debug, Clone, PartialEq, Eq, scale::Encode, scale::Decode)]
+    #[cfg_attr(feature = "std", derive(scale_info::TypeInfo, ink::storage::traits::StorageLayout))]
+    pub struct Settlement {
+        /// The share refunded to the buyer.
+        buyer_share: Balance,
+        /// The share paid to the seller.
+        seller_share: Balance,
+        /// The party that proposed the settlement.
+        proposer: AccountId,
+    }
+
+    /// An amendment to the terms of an escrow, referencing a signed off-chain document.
+    #[derive(Debug, Clone, PartialEq, Eq, scale::Encode, scale::Decode)]
+    #[cfg_attr(feature = "std", derive(scale_info::TypeInfo, ink::storage::traits::StorageLayout))]
+    pub struct Amendment {
+        /// The hash of the signed amendment document.
+        amendment_hash: Hash,
+        /// The amended amount.
+        amount: Balance,
+        /// The amended release deadline, interpreted per the escrow's deadline kind.
+        release_deadline: Option<u64>,
+        /// The party that proposed the amendment.
+        proposer: AccountId,
+    }
+
+    /// Which approvals are required to release the funds to the seller.
+    #[derive(Debug, Default, Clone, Copy, PartialEq, Eq, scale::Encode, scale::Decode)]
+    #[cfg_attr(feature = "std", derive(scale_info::TypeInfo, ink::storage::traits::StorageLayout))]
+    pub enum ApprovalMode {
+        /// Both the buyer and the seller must approve.
+        #[default]
+        BothParties = 0,
+        /// The buyer's approval alone releases the funds.
+        BuyerOnly = 1,
+        /// The seller's approval alone releases the funds.
+        SellerOnly = 2,
+    }
+
+    /// Automatic actions that become available to anyone once their time has come.
+    #[derive(Debug, Clone, Copy, PartialEq, Eq, scale::Encode, scale::Decode)]
+    #[cfg_attr(feature = "std", derive(scale_info::TypeInfo))]
+    pub enum AutoAction {
+        /// The escrow can be swept back to the buyer with `sweep_expired`.
+        Sweep = 0,
+        /// An approval can be cleared with `prune_stale_approval`.
+        ApprovalExpiry = 1,
+        /// The escrow can be released to the seller with `finalize`.
+        Finalize = 2,
+    }
+
+    /// The clock an escrow's deadlines are measured against.
+    #[derive(Debug, Default, Clone, Copy, PartialEq, Eq, scale::Encode, scale::Decode)]
+    #[cfg_attr(feature = "std", derive(scale_info::TypeInfo, ink::storage::traits::StorageLayout))]
+    pub enum DeadlineKind {
+        /// Deadlines are block timestamps and durations are in milliseconds.
+        #[default]
+        Timestamp = 0,
+        /// Deadlines are block numbers and durations are in blocks.
+        BlockNumber = 1,
+    }
+
+    /// Optional terms that can be chosen by the buyer when initiating an escrow.
+    #[derive(Debug, Default, Clone, PartialEq, Eq, scale::Encode, scale::Decode)]
+    #[cfg_attr(feature = "std", derive(scale_info::TypeInfo, ink::storage::traits::StorageLayout))]
+    pub struct EscrowTerms {
+        /// How long an approval stays valid. `None` means approvals never go stale.
+        /// `complete_escrow` does not count stale approvals, so they must be given again.
+        pub approval_ttl: Option<u64>,
+        /// After this moment a still funded escrow can be swept back to the buyer.
+        pub release_deadline: Option<u64>,
+        /// The reward paid out of the escrow amount to whoever sweeps an expired escrow.
+        pub keeper_reward: Balance,
+        /// Funds are not released to the seller before this block number, even if both parties approved.
+        pub release_after_block: Option<BlockNumber>,
+        /// Which approvals release the funds to the seller.
+        pub approval_mode: ApprovalMode,
+        /// After the buyer approves, anyone may release the funds to the seller once this long has passed.
+        pub auto_release_period: Option<u64>,
+        /// The share of a funded escrow, in basis points, paid to the seller when the buyer cancels.
+        pub cancellation_penalty_bps: u16,
+        /// Whether the deadline, period and TTL terms above are measured in milliseconds or blocks.
+        pub deadline_kind: DeadlineKind,
+        /// A funded escrow can only be canceled by the seller once this long has passed since funding.
+        pub seller_cancel_delay: Option<u64>,
+        /// The share of a disputed escrow, in basis points, paid to the arbiter who resolves it.
+        pub arbiter_fee_bps: u16,
+        /// After this moment the escrow can no longer be funded.
+        pub deposit_deadline: Option<u64>,
+        /// How long after a dispute is raised the buyer may reclaim the funds if it is still unresolved.
+        pub dispute_timeout: Option<u64>,
+        /// The deposit each party posts with `post_dispute_deposit` once the escrow is disputed.
+        /// The ruling refunds the winner's deposit and forfeits the loser's to the arbiter.
+        pub dispute_deposit: Balance,
+        /// The oracle that must confirm delivery before the funds are released to the seller.
+        pub oracle: Option<AccountId>,
+        /// How long after completion the buyer may request a return, which the seller must approve.
+        pub return_window: Option<u64>,
+        /// A short human-readable note that helps recognize the escrow in block explorers, at most
+        /// `MAX_MEMO_LEN` bytes. It is echoed in `Initiated`.
+        pub memo: Option<Vec<u8>>,
+    }
+
+    /// How an account relates to an escrow.
+    #[derive(Debug, Clone, Copy, PartialEq, Eq, scale::Encode, scale::Decode)]
+    #[cfg_attr(feature = "std", derive(scale_info::TypeInfo))]
+    pub enum Role {
+        /// The account is the buyer.
+        Buyer = 0,
+        /// The account is the seller.
+        Seller = 1,
+        /// The account resolves disputes of the escrow.
+        Arbiter = 2,
+        /// The account takes no part in the escrow.
+        Observer = 3,
+    }
+
+    /// The participants of an escrow transaction.
+    #[derive(Debug, PartialEq, Eq, scale::Encode, scale::Decode)]
+    #[cfg_attr(feature = "std", derive(scale_info::TypeInfo))]
+    pub struct Participants {
+        /// The account ID of the buyer.
+        buyer: AccountId,
+        /// The account ID of the seller.
+        seller: AccountId,
+        /// The account ID of the arbiter, if one is assigned.
+        arbiter: Option<AccountId>,
+    }
+
+    /// The contract's obligations, broken down by category, as returned by `get_liabilities`.
+    #[derive(Debug, PartialEq, Eq, scale::Encode, scale::Decode)]
+    #[cfg_attr(feature = "std", derive(scale_info::TypeInfo))]
+    pub struct Liabilities {
+        /// The buyers' amounts held by funded escrows, and installments of escrows not yet funded.
+        locked_principal: Balance,
+        /// The seller stakes held by mutual-stake escrows and the posted dispute deposits.
+        posted_bonds: Balance,
+        /// The refunds awaiting `claim_refund`.
+        pending_withdrawals: Balance,
+    }
+
+    /// Why an escrow was canceled, as published in the `Canceled` event.
+    #[derive(Debug, Clone, Copy, PartialEq, Eq, scale::Encode, scale::Decode)]
+    #[cfg_attr(feature = "std", derive(scale_info::TypeInfo))]
+    pub enum CancelReason {
+        /// The buyer canceled the escrow with `cancel_escrow`.
+        BuyerWithdrew = 0,
+        /// The seller canceled the escrow with `cancel_escrow`.
+        SellerDeclined = 1,
+        /// A deadline passed: the release deadline of `sweep_expired` or the dispute timeout.
+        Expired = 2,
+        /// Both parties agreed to cancel the escrow. Reserved, as no cancellation path requires
+        /// both parties yet.
+        MutualConsent = 3,
+        /// The owner drained the shut-down contract with `emergency_drain`.
+        Emergency = 4,
+    }
+
+    /// An amount as published in events.
+    #[derive(Debug, PartialEq, Eq, scale::Encode, scale::Decode)]
+    #[cfg_attr(feature = "std", derive(scale_info::TypeInfo))]
+    pub enum EventAmount {
+        /// The raw amount.
+        Clear(Balance),
+        /// The BLAKE2 hash of the SCALE-encoded `(escrow_id, amount)` pair.
+        Commitment(Hash),
     }
 
     /// Event emitted when a new escrow is initiated.
@@ -94,7 +714,11 @@ mod escrow_smart_contract {
         /// The seller's account ID.
         seller: AccountId,
         /// The agreed amount.
-        amount: Balance,
+        amount: EventAmount,
+        /// The memo of the escrow's terms, if one was given.
+        memo: Option<Vec<u8>>,
+        /// The position of this event among all events emitted by the contract.
+        seq: u64,
     }
 
     /// Event emitted when funds are deposited into an escrow.
@@ -104,7 +728,23 @@ mod escrow_smart_contract {
         #[ink(topic)]
         escrow_id: EscrowId,
         /// The deposited amount.
-        amount: Balance,
+        amount: EventAmount,
+        /// The position of this event among all events emitted by the contract.
+        seq: u64,
+    }
+
+    /// Event emitted when `deposit_assets_or_return` rejects a deposit that does not match the amount owed.
+    #[ink(event)]
+    pub struct DepositRejected {
+        /// The ID of the escrow.
+        #[ink(topic)]
+        escrow_id: EscrowId,
+        /// The value that was sent and returned.
+        sent: Balance,
+        /// The amount still owed.
+        expected: EventAmount,
+        /// The position of this event among all events emitted by the contract.
+        seq: u64,
     }
 
     /// Event emitted when an escrow is completed.
@@ -113,6 +753,13 @@ mod escrow_smart_contract {
         /// The ID of the completed escrow.
         #[ink(topic)]
         escrow_id: EscrowId,
+        /// The account whose approval completed the escrow.
+        #[ink(topic)]
+        approver: AccountId,
+        /// The amount released to the seller.
+        amount: EventAmount,
+        /// The position of this event among all events emitted by the contract.
+        seq: u64,
     }
 
     /// Event emitted when an escrow is canceled.
@@ -121,470 +768,9153 @@ mod escrow_smart_contract {
         /// The ID of the canceled escrow.
         #[ink(topic)]
         escrow_id: EscrowId,
+        /// The account that canceled the escrow.
+        #[ink(topic)]
+        by: AccountId,
+        /// Why the escrow was canceled.
+        reason: CancelReason,
+        /// The amount refunded to the buyer.
+        refunded: EventAmount,
+        /// The cancellation penalty paid to the seller.
+        penalty: EventAmount,
+        /// The position of this event among all events emitted by the contract.
+        seq: u64,
     }
 
-    impl EscrowSmartContract {
-        /// Constructor that initializes a new escrow contract.
-        #[ink(constructor)]
-        pub fn new() -> Self {
-            Self {
-                escrows: Mapping::default(),
-                next_id: 0,
-            }
-        }
+    /// Event emitted when a stale approval is pruned.
+    #[ink(event)]
+    pub struct ApprovalExpired {
+        /// The ID of the escrow.
+        #[ink(topic)]
+        escrow_id: EscrowId,
+        /// The party whose approval expired.
+        party: AccountId,
+        /// The position of this event among all events emitted by the contract.
+        seq: u64,
+    }
 
-        /// Initiates a new escrow transaction.
-        ///
-        /// # Arguments
-        ///
-        /// * `seller` - The account ID of the seller.
-        /// * `amount` - The agreed amount to be transferred.
-        ///
-        /// # Returns
-        ///
-        /// * `Ok(EscrowId)` - The ID of the newly created escrow.
-        /// * `Err(Error)` - An error if the operation failed.
-        #[ink(message)]
-        pub fn initiate_escrow(
-            &mut self,
-            seller: AccountId,
-            amount: Balance
-        ) -> Result<EscrowId, Error> {
-            // Get the caller's account ID (the buyer).
-            let buyer = self.env().caller();
-            // Check if the buyer and seller are the same account.
-            if buyer == seller {
-                return Err(Error::InvalidParticipants);
-            }
+    /// Event emitted when an expired escrow is swept back to the buyer.
+    #[ink(event)]
+    pub struct Swept {
+        /// The ID of the swept escrow.
+        #[ink(topic)]
+        escrow_id: EscrowId,
+        /// The account that swept the escrow and received the keeper reward.
+        keeper: AccountId,
+        /// The position of this event among all events emitted by the contract.
+        seq: u64,
+    }
 
-            // Get the next available escrow ID.
-            let escrow_id = self.next_id;
-            // Increment the next ID, handling potential overflow.
-            self.next_id = escrow_id.checked_add(1).ok_or(Error::IdOverflow)?;
+    /// Event emitted when the seller proposes a new amount.
+    #[ink(event)]
+    pub struct AmountProposed {
+        /// The ID of the escrow.
+        #[ink(topic)]
+        escrow_id: EscrowId,
+        /// The proposed amount.
+        amount: EventAmount,
+        /// The position of this event among all events emitted by the contract.
+        seq: u64,
+    }
 
-            // Create the new escrow data.
-            let escrow = Escrow {
-                buyer,
-                seller,
-                amount,
-                buyer_approved: false,
-                seller_approved: false,
-                state: EscrowState::Created,
-            };
+    /// Event emitted when the buyer accepts a proposed amount.
+    #[ink(event)]
+    pub struct AmountAccepted {
+        /// The ID of the escrow.
+        #[ink(topic)]
+        escrow_id: EscrowId,
+        /// The new agreed amount.
+        amount: EventAmount,
+        /// The position of this event among all events emitted by the contract.
+        seq: u64,
+    }
 
-            // Insert the escrow data into the storage mapping.
-            self.escrows.insert(escrow_id, &escrow);
+    /// Event emitted when the buyer sets a refund address.
+    #[ink(event)]
+    pub struct RefundAddressSet {
+        /// The ID of the escrow.
+        #[ink(topic)]
+        escrow_id: EscrowId,
+        /// The account that will receive refunds.
+        refund_address: AccountId,
+        /// The position of this event among all events emitted by the contract.
+        seq: u64,
+    }
 
-            // Emit an event to notify about the new escrow.
-            self.env().emit_event(Initiated {
-                escrow_id,
-                buyer,
-                seller,
-                amount,
-            });
+    /// Event emitted when one party signs an amendment.
+    #[ink(event)]
+    pub struct AmendmentProposed {
+        /// The ID of the escrow.
+        #[ink(topic)]
+        escrow_id: EscrowId,
+        /// The hash of the signed amendment document.
+        amendment_hash: Hash,
+        /// The party that proposed the amendment.
+        proposer: AccountId,
+        /// The position of this event among all events emitted by the contract.
+        seq: u64,
+    }
 
-            // Return the new escrow ID.
-            Ok(escrow_id)
-        }
+    /// Event emitted when both parties have signed an amendment and it is applied.
+    #[ink(event)]
+    pub struct Amended {
+        /// The ID of the escrow.
+        #[ink(topic)]
+        escrow_id: EscrowId,
+        /// The hash of the signed amendment document.
+        amendment_hash: Hash,
+        /// The position of this event among all events emitted by the contract.
+        seq: u64,
+    }
 
-        /// Deposits funds into an escrow.
-        ///
-        /// # Arguments
-        ///
-        /// * `escrow_id` - The ID of the escrow.
-        ///
-        /// # Returns
-        ///
-        /// * `Ok(())` - If the deposit was successful.
-        /// * `Err(Error)` - An error if the operation failed.
-        #[ink(message, payable)]
-        pub fn deposit_assets(&mut self, escrow_id: EscrowId) -> Result<(), Error> {
-            // Get a mutable reference to the escrow data.
-            let mut escrow = self.escrows.get(escrow_id).ok_or(Error::NotFound)?;
-            // Get the caller's account ID.
-            let caller = self.env().caller();
+    /// Event emitted when a seller creates an invoice escrow.
+    #[ink(event)]
+    pub struct InvoiceCreated {
+        /// The ID of the newly created escrow.
+        #[ink(topic)]
+        escrow_id: EscrowId,
+        /// The buyer's account ID, expected to fund the escrow.
+        buyer: AccountId,
+        /// The seller's account ID.
+        seller: AccountId,
+        /// The requested amount.
+        amount: EventAmount,
+        /// The position of this event among all events emitted by the contract.
+        seq: u64,
+    }
 
-            // Check if the caller is the buyer.
-            if caller != escrow.buyer {
-                return Err(Error::Unauthorized);
-            }
+    /// Event emitted when an escrow is released to the seller after the auto-release period.
+    #[ink(event)]
+    pub struct AutoFinalized {
+        /// The ID of the finalized escrow.
+        #[ink(topic)]
+        escrow_id: EscrowId,
+        /// The account that triggered the release.
+        by: AccountId,
+        /// The position of this event among all events emitted by the contract.
+        seq: u64,
+    }
 
-            // Check if the escrow is in the correct state.
-            if escrow.state != EscrowState::Created {
-                return Err(Error::InvalidState);
-            }
+    /// Event emitted when the buyer sets how refunds are split.
+    #[ink(event)]
+    pub struct RefundSplitsSet {
+        /// The ID of the escrow.
+        #[ink(topic)]
+        escrow_id: EscrowId,
+        /// The accounts receiving refunds and their shares in basis points.
+        splits: Vec<(AccountId, u16)>,
+        /// The position of this event among all events emitted by the contract.
+        seq: u64,
+    }
 
-            // Check if the deposited amount is correct.
-            if self.env().transferred_value() != escrow.amount {
-                return Err(Error::InvalidAmount);
-            }
+    /// Event emitted when a canceled escrow that was never funded is reopened.
+    #[ink(event)]
+    pub struct Reopened {
+        /// The ID of the reopened escrow.
+        #[ink(topic)]
+        escrow_id: EscrowId,
+        /// The position of this event among all events emitted by the contract.
+        seq: u64,
+    }
 
-            // Update the escrow state.
-            escrow.state = EscrowState::Funded;
+    /// Event emitted when a party proposes a split settlement.
+    #[ink(event)]
+    pub struct SettlementProposed {
+        /// The ID of the escrow.
+        #[ink(topic)]
+        escrow_id: EscrowId,
+        /// The share refunded to the buyer.
+        buyer_share: EventAmount,
+        /// The share paid to the seller.
+        seller_share: EventAmount,
+        /// The party that proposed the settlement.
+        proposer: AccountId,
+        /// The position of this event among all events emitted by the contract.
+        seq: u64,
+    }
 
-            // Save changes back to storage
-            self.escrows.insert(escrow_id, &escrow);
+    /// Event emitted when a split settlement is accepted and paid out.
+    #[ink(event)]
+    pub struct SettlementExecuted {
+        /// The ID of the escrow.
+        #[ink(topic)]
+        escrow_id: EscrowId,
+        /// The share refunded to the buyer.
+        buyer_share: EventAmount,
+        /// The share paid to the seller.
+        seller_share: EventAmount,
+        /// The position of this event among all events emitted by the contract.
+        seq: u64,
+    }
 
-            // Emit an event to notify about the deposit.
-            self.env().emit_event(Deposited {
-                escrow_id,
-                amount: escrow.amount,
-            });
+    /// Event emitted when a party requests a later release deadline.
+    #[ink(event)]
+    pub struct ExtensionRequested {
+        /// The ID of the escrow.
+        #[ink(topic)]
+        escrow_id: EscrowId,
+        /// The requested release deadline.
+        new_deadline: u64,
+        /// The party that requested the extension.
+        requester: AccountId,
+        /// The position of this event among all events emitted by the contract.
+        seq: u64,
+    }
 
-            Ok(())
-        }
+    /// Event emitted when both parties agreed on a later release deadline.
+    #[ink(event)]
+    pub struct ExtensionApplied {
+        /// The ID of the escrow.
+        #[ink(topic)]
+        escrow_id: EscrowId,
+        /// The new release deadline.
+        new_deadline: u64,
+        /// The position of this event among all events emitted by the contract.
+        seq: u64,
+    }
 
-        /// Completes an escrow transaction if both parties have approved.
-        ///
-        /// # Arguments
-        ///
-        /// * `escrow_id` - The ID of the escrow.
-        ///
-        /// # Returns
-        ///
-        /// * `Ok(())` - If the escrow was successfully completed.
-        /// * `Err(Error)` - An error if the operation failed.
-        #[ink(message)]
-        pub fn complete_escrow(&mut self, escrow_id: EscrowId) -> Result<(), Error> {
-            // Get owned Escrow value
-            let mut escrow = self.escrows.get(escrow_id).ok_or(Error::NotFound)?;
+    /// Event emitted when a party disputes a funded escrow.
+    #[ink(event)]
+    pub struct DisputeRaised {
+        /// The ID of the escrow.
+        #[ink(topic)]
+        escrow_id: EscrowId,
+        /// The party that raised the dispute.
+        by: AccountId,
+        /// The position of this event among all events emitted by the contract.
+        seq: u64,
+    }
 
-            // Check if the escrow is in the correct state.
-            if escrow.state != EscrowState::Funded {
-                return Err(Error::InvalidState);
-            }
+    /// Event emitted when a party posts its dispute deposit.
+    #[ink(event)]
+    pub struct DisputeDepositPosted {
+        /// The ID of the escrow.
+        #[ink(topic)]
+        escrow_id: EscrowId,
+        /// The party that posted the deposit.
+        party: AccountId,
+        /// The deposit.
+        amount: EventAmount,
+        /// The position of this event among all events emitted by the contract.
+        seq: u64,
+    }
 
-            // Pass owned value to approve function and get updated escrow
-            escrow = self.approve(escrow, self.env().caller())?;
+    /// Event emitted when the arbiter resolves a dispute.
+    #[ink(event)]
+    pub struct DisputeResolved {
+        /// The ID of the escrow.
+        #[ink(topic)]
+        escrow_id: EscrowId,
+        /// The amount refunded to the buyer.
+        buyer_amount: EventAmount,
+        /// The amount released to the seller.
+        seller_amount: EventAmount,
+        /// The fee paid to the arbiter.
+        arbiter_fee: EventAmount,
+        /// The position of this event among all events emitted by the contract.
+        seq: u64,
+    }
 
-            // Save changes back to storage
-            self.escrows.insert(escrow_id, &escrow);
+    /// Event emitted when a refund, penalty or returned stake of a canceled escrow could not be
+    /// transferred and is kept for the recipient to claim.
+    #[ink(event)]
+    pub struct RefundPending {
+        /// The ID of the escrow.
+        #[ink(topic)]
+        escrow_id: EscrowId,
+        /// The account the refund is owed to.
+        #[ink(topic)]
+        account: AccountId,
+        /// The refund amount.
+        amount: EventAmount,
+        /// The position of this event among all events emitted by the contract.
+        seq: u64,
+    }
 
-            // Check if both parties have approved.
-            if escrow.buyer_approved && escrow.seller_approved {
-                // Transfer the funds to the seller.
-                self
-                    .env()
-                    .transfer(escrow.seller, escrow.amount)
-                    .map_err(|_| Error::TransferFailed)?;
+    /// Event emitted when the owner changes the platform fee.
+    #[ink(event)]
+    pub struct FeeUpdated {
+        /// The previous platform fee in basis points.
+        old_fee_bps: u16,
+        /// The new platform fee in basis points.
+        new_fee_bps: u16,
+        /// The position of this event among all events emitted by the contract.
+        seq: u64,
+    }
 
-                // Update the escrow state.
-                escrow.state = EscrowState::Completed;
+    /// Event emitted when the owner sets or removes a buyer's fee override.
+    #[ink(event)]
+    pub struct FeeOverrideSet {
+        /// The buyer whose fee is overridden.
+        #[ink(topic)]
+        account: AccountId,
+        /// The buyer's previous fee in basis points, or `None` if there was no override.
+        old_fee_bps: Option<u16>,
+        /// The buyer's fee in basis points, or `None` if the override was removed.
+        fee_bps: Option<u16>,
+        /// The position of this event among all events emitted by the contract.
+        seq: u64,
+    }
 
-                // Save changes back to storage
-                self.escrows.insert(escrow_id, &escrow);
+    /// Event emitted when a buyer deletes an escrow that was never funded.
+    #[ink(event)]
+    pub struct Discarded {
+        /// The ID of the discarded escrow.
+        #[ink(topic)]
+        escrow_id: EscrowId,
+        /// The position of this event among all events emitted by the contract.
+        seq: u64,
+    }
 
-                // Emit an event to notify about the completion.
-                self.env().emit_event(Completed { escrow_id });
-            }
+    /// Event emitted when the owner changes a limit: the maximum number of open escrows per
+    /// buyer or the high-value confirmation threshold.
+    #[ink(event)]
+    pub struct LimitsUpdated {
+        /// The previous maximum number of open escrows per buyer, or 0 for no limit.
+        old_max_open_per_buyer: u32,
+        /// The new maximum number of open escrows per buyer, or 0 for no limit.
+        new_max_open_per_buyer: u32,
+        /// The previous high-value threshold, or 0 if disabled.
+        old_high_value_threshold: Balance,
+        /// The new high-value threshold, or 0 if disabled.
+        new_high_value_threshold: Balance,
+        /// The position of this event among all events emitted by the contract.
+        seq: u64,
+    }
 
-            Ok(())
+    /// Event emitted when the arbiter resolving disputes of all escrows changes.
+    #[ink(event)]
+    pub struct ArbiterChanged {
+        /// The previous arbiter, if any.
+        old: Option<AccountId>,
+        /// The new arbiter, if any.
+        new: Option<AccountId>,
+        /// The position of this event among all events emitted by the contract.
+        seq: u64,
+    }
+
+    /// Event emitted when a party withdraws its approval.
+    #[ink(event)]
+    pub struct ApprovalRevoked {
+        /// The ID of the escrow.
+        #[ink(topic)]
+        escrow_id: EscrowId,
+        /// The party that withdrew its approval.
+        party: AccountId,
+        /// The position of this event among all events emitted by the contract.
+        seq: u64,
+    }
+
+    /// Event emitted when a party proposes an arbiter for an escrow.
+    #[ink(event)]
+    pub struct ArbiterProposed {
+        /// The ID of the escrow.
+        #[ink(topic)]
+        escrow_id: EscrowId,
+        /// The proposed arbiter.
+        arbiter: AccountId,
+        /// The party that proposed the arbiter.
+        proposer: AccountId,
+        /// The position of this event among all events emitted by the contract.
+        seq: u64,
+    }
+
+    /// Event emitted when both parties agreed on an arbiter for an escrow.
+    #[ink(event)]
+    pub struct ArbiterSet {
+        /// The ID of the escrow.
+        #[ink(topic)]
+        escrow_id: EscrowId,
+        /// The arbiter of the escrow.
+        arbiter: AccountId,
+        /// The position of this event among all events emitted by the contract.
+        seq: u64,
+    }
+
+    /// Event emitted when the owner enables or disables reuse of discarded escrow IDs.
+    #[ink(event)]
+    pub struct IdRecyclingSet {
+        /// Whether discarded IDs are reused.
+        enabled: bool,
+        /// The position of this event among all events emitted by the contract.
+        seq: u64,
+    }
+
+    /// Event emitted when an installment is deposited into an escrow that is not yet fully funded.
+    #[ink(event)]
+    pub struct PartialDeposit {
+        /// The ID of the escrow.
+        #[ink(topic)]
+        escrow_id: EscrowId,
+        /// The deposited installment.
+        amount: EventAmount,
+        /// The amount still to be deposited.
+        remaining: EventAmount,
+        /// The position of this event among all events emitted by the contract.
+        seq: u64,
+    }
+
+    /// Event emitted when the seller sets a payout address.
+    #[ink(event)]
+    pub struct PayoutAddressSet {
+        /// The ID of the escrow.
+        #[ink(topic)]
+        escrow_id: EscrowId,
+        /// The account that will receive the seller's payouts.
+        payout_address: AccountId,
+        /// The position of this event among all events emitted by the contract.
+        seq: u64,
+    }
+
+    /// Event emitted when the buyer reclaims a dispute that was not resolved in time.
+    #[ink(event)]
+    pub struct DisputeTimedOut {
+        /// The ID of the escrow.
+        #[ink(topic)]
+        escrow_id: EscrowId,
+        /// The amount refunded.
+        refunded: EventAmount,
+        /// The position of this event among all events emitted by the contract.
+        seq: u64,
+    }
+
+    /// Event emitted on every state transition of an escrow, alongside the specific event.
+    #[ink(event)]
+    pub struct StateChanged {
+        /// The ID of the escrow.
+        #[ink(topic)]
+        escrow_id: EscrowId,
+        /// The previous state.
+        from: EscrowState,
+        /// The new state.
+        to: EscrowState,
+        /// The position of this event among all events emitted by the contract.
+        seq: u64,
+    }
+
+    /// Event emitted when the buyer confirms the release of a high-value escrow.
+    #[ink(event)]
+    pub struct HighValueConfirmed {
+        /// The ID of the escrow.
+        #[ink(topic)]
+        escrow_id: EscrowId,
+        /// The position of this event among all events emitted by the contract.
+        seq: u64,
+    }
+
+    /// Event emitted when the buyer transfers its position in an escrow to another account.
+    #[ink(event)]
+    pub struct BuyerTransferred {
+        /// The ID of the escrow.
+        #[ink(topic)]
+        escrow_id: EscrowId,
+        /// The previous buyer.
+        from: AccountId,
+        /// The new buyer.
+        to: AccountId,
+        /// The position of this event among all events emitted by the contract.
+        seq: u64,
+    }
+
+    /// Event emitted when the seller deposits its stake into a mutual-stake escrow.
+    #[ink(event)]
+    pub struct StakeDeposited {
+        /// The ID of the escrow.
+        #[ink(topic)]
+        escrow_id: EscrowId,
+        /// The seller's stake.
+        amount: EventAmount,
+        /// The position of this event among all events emitted by the contract.
+        seq: u64,
+    }
+
+    /// Event emitted when the owner changes the refund lock period.
+    #[ink(event)]
+    pub struct RefundLockPeriodUpdated {
+        /// The previous period.
+        old_period: u64,
+        /// The new period.
+        new_period: u64,
+        /// The position of this event among all events emitted by the contract.
+        seq: u64,
+    }
+
+    /// Event emitted when the owner shuts the contract down.
+    #[ink(event)]
+    pub struct Shutdown {
+        /// The owner, or the admin executing the proposal, that shut the contract down.
+        #[ink(topic)]
+        by: AccountId,
+        /// The position of this event among all events emitted by the contract.
+        seq: u64,
+    }
+
+    /// Event emitted for each batch of escrows the owner drains from a shut-down contract.
+    #[ink(event)]
+    pub struct EmergencyDrained {
+        /// The account that receives the contract balance.
+        #[ink(topic)]
+        to: AccountId,
+        /// The balance transferred, which is 0 until no escrows remain.
+        amount: Balance,
+        /// The number of escrows holding funds that this batch canceled.
+        canceled: u32,
+        /// The number of escrows still to cancel or check.
+        remaining: u32,
+        /// The position of this event among all events emitted by the contract.
+        seq: u64,
+    }
+
+    /// Event emitted when the owner sets the admins.
+    #[ink(event)]
+    pub struct AdminsSet {
+        /// The admins.
+        admins: Vec<AccountId>,
+        /// The number of confirmations an action needs.
+        threshold: u32,
+        /// The position of this event among all events emitted by the contract.
+        seq: u64,
+    }
+
+    /// Event emitted when an admin proposes an action.
+    #[ink(event)]
+    pub struct AdminActionProposed {
+        /// The ID of the proposal.
+        #[ink(topic)]
+        proposal_id: ProposalId,
+        /// The admin that proposed the action.
+        #[ink(topic)]
+        proposer: AccountId,
+        /// The proposed action.
+        action: AdminAction,
+        /// The position of this event among all events emitted by the contract.
+        seq: u64,
+    }
+
+    /// Event emitted when an admin confirms a proposed action.
+    #[ink(event)]
+    pub struct AdminActionConfirmed {
+        /// The ID of the proposal.
+        #[ink(topic)]
+        proposal_id: ProposalId,
+        /// The admin that confirmed the action.
+        #[ink(topic)]
+        admin: AccountId,
+        /// The number of confirmations so far.
+        confirmations: u32,
+        /// The position of this event among all events emitted by the contract.
+        seq: u64,
+    }
+
+    /// Event emitted when a confirmed action is executed.
+    #[ink(event)]
+    pub struct AdminActionExecuted {
+        /// The ID of the proposal.
+        #[ink(topic)]
+        proposal_id: ProposalId,
+        /// The position of this event among all events emitted by the contract.
+        seq: u64,
+    }
+
+    /// Event emitted when the oracle of an escrow confirms delivery.
+    #[ink(event)]
+    pub struct DeliveryConfirmed {
+        /// The ID of the escrow.
+        #[ink(topic)]
+        escrow_id: EscrowId,
+        /// The oracle that confirmed delivery.
+        #[ink(topic)]
+        oracle: AccountId,
+        /// The position of this event among all events emitted by the contract.
+        seq: u64,
+    }
+
+    /// Event emitted when the contract code is replaced.
+    #[ink(event)]
+    pub struct CodeUpgraded {
+        /// The code hash before the upgrade.
+        old_code_hash: Hash,
+        /// The code hash after the upgrade.
+        new_code_hash: Hash,
+        /// The position of this event among all events emitted by the contract.
+        seq: u64,
+    }
+
+    /// Event emitted when the buyer requests the return of a completed escrow.
+    #[ink(event)]
+    pub struct ReturnRequested {
+        /// The ID of the escrow.
+        #[ink(topic)]
+        escrow_id: EscrowId,
+        /// The position of this event among all events emitted by the contract.
+        seq: u64,
+    }
+
+    /// Event emitted when the seller approves a return and repays the buyer.
+    #[ink(event)]
+    pub struct Returned {
+        /// The ID of the returned escrow.
+        #[ink(topic)]
+        escrow_id: EscrowId,
+        /// The amount repaid to the buyer.
+        amount: EventAmount,
+        /// The position of this event among all events emitted by the contract.
+        seq: u64,
+    }
+
+    /// Event emitted when a buyer adds a seller to or removes it from its allowlist.
+    #[ink(event)]
+    pub struct AllowedSellerUpdated {
+        /// The buyer owning the allowlist.
+        #[ink(topic)]
+        buyer: AccountId,
+        /// The seller that was added or removed.
+        #[ink(topic)]
+        seller: AccountId,
+        /// Whether the seller is now allowed.
+        allowed: bool,
+        /// The position of this event among all events emitted by the contract.
+        seq: u64,
+    }
+
+    /// Event emitted when a buyer enables or disables its seller allowlist.
+    #[ink(event)]
+    pub struct AllowlistToggled {
+        /// The buyer owning the allowlist.
+        #[ink(topic)]
+        buyer: AccountId,
+        /// Whether the allowlist is now enforced.
+        enabled: bool,
+        /// The position of this event among all events emitted by the contract.
+        seq: u64,
+    }
+
+    impl EscrowSmartContract {
+        /// Constructor that initializes a new escrow contract.
+        #[ink(constructor)]
+        pub fn new() -> Self {
+            Self { owner: Self::env().caller(), ..Self::default() }
         }
 
-        /// Cancels an escrow transaction and refunds the buyer if funded.
+        /// Constructor that initializes a new escrow contract which hides amounts in events.
+        ///
+        /// Events carry a commitment hash of the amount instead of the raw value, while
+        /// `get_escrow` and `simulate_completion` only answer an escrow's participants, and
+        /// `locked_balance_of` and `pending_withdrawal_of` only the account itself. Contract-wide
+        /// totals stay readable, as the contract's balance is public anyway.
+        #[ink(constructor)]
+        pub fn new_with_obfuscated_amounts() -> Self {
+            let mut contract = Self::new();
+            contract.obfuscate_amounts = true;
+            contract
+        }
+
+        /// Constructor that initializes a new escrow contract with an arbiter for all escrows.
+        ///
+        /// The arbiter cannot take part as buyer or seller in any escrow of this contract.
         ///
         /// # Arguments
         ///
-        /// * `escrow_id` - The ID of the escrow.
+        /// * `arbiter` - The account ID of the arbiter.
         ///
         /// # Returns
         ///
-        /// * `Ok(())` - If the escrow was successfully canceled.
-        /// * `Err(Error)` - An error if the operation failed.
-        #[ink(message)]
-        pub fn cancel_escrow(&mut self, escrow_id: EscrowId) -> Result<(), Error> {
-            // Get a mutable reference to the escrow data.
-            let mut escrow = self.escrows.get(escrow_id).ok_or(Error::NotFound)?;
-            // Get the caller's account ID.
-            let caller = self.env().caller();
-
-            // Check if the caller is the buyer or the seller.
-            if caller != escrow.buyer && caller != escrow.seller {
-                return Err(Error::Unauthorized);
-            }
+        /// * `Ok(Self)` - The new contract.
+        /// * `Err(Error)` - `ZeroAddress` if the arbiter is the zero account.
+        #[ink(constructor)]
+        pub fn new_with_arbiter(arbiter: AccountId) -> Result<Self, Error> {
+            // Check if the arbiter is a real account.
+            Self::ensure_not_zero(arbiter)?;
 
-            // Check if the escrow is already completed.
-            if escrow.state == EscrowState::Completed {
-                return Err(Error::InvalidState);
-            }
+            let mut contract = Self::new();
+            contract.default_arbiter = Some(arbiter);
 
-            // Refund buyer if escrow was funded
-            if escrow.state == EscrowState::Funded {
-                self
-                    .env()
-                    .transfer(escrow.buyer, escrow.amount)
-                    .map_err(|_| Error::TransferFailed)?;
-            }
+            // Emit an event to notify about the arbiter, as the first event of the contract.
+            contract.seq = 1;
+            Self::env().emit_event(ArbiterChanged { seq: 0, old: None, new: Some(arbiter) });
 
-            // Update the escrow state.
-            escrow.state = EscrowState::Canceled;
+            Ok(contract)
+        }
 
-            // Save the modified escrow back to storage
-            self.escrows.insert(escrow_id, &escrow);
+        /// Constructor that initializes a new escrow contract and registers it with a registry.
+        ///
+        /// Calls the registry's `register` message with the account ID of the new contract,
+        /// so factories can keep track of their deployed instances.
+        ///
+        /// # Arguments
+        ///
+        /// * `registry` - The account ID of the registry contract.
+        ///
+        /// # Returns
+        ///
+        /// * `Ok(Self)` - The registered contract.
+        /// * `Err(Error)` - `RegistrationFailed` if the registry call failed or reverted.
+        #[ink(constructor)]
+        pub fn new_with_registry(registry: AccountId) -> Result<Self, Error> {
+            let contract = Self::new();
 
-            // Emit an event to notify about the cancellation.
-            self.env().emit_event(Canceled { escrow_id });
+            // Register this instance with the registry.
+            build_call::<Environment>()
+                .call(registry)
+                .exec_input(
+                    ExecutionInput::new(Selector::new(ink::selector_bytes!("register")))
+                        .push_arg(Self::env().account_id())
+                )
+                .returns::<()>()
+                .try_invoke()
+                .map_err(|_| Error::RegistrationFailed)?
+                .map_err(|_| Error::RegistrationFailed)?;
 
-            Ok(())
+            Ok(contract)
         }
 
-        // --- Helper functions ---
-
-        /// Approves an escrow transaction for a given party.
+        /// Initiates a new escrow transaction.
         ///
         /// # Arguments
         ///
-        /// * `escrow` - A mutable reference to the escrow data.
-        /// * `caller` - The account ID of the party approving.
+        /// * `seller` - The account ID of the seller.
+        /// * `amount` - The agreed amount to be transferred.
         ///
         /// # Returns
         ///
-        /// * `Ok(())` - If the approval was successful.
+        /// * `Ok(EscrowId)` - The ID of the newly created escrow.
         /// * `Err(Error)` - An error if the operation failed.
-        fn approve(&mut self, mut escrow: Escrow, caller: AccountId) -> Result<Escrow, Error> {
-            // Match the caller to the buyer or seller.
-            match caller {
-                // If the caller is the buyer.
-                _ if caller == escrow.buyer => {
-                    // Check if the buyer has already approved.
-                    if escrow.buyer_approved {
-                        return Err(Error::AlreadyApproved);
-                    }
-                    // Update the buyer's approval status.
-                    escrow.buyer_approved = true;
-                }
-                // If the caller is the seller.
-                _ if caller == escrow.seller => {
-                    // Check if the seller has already approved.
-                    if escrow.seller_approved {
-                        return Err(Error::AlreadyApproved);
-                    }
-                    // Update the seller's approval status.
-                    escrow.seller_approved = true;
+        #[ink(message)]
+        pub fn initiate_escrow(
+            &mut self,
+            seller: AccountId,
+            amount: Balance
+        ) -> Result<EscrowId, Error> {
+            self.initiate_escrow_with_terms(seller, amount, EscrowTerms::default())
+        }
+
+        /// Initiates a new escrow transaction and funds it in the same call.
+        ///
+        /// Emits both `Initiated` and `Deposited`. The whole call reverts if the transferred
+        /// value is not exactly the agreed amount.
+        ///
+        /// # Arguments
+        ///
+        /// * `seller` - The account ID of the seller.
+        /// * `amount` - The agreed amount to be transferred.
+        ///
+        /// # Returns
+        ///
+        /// * `Ok(EscrowId)` - The ID of the newly created, funded escrow.
+        /// * `Err(Error)` - An error if the operation failed.
+        #[ink(message, payable)]
+        pub fn initiate_and_fund(
+            &mut self,
+            seller: AccountId,
+            amount: Balance
+        ) -> Result<EscrowId, Error> {
+            // Check if the transferred value covers the whole amount, not an installment.
+            if self.env().transferred_value() != amount {
+                return Err(Error::InvalidAmount);
+            }
+
+            let escrow_id = self.initiate_escrow(seller, amount)?;
+            self.deposit_assets(escrow_id)?;
+            Ok(escrow_id)
+        }
+
+        /// Initiates a mutual-stake escrow, in which the seller stakes funds as well.
+        ///
+        /// The buyer deposits `amount` with `deposit_assets` and the seller deposits
+        /// `seller_amount` with `deposit_stake`. On completion the seller receives the buyer's
+        /// amount and the buyer receives the seller's stake. On cancellation each party is
+        /// refunded its own deposit. Dispute rulings and settlements only divide the buyer's
+        /// amount, so they return the stake to the seller.
+        ///
+        /// # Arguments
+        ///
+        /// * `seller` - The account ID of the seller.
+        /// * `amount` - The amount deposited by the buyer.
+        /// * `seller_amount` - The stake deposited by the seller.
+        ///
+        /// # Returns
+        ///
+        /// * `Ok(EscrowId)` - The ID of the newly created escrow.
+        /// * `Err(Error)` - An error if the operation failed.
+        #[ink(message)]
+        pub fn initiate_stake_escrow(
+            &mut self,
+            seller: AccountId,
+            amount: Balance,
+            seller_amount: Balance
+        ) -> Result<EscrowId, Error> {
+            // Check if the seller stakes something.
+            if seller_amount == 0 {
+                return Err(Error::InvalidAmount);
+            }
+
+            let escrow_id = self.initiate_escrow(seller, amount)?;
+            let mut escrow = self.escrows.get(escrow_id).ok_or(Error::NotFound)?;
+            escrow.seller_amount = seller_amount;
+
+            // Save changes back to storage
+            self.escrows.insert(escrow_id, &escrow);
+
+            Ok(escrow_id)
+        }
+
+        /// Stores a seller and amount from which the caller can later initiate escrows.
+        ///
+        /// # Arguments
+        ///
+        /// * `seller` - The account ID of the seller.
+        /// * `amount` - The agreed amount to be transferred.
+        ///
+        /// # Returns
+        ///
+        /// * `Ok(TemplateId)` - The ID of the new template.
+        /// * `Err(Error)` - An error if the operation failed.
+        #[ink(message)]
+        pub fn create_template(
+            &mut self,
+            seller: AccountId,
+            amount: Balance
+        ) -> Result<TemplateId, Error> {
+            let caller = self.env().caller();
+
+            // Check if the template can be used to create an escrow.
+            Self::ensure_not_zero(seller)?;
+            if caller == seller {
+                return Err(Error::InvalidParticipants);
+            }
+            if amount == 0 {
+                return Err(Error::InvalidAmount);
+            }
+
+            // Get the next available template ID.
+            let template_id = self.next_template_id;
+            self.next_template_id = template_id.checked_add(1).ok_or(Error::IdOverflow)?;
+
+            self.templates.insert(template_id, &Template { owner: caller, seller, amount });
+
+            Ok(template_id)
+        }
+
+        /// Initiates a new escrow from one of the caller's templates.
+        ///
+        /// # Arguments
+        ///
+        /// * `template_id` - The ID of the template.
+        ///
+        /// # Returns
+        ///
+        /// * `Ok(EscrowId)` - The ID of the newly created escrow.
+        /// * `Err(Error)` - An error if the operation failed.
+        #[ink(message)]
+        pub fn initiate_from_template(&mut self, template_id: TemplateId) -> Result<EscrowId, Error> {
+            let template = self.templates.get(template_id).ok_or(Error::TemplateNotFound)?;
+
+            // Check if the caller owns the template.
+            if self.env().caller() != template.owner {
+                return Err(Error::Unauthorized);
+            }
+
+            self.initiate_escrow(template.seller, template.amount)
+        }
+
+        /// Deletes one of the caller's templates.
+        ///
+        /// # Arguments
+        ///
+        /// * `template_id` - The ID of the template.
+        ///
+        /// # Returns
+        ///
+        /// * `Ok(())` - If the template was deleted.
+        /// * `Err(Error)` - An error if the operation failed.
+        #[ink(message)]
+        pub fn delete_template(&mut self, template_id: TemplateId) -> Result<(), Error> {
+            let template = self.templates.get(template_id).ok_or(Error::TemplateNotFound)?;
+
+            // Check if the caller owns the template.
+            if self.env().caller() != template.owner {
+                return Err(Error::Unauthorized);
+            }
+
+            self.templates.remove(template_id);
+
+            Ok(())
+        }
+
+        /// Adds a seller to the caller's allowlist, used once the caller enables it as buyer.
+        ///
+        /// # Arguments
+        ///
+        /// * `seller` - The seller to allow.
+        ///
+        /// # Returns
+        ///
+        /// * `Ok(())` - If the seller is allowed.
+        /// * `Err(Error)` - `BatchTooLarge` if the allowlist is full, otherwise an error if the operation failed.
+        #[ink(message)]
+        pub fn add_allowed_seller(&mut self, seller: AccountId) -> Result<(), Error> {
+            let buyer = self.env().caller();
+            Self::ensure_not_zero(seller)?;
+
+            // Check if the seller is already allowed.
+            let mut sellers = self.allowed_sellers.get(buyer).unwrap_or_default();
+            if sellers.contains(&seller) {
+                return Ok(());
+            }
+
+            // Check if the allowlist has room.
+            if sellers.len() >= MAX_ALLOWED_SELLERS {
+                return Err(Error::BatchTooLarge);
+            }
+
+            sellers.push(seller);
+            self.allowed_sellers.insert(buyer, &sellers);
+
+            // Emit an event to notify about the allowlist.
+            let seq = self.next_seq()?;
+            self.env().emit_event(AllowedSellerUpdated { seq, buyer, seller, allowed: true });
+
+            Ok(())
+        }
+
+        /// Removes a seller from the caller's allowlist.
+        ///
+        /// Escrows already initiated with the seller are not affected.
+        ///
+        /// # Arguments
+        ///
+        /// * `seller` - The seller to remove.
+        ///
+        /// # Returns
+        ///
+        /// * `Ok(())` - If the seller was removed.
+        /// * `Err(Error)` - `NotFound` if the seller is not on the allowlist.
+        #[ink(message)]
+        pub fn remove_allowed_seller(&mut self, seller: AccountId) -> Result<(), Error> {
+            let buyer = self.env().caller();
+
+            // Check if the seller is on the allowlist.
+            let mut sellers = self.allowed_sellers.get(buyer).unwrap_or_default();
+            let position = sellers.iter().position(|allowed| *allowed == seller).ok_or(Error::NotFound)?;
+
+            sellers.swap_remove(position);
+            if sellers.is_empty() {
+                self.allowed_sellers.remove(buyer);
+            } else {
+                self.allowed_sellers.insert(buyer, &sellers);
+            }
+
+            // Emit an event to notify about the allowlist.
+            let seq = self.next_seq()?;
+            self.env().emit_event(AllowedSellerUpdated { seq, buyer, seller, allowed: false });
+
+            Ok(())
+        }
+
+        /// Enables or disables the caller's seller allowlist.
+        ///
+        /// While enabled, escrows with the caller as buyer can only be created with, or have
+        /// their buyer position transferred to the caller for, sellers on the allowlist.
+        ///
+        /// # Arguments
+        ///
+        /// * `enabled` - Whether to enforce the allowlist.
+        ///
+        /// # Returns
+        ///
+        /// * `Ok(())` - If the setting was recorded.
+        /// * `Err(Error)` - An error if the operation failed.
+        #[ink(message)]
+        pub fn set_allowlist_enabled(&mut self, enabled: bool) -> Result<(), Error> {
+            let buyer = self.env().caller();
+            if enabled {
+                self.allowlist_enabled.insert(buyer, &());
+            } else {
+                self.allowlist_enabled.remove(buyer);
+            }
+
+            // Emit an event to notify about the setting.
+            let seq = self.next_seq()?;
+            self.env().emit_event(AllowlistToggled { seq, buyer, enabled });
+
+            Ok(())
+        }
+
+        /// Returns a buyer's seller allowlist and whether it is enforced.
+        ///
+        /// # Arguments
+        ///
+        /// * `buyer` - The account ID of the buyer.
+        ///
+        /// # Returns
+        ///
+        /// * `(Vec<AccountId>, bool)` - The allowed sellers and whether the allowlist is enabled.
+        #[ink(message)]
+        pub fn get_allowed_sellers(&self, buyer: AccountId) -> (Vec<AccountId>, bool) {
+            (self.allowed_sellers.get(buyer).unwrap_or_default(), self.allowlist_enabled.contains(buyer))
+        }
+
+        /// Initiates a new escrow transaction with optional terms.
+        ///
+        /// # Arguments
+        ///
+        /// * `seller` - The account ID of the seller.
+        /// * `amount` - The agreed amount to be transferred.
+        /// * `terms` - The optional terms of the escrow.
+        ///
+        /// # Returns
+        ///
+        /// * `Ok(EscrowId)` - The ID of the newly created escrow.
+        /// * `Err(Error)` - An error if the operation failed.
+        #[ink(message)]
+        pub fn initiate_escrow_with_terms(
+            &mut self,
+            seller: AccountId,
+            amount: Balance,
+            terms: EscrowTerms
+        ) -> Result<EscrowId, Error> {
+            // Get the caller's account ID (the buyer).
+            let buyer = self.env().caller();
+
+            // Validate and store the new escrow.
+            let memo = terms.memo.clone();
+            let escrow_id = self.create_escrow(buyer, seller, amount, terms, Vec::new())?;
+
+            // Emit an event to notify about the new escrow.
+            let seq = self.next_seq()?;
+            self.env().emit_event(Initiated {
+                seq,
+                escrow_id,
+                buyer,
+                seller,
+                amount: self.event_amount(escrow_id, amount),
+                memo,
+            });
+
+            // Return the new escrow ID.
+            Ok(escrow_id)
+        }
+
+        /// Initiates a new escrow whose payout is split among several recipients.
+        ///
+        /// The first recipient acts as the seller: they approve completion and can cancel
+        /// on behalf of all recipients.
+        ///
+        /// # Arguments
+        ///
+        /// * `recipients` - The recipients and their shares, at most `MAX_RECIPIENTS`.
+        /// * `amount` - The agreed total amount, which the shares must add up to.
+        ///
+        /// # Returns
+        ///
+        /// * `Ok(EscrowId)` - The ID of the newly created escrow.
+        /// * `Err(Error)` - An error if the operation failed.
+        #[ink(message)]
+        pub fn initiate_split_escrow(
+            &mut self,
+            recipients: Vec<(AccountId, Balance)>,
+            amount: Balance
+        ) -> Result<EscrowId, Error> {
+            // Get the caller's account ID (the buyer).
+            let buyer = self.env().caller();
+
+            // Check if there is at least one recipient, and not too many.
+            let seller = recipients.first().ok_or(Error::NoRecipients)?.0;
+            if recipients.len() > MAX_RECIPIENTS {
+                return Err(Error::BatchTooLarge);
+            }
+
+            // Check each share and that the shares add up to the total.
+            let mut total: Balance = 0;
+            for (recipient, share) in recipients.iter() {
+                Self::ensure_not_zero(*recipient)?;
+                if *recipient == buyer {
+                    return Err(Error::InvalidParticipants);
                 }
-                // If the caller is neither the buyer nor the seller.
-                _ => {
-                    return Err(Error::Unauthorized);
+                if *share == 0 {
+                    return Err(Error::InvalidAmount);
                 }
+                total = total.checked_add(*share).ok_or(Error::ShareSumMismatch)?;
             }
-            Ok(escrow)
+            if total != amount {
+                return Err(Error::ShareSumMismatch);
+            }
+
+            // Validate and store the new escrow.
+            let escrow_id = self.create_escrow(
+                buyer,
+                seller,
+                amount,
+                EscrowTerms::default(),
+                recipients
+            )?;
+
+            // Emit an event to notify about the new escrow.
+            let seq = self.next_seq()?;
+            self.env().emit_event(Initiated {
+                seq,
+                escrow_id,
+                buyer,
+                seller,
+                amount: self.event_amount(escrow_id, amount),
+                memo: None,
+            });
+
+            // Return the new escrow ID.
+            Ok(escrow_id)
         }
 
-        #[ink(message)]
-        pub fn get_escrow(&self, escrow_id: EscrowId) -> Option<Escrow> {
-            self.escrows.get(escrow_id)
+        /// Creates an invoice: an escrow initiated by the seller for the buyer to fund.
+        ///
+        /// The invoice only counts towards the buyer's open escrow limit once the buyer deposits.
+        ///
+        /// # Arguments
+        ///
+        /// * `buyer` - The account ID of the buyer expected to fund the escrow.
+        /// * `amount` - The requested amount.
+        ///
+        /// # Returns
+        ///
+        /// * `Ok(EscrowId)` - The ID of the newly created escrow.
+        /// * `Err(Error)` - An error if the operation failed.
+        #[ink(message)]
+        pub fn request_payment(
+            &mut self,
+            buyer: AccountId,
+            amount: Balance
+        ) -> Result<EscrowId, Error> {
+            // Get the caller's account ID (the seller).
+            let seller = self.env().caller();
+
+            // Validate and store the new escrow.
+            let escrow_id = self.create_escrow(
+                buyer,
+                seller,
+                amount,
+                EscrowTerms::default(),
+                Vec::new()
+            )?;
+
+            // Emit an event to notify about the new invoice.
+            let seq = self.next_seq()?;
+            self.env().emit_event(InvoiceCreated {
+                seq,
+                escrow_id,
+                buyer,
+                seller,
+                amount: self.event_amount(escrow_id, amount),
+            });
+
+            // Return the new escrow ID.
+            Ok(escrow_id)
+        }
+
+        /// Deposits funds into an escrow, in one payment or in several installments.
+        ///
+        /// Installments accumulate until the agreed amount is reached, at which point the escrow
+        /// becomes `Funded`. A rejected deposit reverts the whole call, so no event is left
+        /// behind. Use `deposit_assets_or_return` to have rejected payments recorded instead.
+        ///
+        /// # Arguments
+        ///
+        /// * `escrow_id` - The ID of the escrow.
+        ///
+        /// # Returns
+        ///
+        /// * `Ok(())` - If the deposit was successful.
+        /// * `Err(Error)` - An error if the operation failed.
+        #[ink(message, payable)]
+        pub fn deposit_assets(&mut self, escrow_id: EscrowId) -> Result<(), Error> {
+            // Check if the contract has been shut down.
+            self.ensure_running()?;
+
+            // Get a mutable reference to the escrow data.
+            let mut escrow = self.escrows.get(escrow_id).ok_or(Error::NotFound)?;
+            // Get the caller's account ID.
+            let caller = self.env().caller();
+
+            // Check if the caller is the buyer.
+            if caller != escrow.buyer {
+                return Err(Error::NotBuyer);
+            }
+
+            // Check if the escrow is in the correct state.
+            if escrow.state != EscrowState::Created {
+                return Err(Error::InvalidState);
+            }
+
+            // Check if the deposit deadline has passed.
+            if escrow.terms.deposit_deadline.is_some_and(|deadline| self.now(&escrow) > deadline) {
+                return Err(Error::DeadlineExceeded);
+            }
+
+            // Check if the payment is non-zero and does not exceed the amount still owed.
+            let sent = self.env().transferred_value();
+            let previously = escrow.deposited;
+            if sent == 0 || sent > escrow.amount - previously {
+                return Err(Error::InvalidAmount);
+            }
+
+            // Count an invoice towards the buyer's open escrows once the buyer deposits.
+            if !escrow.open_counted {
+                self.check_open_limit(caller)?;
+                let open = self.open_counts.get(caller).unwrap_or_default();
+                self.open_counts.insert(caller, &open.saturating_add(1));
+                escrow.open_counted = true;
+            }
+            escrow.deposited = previously + sent;
+            escrow.ever_funded = true;
+            self.total_locked = self.total_locked.saturating_add(sent);
+
+            // Keep waiting for further installments.
+            if escrow.deposited < escrow.amount {
+                // Save changes back to storage
+                self.escrows.insert(escrow_id, &escrow);
+
+                // Emit an event to notify about the installment.
+                let seq = self.next_seq()?;
+                self.env().emit_event(PartialDeposit {
+                    seq,
+                    escrow_id,
+                    amount: self.event_amount(escrow_id, sent),
+                    remaining: self.event_amount(escrow_id, escrow.amount - escrow.deposited),
+                });
+
+                return Ok(());
+            }
+
+            // Update the escrow state, unless it still waits for the seller's stake.
+            self.fund_if_ready(escrow_id, &mut escrow)?;
+
+            // Save changes back to storage
+            self.escrows.insert(escrow_id, &escrow);
+
+            // Emit an event to notify about the deposit.
+            let seq = self.next_seq()?;
+            self.env().emit_event(Deposited {
+                seq,
+                escrow_id,
+                amount: self.event_amount(escrow_id, escrow.amount),
+            });
+
+            Ok(())
+        }
+
+        /// Deposits the seller's stake into a mutual-stake escrow.
+        ///
+        /// The escrow becomes `Funded` once both the buyer's amount and the seller's stake
+        /// are deposited, in either order.
+        ///
+        /// # Arguments
+        ///
+        /// * `escrow_id` - The ID of the escrow.
+        ///
+        /// # Returns
+        ///
+        /// * `Ok(())` - If the stake was deposited.
+        /// * `Err(Error)` - An error if the operation failed.
+        #[ink(message, payable)]
+        pub fn deposit_stake(&mut self, escrow_id: EscrowId) -> Result<(), Error> {
+            // Check if the contract has been shut down.
+            self.ensure_running()?;
+
+            let mut escrow = self.escrows.get(escrow_id).ok_or(Error::NotFound)?;
+
+            // Check if the caller is the seller.
+            if self.env().caller() != escrow.seller {
+                return Err(Error::NotSeller);
+            }
+
+            // Check if the escrow still awaits a stake from the seller.
+            if escrow.state != EscrowState::Created || escrow.seller_amount == 0 || escrow.seller_deposited {
+                return Err(Error::InvalidState);
+            }
+
+            // Check if the deposit deadline has passed.
+            if escrow.terms.deposit_deadline.is_some_and(|deadline| self.now(&escrow) > deadline) {
+                return Err(Error::DeadlineExceeded);
+            }
+
+            // Check if the staked amount is correct.
+            if self.env().transferred_value() != escrow.seller_amount {
+                return Err(Error::InvalidAmount);
+            }
+            escrow.seller_deposited = true;
+            escrow.ever_funded = true;
+            self.total_locked = self.total_locked.saturating_add(escrow.seller_amount);
+            self.total_bonds = self.total_bonds.saturating_add(escrow.seller_amount);
+
+            // Update the escrow state, unless it still waits for the buyer's deposit.
+            self.fund_if_ready(escrow_id, &mut escrow)?;
+
+            // Save changes back to storage
+            self.escrows.insert(escrow_id, &escrow);
+
+            // Emit an event to notify about the stake.
+            let seq = self.next_seq()?;
+            self.env().emit_event(StakeDeposited {
+                seq,
+                escrow_id,
+                amount: self.event_amount(escrow_id, escrow.seller_amount),
+            });
+
+            Ok(())
+        }
+
+        /// Deposits funds into an escrow only if it is still in the state the caller expects.
+        ///
+        /// Protects retried or delayed transactions: if the escrow was canceled or funded
+        /// since the caller read its state, the deposit is rejected instead of applied.
+        ///
+        /// # Arguments
+        ///
+        /// * `escrow_id` - The ID of the escrow.
+        /// * `expected_state` - The state the caller believes the escrow is in.
+        ///
+        /// # Returns
+        ///
+        /// * `Ok(())` - If the deposit was successful.
+        /// * `Err(Error)` - `StateChanged` if the escrow is in another state, otherwise as in `deposit_assets`.
+        #[ink(message, payable)]
+        pub fn deposit_assets_expecting(&mut self, escrow_id: EscrowId, expected_state: EscrowState) -> Result<(), Error> {
+            let escrow = self.escrows.get(escrow_id).ok_or(Error::NotFound)?;
+
+            // Check if the escrow is still in the expected state.
+            if escrow.state != expected_state {
+                return Err(Error::StateChanged);
+            }
+
+            self.deposit_assets(escrow_id)
+        }
+
+        /// Deposits funds into an escrow, recording amount mismatches instead of reverting.
+        ///
+        /// When the transferred value is zero or exceeds the amount still owed, the payment is sent
+        /// back to the caller, the escrow's `deposit_mismatches` counter is incremented and a
+        /// `DepositRejected` event is emitted. Since the call succeeds, both the counter and
+        /// the event persist. All other failures revert as in `deposit_assets`.
+        ///
+        /// # Arguments
+        ///
+        /// * `escrow_id` - The ID of the escrow.
+        ///
+        /// # Returns
+        ///
+        /// * `Ok(true)` - If the payment was deposited.
+        /// * `Ok(false)` - If the amount did not match and the payment was returned.
+        /// * `Err(Error)` - An error if the operation failed.
+        #[ink(message, payable)]
+        pub fn deposit_assets_or_return(&mut self, escrow_id: EscrowId) -> Result<bool, Error> {
+            // Check if the contract has been shut down.
+            self.ensure_running()?;
+
+            let mut escrow = self.escrows.get(escrow_id).ok_or(Error::NotFound)?;
+            // Get the caller's account ID.
+            let caller = self.env().caller();
+
+            // Check if the caller is the buyer.
+            if caller != escrow.buyer {
+                return Err(Error::NotBuyer);
+            }
+
+            // Check if the escrow is in the correct state.
+            if escrow.state != EscrowState::Created {
+                return Err(Error::InvalidState);
+            }
+
+            // Check if the deposit deadline has passed, before any payment is returned.
+            if escrow.terms.deposit_deadline.is_some_and(|deadline| self.now(&escrow) > deadline) {
+                return Err(Error::DeadlineExceeded);
+            }
+
+            // Record a mismatching deposit without reverting.
+            let sent = self.env().transferred_value();
+            let remaining = escrow.amount - escrow.deposited;
+            if sent == 0 || sent > remaining {
+                // Return the payment to the caller.
+                if sent > 0 {
+                    self.env().transfer(caller, sent).map_err(|_| Error::TransferFailed)?;
+                }
+
+                // Count the mismatch.
+                escrow.deposit_mismatches = escrow.deposit_mismatches.saturating_add(1);
+
+                // Save changes back to storage
+                self.escrows.insert(escrow_id, &escrow);
+
+                // Emit an event to notify about the rejected deposit.
+                let seq = self.next_seq()?;
+                self.env().emit_event(DepositRejected {
+                    seq,
+                    escrow_id,
+                    sent,
+                    expected: self.event_amount(escrow_id, remaining),
+                });
+
+                return Ok(false);
+            }
+
+            self.deposit_assets(escrow_id)?;
+            Ok(true)
+        }
+
+        /// Confirms the release of a funded high-value escrow, as required in addition to the
+        /// approvals once its amount reaches the owner's high-value threshold.
+        ///
+        /// # Arguments
+        ///
+        /// * `escrow_id` - The ID of the escrow.
+        ///
+        /// # Returns
+        ///
+        /// * `Ok(())` - If the release was confirmed.
+        /// * `Err(Error)` - An error if the operation failed.
+        #[ink(message)]
+        pub fn confirm_high_value(&mut self, escrow_id: EscrowId) -> Result<(), Error> {
+            let mut escrow = self.escrows.get(escrow_id).ok_or(Error::NotFound)?;
+
+            // Check if the caller is the buyer.
+            if self.env().caller() != escrow.buyer {
+                return Err(Error::NotBuyer);
+            }
+
+            // Check if the escrow is funded and not confirmed yet.
+            if escrow.state != EscrowState::Funded || escrow.high_value_confirmed {
+                return Err(Error::InvalidState);
+            }
+
+            // Record the confirmation.
+            escrow.high_value_confirmed = true;
+
+            // Save changes back to storage
+            self.escrows.insert(escrow_id, &escrow);
+
+            // Emit an event to notify about the confirmation.
+            let seq = self.next_seq()?;
+            self.env().emit_event(HighValueConfirmed { seq, escrow_id });
+
+            Ok(())
+        }
+
+        /// Confirms delivery of a funded escrow with an oracle, as required in addition to the
+        /// approvals before the funds are released to the seller.
+        ///
+        /// # Arguments
+        ///
+        /// * `escrow_id` - The ID of the escrow.
+        ///
+        /// # Returns
+        ///
+        /// * `Ok(())` - If delivery was confirmed.
+        /// * `Err(Error)` - An error if the operation failed.
+        #[ink(message)]
+        pub fn confirm_delivery(&mut self, escrow_id: EscrowId) -> Result<(), Error> {
+            let mut escrow = self.escrows.get(escrow_id).ok_or(Error::NotFound)?;
+            let caller = self.env().caller();
+
+            // Check if the caller is the oracle.
+            if escrow.terms.oracle != Some(caller) {
+                return Err(Error::Unauthorized);
+            }
+
+            // Check if the escrow is funded and delivery not confirmed yet.
+            if escrow.state != EscrowState::Funded || escrow.delivery_confirmed {
+                return Err(Error::InvalidState);
+            }
+
+            // Record the confirmation.
+            escrow.delivery_confirmed = true;
+
+            // Save changes back to storage
+            self.escrows.insert(escrow_id, &escrow);
+
+            // Emit an event to notify about the confirmation.
+            let seq = self.next_seq()?;
+            self.env().emit_event(DeliveryConfirmed { seq, escrow_id, oracle: caller });
+
+            Ok(())
+        }
+
+        /// Approves an escrow transaction and completes it once the approvals required by its
+        /// approval mode are present.
+        ///
+        /// # Arguments
+        ///
+        /// * `escrow_id` - The ID of the escrow.
+        ///
+        /// # Returns
+        ///
+        /// * `Ok(())` - If the escrow was successfully completed.
+        /// * `Err(Error)` - An error if the operation failed.
+        #[ink(message)]
+        pub fn complete_escrow(&mut self, escrow_id: EscrowId) -> Result<(), Error> {
+            let (escrow, expired) = self.prepare_completion(escrow_id)?;
+            self.apply_completion(escrow_id, escrow, expired)
+        }
+
+        /// Approves several escrows at once, completing each as in `complete_escrow`.
+        ///
+        /// Escrows the caller cannot approve are reported per escrow and skipped, as nothing was
+        /// changed for them. An approval that fails once it has started fails the whole batch,
+        /// so funds are never moved by half a batch.
+        ///
+        /// # Arguments
+        ///
+        /// * `ids` - The IDs of the escrows to approve, at most `MAX_BATCH_SIZE`.
+        ///
+        /// # Returns
+        ///
+        /// * `Ok(Vec<Result<(), Error>>)` - The outcome of each approval, in the order of `ids`.
+        /// * `Err(Error)` - An error if the batch is too large or an approval failed.
+        #[ink(message)]
+        pub fn complete_escrow_batch(&mut self, ids: Vec<EscrowId>) -> Result<Vec<Result<(), Error>>, Error> {
+            // Check if the batch is within bounds.
+            if ids.len() > MAX_BATCH_SIZE as usize {
+                return Err(Error::BatchTooLarge);
+            }
+
+            let mut outcomes = Vec::new();
+            for escrow_id in ids {
+                // Skip escrows that cannot be approved, failing the batch once an approval has started.
+                let outcome = match self.prepare_completion(escrow_id) {
+                    Ok((escrow, expired)) => Ok(self.apply_completion(escrow_id, escrow, expired)?),
+                    Err(error) => Err(error),
+                };
+                outcomes.push(outcome);
+            }
+
+            Ok(outcomes)
+        }
+
+        /// Releases a funded escrow to the seller once the auto-release period has passed
+        /// since the buyer approved, without waiting for the seller's approval.
+        ///
+        /// Anyone may call this.
+        ///
+        /// # Arguments
+        ///
+        /// * `escrow_id` - The ID of the escrow.
+        ///
+        /// # Returns
+        ///
+        /// * `Ok(())` - If the escrow was released to the seller.
+        /// * `Err(Error)` - An error if the operation failed.
+        #[ink(message)]
+        pub fn finalize(&mut self, escrow_id: EscrowId) -> Result<(), Error> {
+            // Check if the contract has been shut down.
+            self.ensure_running()?;
+
+            let mut escrow = self.escrows.get(escrow_id).ok_or(Error::NotFound)?;
+
+            // Check if the escrow is in the correct state.
+            if escrow.state != EscrowState::Funded {
+                return Err(Error::InvalidState);
+            }
+
+            // Check if the escrow auto-releases and the buyer has a valid approval.
+            let period = escrow.terms.auto_release_period.ok_or(Error::InvalidState)?;
+            let approved_at = escrow.buyer_approved_at.ok_or(Error::InvalidState)?;
+            if self.is_stale(&escrow, Some(approved_at)) {
+                return Err(Error::InvalidState);
+            }
+
+            // Check if the auto-release period has passed.
+            if self.now(&escrow) < approved_at.saturating_add(period) {
+                return Err(Error::DeadlineNotReached);
+            }
+
+            // Check if the release is gated on a block height that has not been reached.
+            if let Some(release_block) = escrow.terms.release_after_block {
+                if self.env().block_number() < release_block {
+                    return Err(Error::BlockNotReached);
+                }
+            }
+
+            // Check if a high-value escrow was confirmed by the buyer.
+            self.check_high_value_confirmed(&escrow)?;
+
+            // Check if the oracle confirmed delivery.
+            Self::check_delivery_confirmed(&escrow)?;
+
+            // Transfer the funds to the seller and the seller's stake to the buyer.
+            escrow.seller_received = self.pay_seller(&escrow, escrow.amount)?;
+            self.release_stake(&escrow, escrow.buyer)?;
+
+            // Update the escrow state.
+            self.set_state(escrow_id, &mut escrow, EscrowState::Completed)?;
+
+            // Save changes back to storage
+            self.escrows.insert(escrow_id, &escrow);
+
+            // Emit an event to notify about the release.
+            let seq = self.next_seq()?;
+            self.env().emit_event(AutoFinalized {
+                seq,
+                escrow_id,
+                by: self.env().caller(),
+            });
+
+            Ok(())
+        }
+
+        /// Cancels an escrow transaction and refunds the buyer if funded.
+        ///
+        /// When the buyer cancels a funded escrow, the escrow's cancellation penalty is paid
+        /// to the seller and only the remainder is refunded. A refund that cannot be transferred
+        /// is kept for its recipient to collect with `claim_refund`. Seller cancellations refund in full,
+        /// but only once the escrow's seller cancellation delay has passed since funding. Buyers
+        /// cannot cancel a funded escrow during the owner's refund lock period.
+        ///
+        /// # Arguments
+        ///
+        /// * `escrow_id` - The ID of the escrow.
+        ///
+        /// # Returns
+        ///
+        /// * `Ok(())` - If the escrow was successfully canceled.
+        /// * `Err(Error)` - An error if the operation failed.
+        #[ink(message)]
+        pub fn cancel_escrow(&mut self, escrow_id: EscrowId) -> Result<(), Error> {
+            // Check if the contract has been shut down.
+            self.ensure_running()?;
+
+            // Get a mutable reference to the escrow data.
+            let mut escrow = self.escrows.get(escrow_id).ok_or(Error::NotFound)?;
+            // Get the caller's account ID.
+            let caller = self.env().caller();
+
+            // Check if the caller is the buyer or the seller.
+            if caller != escrow.buyer && caller != escrow.seller {
+                return Err(Error::Unauthorized);
+            }
+
+            // Check if the escrow is already completed or awaits the arbiter.
+            if escrow.state == EscrowState::Completed || escrow.state == EscrowState::Disputed {
+                return Err(Error::InvalidState);
+            }
+
+            // Check if the seller is still within the cancellation delay after funding.
+            if caller == escrow.seller && escrow.state == EscrowState::Funded {
+                if let (Some(delay), Some(funded_at)) = (escrow.terms.seller_cancel_delay, escrow.funded_at) {
+                    if self.now(&escrow) < funded_at.saturating_add(delay) {
+                        return Err(Error::DeadlineNotReached);
+                    }
+                }
+            }
+
+            // Check if the buyer is still within the refund lock period after funding.
+            if caller == escrow.buyer && escrow.state == EscrowState::Funded {
+                if let Some(funded_at) = escrow.funded_at {
+                    if self.now(&escrow) < funded_at.saturating_add(self.refund_lock_period) {
+                        return Err(Error::DeadlineNotReached);
+                    }
+                }
+            }
+
+            // Refund buyer if escrow was funded, minus the penalty if the buyer cancels.
+            // Installments of an escrow that is not fully funded are refunded in full.
+            let mut refunded = 0;
+            let mut penalty = 0;
+            if escrow.state == EscrowState::Created && escrow.deposited > 0 {
+                refunded = escrow.deposited;
+                self.total_locked = self.total_locked.saturating_sub(refunded);
+                escrow.deposited = 0;
+            }
+
+            // Return the seller's stake of an escrow that is not yet funded.
+            if escrow.state == EscrowState::Created && escrow.seller_deposited {
+                self.total_locked = self.total_locked.saturating_sub(escrow.seller_amount);
+                self.total_bonds = self.total_bonds.saturating_sub(escrow.seller_amount);
+                escrow.seller_deposited = false;
+                self.transfer_or_owe(escrow_id, escrow.seller, escrow.seller_amount)?;
+            }
+            if escrow.state == EscrowState::Funded {
+                if caller == escrow.buyer {
+                    penalty = Self::apply_bps(escrow.amount, escrow.terms.cancellation_penalty_bps)?;
+                }
+                refunded = escrow.amount - penalty;
+            }
+            // Keep failed transfers claimable instead of blocking the cancellation.
+            if refunded > 0 {
+                for (account, share) in Self::refund_payouts(&escrow, refunded)? {
+                    self.transfer_or_owe(escrow_id, account, share)?;
+                }
+            }
+
+            // Compensate the seller.
+            if penalty > 0 {
+                self.transfer_or_owe(escrow_id, escrow.seller, penalty)?;
+            }
+
+            // Return the seller's stake of a funded escrow.
+            if escrow.state == EscrowState::Funded && escrow.seller_amount > 0 {
+                self.transfer_or_owe(escrow_id, escrow.seller, escrow.seller_amount)?;
+            }
+
+            // Update the escrow state.
+            self.set_state(escrow_id, &mut escrow, EscrowState::Canceled)?;
+
+            // Save the modified escrow back to storage
+            self.escrows.insert(escrow_id, &escrow);
+
+            // Emit an event to notify about the cancellation.
+            let reason = if caller == escrow.buyer { CancelReason::BuyerWithdrew } else { CancelReason::SellerDeclined };
+            let seq = self.next_seq()?;
+            self.env().emit_event(Canceled {
+                seq,
+                escrow_id,
+                by: caller,
+                reason,
+                refunded: self.event_amount(escrow_id, refunded),
+                penalty: self.event_amount(escrow_id, penalty),
+            });
+
+            Ok(())
+        }
+
+        /// Deletes an escrow that was never funded from storage, reclaiming its storage deposit.
+        ///
+        /// If ID recycling is enabled, the ID is reused by a later escrow.
+        ///
+        /// # Arguments
+        ///
+        /// * `escrow_id` - The ID of the escrow.
+        ///
+        /// # Returns
+        ///
+        /// * `Ok(())` - If the escrow was discarded.
+        /// * `Err(Error)` - An error if the operation failed.
+        #[ink(message)]
+        pub fn discard_escrow(&mut self, escrow_id: EscrowId) -> Result<(), Error> {
+            let escrow = self.escrows.get(escrow_id).ok_or(Error::NotFound)?;
+
+            // Check if the caller is the buyer.
+            if self.env().caller() != escrow.buyer {
+                return Err(Error::NotBuyer);
+            }
+
+            // Check if the escrow is unfunded and no funds ever moved.
+            if escrow.state != EscrowState::Created || escrow.ever_funded || escrow.deposited > 0 || escrow.seller_deposited {
+                return Err(Error::InvalidState);
+            }
+
+            // Remove the escrow, its pending proposals and its history.
+            self.remove_escrow(escrow_id, escrow.state);
+            if escrow.open_counted {
+                self.close_open(escrow.buyer);
+            }
+
+            // Drop the escrow from both participants' listings.
+            self.unlist_for_buyer(escrow.buyer, escrow_id);
+            self.unlist_for_seller(escrow.seller, escrow_id);
+
+            // Make the ID available for reuse.
+            if self.recycle_ids {
+                self.free_ids.insert(self.free_count, &escrow_id);
+                self.free_count = self.free_count.checked_add(1).ok_or(Error::IdOverflow)?;
+            }
+
+            // Emit an event to notify about the removal.
+            let seq = self.next_seq()?;
+            self.env().emit_event(Discarded { seq, escrow_id });
+
+            Ok(())
+        }
+
+        /// Reopens a canceled escrow that was never funded, returning it to `Created`.
+        ///
+        /// # Arguments
+        ///
+        /// * `escrow_id` - The ID of the escrow.
+        ///
+        /// # Returns
+        ///
+        /// * `Ok(())` - If the escrow was reopened.
+        /// * `Err(Error)` - An error if the operation failed.
+        #[ink(message)]
+        pub fn reopen_escrow(&mut self, escrow_id: EscrowId) -> Result<(), Error> {
+            // Check if the contract has been shut down.
+            self.ensure_running()?;
+
+            let mut escrow = self.escrows.get(escrow_id).ok_or(Error::NotFound)?;
+
+            // Check if the caller is the buyer.
+            if self.env().caller() != escrow.buyer {
+                return Err(Error::NotBuyer);
+            }
+
+            // Check if the escrow is canceled and no funds ever moved.
+            if escrow.state != EscrowState::Canceled || escrow.ever_funded {
+                return Err(Error::InvalidState);
+            }
+
+            // Check if the buyer may open another escrow.
+            self.check_open_limit(escrow.buyer)?;
+
+            // Update the escrow state.
+            self.set_state(escrow_id, &mut escrow, EscrowState::Created)?;
+
+            // Save changes back to storage
+            self.escrows.insert(escrow_id, &escrow);
+
+            // Emit an event to notify about the reopening.
+            let seq = self.next_seq()?;
+            self.env().emit_event(Reopened { seq, escrow_id });
+
+            Ok(())
+        }
+
+        /// Returns the refunds owed to an account that it can collect with `claim_refund`.
+        ///
+        /// When amounts are obfuscated, only the account itself can read what it is owed.
+        ///
+        /// # Arguments
+        ///
+        /// * `account` - The account ID of the refund recipient.
+        ///
+        /// # Returns
+        ///
+        /// * `Balance` - The amount owed, or 0 if nothing is owed or the caller may not read it.
+        #[ink(message)]
+        pub fn pending_withdrawal_of(&self, account: AccountId) -> Balance {
+            if self.obfuscate_amounts && self.env().caller() != account {
+                return 0;
+            }
+            self.pending_refunds.get(account).unwrap_or_default()
+        }
+
+        /// Transfers the refunds owed to the caller that could not be transferred on cancellation.
+        ///
+        /// # Returns
+        ///
+        /// * `Ok(Balance)` - The amount transferred.
+        /// * `Err(Error)` - An error if the operation failed.
+        #[ink(message)]
+        pub fn claim_refund(&mut self) -> Result<Balance, Error> {
+            // Check if the contract has been shut down.
+            self.ensure_running()?;
+
+            let caller = self.env().caller();
+
+            // Check if the caller is owed a refund.
+            let owed = self.pending_refunds.take(caller).ok_or(Error::NotFound)?;
+
+            // Transfer the refund.
+            self.total_locked = self.total_locked.saturating_sub(owed);
+            self.total_pending_refunds = self.total_pending_refunds.saturating_sub(owed);
+            self.env().transfer(caller, owed).map_err(|_| Error::TransferFailed)?;
+
+            Ok(owed)
+        }
+
+        /// Requests the return of a completed escrow within its return window.
+        ///
+        /// The return only happens once the seller approves it with `approve_return`.
+        ///
+        /// # Arguments
+        ///
+        /// * `escrow_id` - The ID of the escrow.
+        ///
+        /// # Returns
+        ///
+        /// * `Ok(())` - If the return was requested.
+        /// * `Err(Error)` - `DeadlineExceeded` after the window, otherwise an error if the operation failed.
+        #[ink(message)]
+        pub fn request_return(&mut self, escrow_id: EscrowId) -> Result<(), Error> {
+            let mut escrow = self.escrows.get(escrow_id).ok_or(Error::NotFound)?;
+
+            // Check if the caller is the buyer.
+            if self.env().caller() != escrow.buyer {
+                return Err(Error::NotBuyer);
+            }
+
+            // Check if the escrow is completed with a return window and no request yet.
+            if escrow.state != EscrowState::Completed || escrow.return_requested {
+                return Err(Error::InvalidState);
+            }
+            let deadline = escrow.return_deadline.ok_or(Error::InvalidState)?;
+
+            // Check if the return window has passed.
+            if self.now(&escrow) > deadline {
+                return Err(Error::DeadlineExceeded);
+            }
+
+            // Record the request.
+            escrow.return_requested = true;
+
+            // Save changes back to storage
+            self.escrows.insert(escrow_id, &escrow);
+
+            // Emit an event to notify about the request.
+            let seq = self.next_seq()?;
+            self.env().emit_event(ReturnRequested { seq, escrow_id });
+
+            Ok(())
+        }
+
+        /// Approves a requested return within the return window, repaying the buyer.
+        ///
+        /// The seller sends back what its recipients received on completion, which is forwarded
+        /// to the buyer or its refund address, and the escrow becomes `Returned`. The platform
+        /// fee is not reversed, so the buyer is repaid the amount net of the fee.
+        ///
+        /// # Arguments
+        ///
+        /// * `escrow_id` - The ID of the escrow.
+        ///
+        /// # Returns
+        ///
+        /// * `Ok(())` - If the escrow was returned.
+        /// * `Err(Error)` - `DeadlineExceeded` after the window, otherwise an error if the operation failed.
+        #[ink(message, payable)]
+        pub fn approve_return(&mut self, escrow_id: EscrowId) -> Result<(), Error> {
+            // Check if the contract has been shut down.
+            self.ensure_running()?;
+
+            let mut escrow = self.escrows.get(escrow_id).ok_or(Error::NotFound)?;
+
+            // Check if the caller is the seller.
+            if self.env().caller() != escrow.seller {
+                return Err(Error::NotSeller);
+            }
+
+            // Check if the buyer requested a return of the completed escrow.
+            if escrow.state != EscrowState::Completed || !escrow.return_requested {
+                return Err(Error::InvalidState);
+            }
+
+            // Check if the return window has passed.
+            if escrow.return_deadline.is_none_or(|deadline| self.now(&escrow) > deadline) {
+                return Err(Error::DeadlineExceeded);
+            }
+
+            // Check if the seller repays what it received.
+            if self.env().transferred_value() != escrow.seller_received {
+                return Err(Error::InvalidAmount);
+            }
+
+            // Forward the repayment to the buyer.
+            self.env()
+                .transfer(Self::refund_recipient(&escrow), escrow.seller_received)
+                .map_err(|_| Error::TransferFailed)?;
+
+            // Update the escrow state.
+            self.set_state(escrow_id, &mut escrow, EscrowState::Returned)?;
+
+            // Save changes back to storage
+            self.escrows.insert(escrow_id, &escrow);
+
+            // Emit an event to notify about the return.
+            let seq = self.next_seq()?;
+            self.env().emit_event(Returned {
+                seq,
+                escrow_id,
+                amount: self.event_amount(escrow_id, escrow.seller_received),
+            });
+
+            Ok(())
+        }
+
+        /// Proposes a new amount for an escrow that has not been funded yet.
+        ///
+        /// The proposal only takes effect once the buyer accepts it, and a later proposal
+        /// replaces an earlier one.
+        ///
+        /// # Arguments
+        ///
+        /// * `escrow_id` - The ID of the escrow.
+        /// * `new_amount` - The proposed amount.
+        ///
+        /// # Returns
+        ///
+        /// * `Ok(())` - If the proposal was recorded.
+        /// * `Err(Error)` - An error if the operation failed.
+        #[ink(message)]
+        pub fn propose_amount(
+            &mut self,
+            escrow_id: EscrowId,
+            new_amount: Balance
+        ) -> Result<(), Error> {
+            let mut escrow = self.escrows.get(escrow_id).ok_or(Error::NotFound)?;
+
+            // Check if the caller is the seller.
+            if self.env().caller() != escrow.seller {
+                return Err(Error::NotSeller);
+            }
+
+            // Check if the escrow is still unfunded. Split escrows keep their amount, since
+            // it must match the recipients' shares.
+            if escrow.state != EscrowState::Created || !escrow.recipients.is_empty() {
+                return Err(Error::InvalidState);
+            }
+
+            // Check if the proposed amount is valid.
+            if new_amount == 0 || escrow.terms.keeper_reward > new_amount {
+                return Err(Error::InvalidAmount);
+            }
+
+            // Record the proposal.
+            escrow.proposed_amount = Some(new_amount);
+
+            // Save changes back to storage
+            self.escrows.insert(escrow_id, &escrow);
+
+            // Emit an event to notify about the proposal.
+            let seq = self.next_seq()?;
+            self.env().emit_event(AmountProposed {
+                seq,
+                escrow_id,
+                amount: self.event_amount(escrow_id, new_amount),
+            });
+
+            Ok(())
+        }
+
+        /// Accepts the amount proposed by the seller, making it the new agreed amount.
+        ///
+        /// # Arguments
+        ///
+        /// * `escrow_id` - The ID of the escrow.
+        ///
+        /// # Returns
+        ///
+        /// * `Ok(())` - If the proposed amount was applied.
+        /// * `Err(Error)` - An error if the operation failed.
+        #[ink(message)]
+        pub fn accept_amount(&mut self, escrow_id: EscrowId) -> Result<(), Error> {
+            let mut escrow = self.escrows.get(escrow_id).ok_or(Error::NotFound)?;
+
+            // Check if the caller is the buyer.
+            if self.env().caller() != escrow.buyer {
+                return Err(Error::NotBuyer);
+            }
+
+            // Check if the escrow is still unfunded and no installments were deposited.
+            if escrow.state != EscrowState::Created || escrow.deposited > 0 {
+                return Err(Error::InvalidState);
+            }
+
+            // Apply the pending proposal.
+            let amount = escrow.proposed_amount.take().ok_or(Error::InvalidState)?;
+            escrow.amount = amount;
+
+            // Save changes back to storage
+            self.escrows.insert(escrow_id, &escrow);
+
+            // Emit an event to notify about the new amount.
+            let seq = self.next_seq()?;
+            self.env().emit_event(AmountAccepted {
+                seq,
+                escrow_id,
+                amount: self.event_amount(escrow_id, amount),
+            });
+
+            Ok(())
+        }
+
+        /// Splits refunds of an escrow across several accounts instead of the buyer.
+        ///
+        /// Splits take precedence over the refund address. Passing an empty list removes them.
+        ///
+        /// # Arguments
+        ///
+        /// * `escrow_id` - The ID of the escrow.
+        /// * `splits` - The accounts and their shares in basis points, summing to 10_000.
+        ///
+        /// # Returns
+        ///
+        /// * `Ok(())` - If the refund splits were set.
+        /// * `Err(Error)` - An error if the operation failed.
+        #[ink(message)]
+        pub fn set_refund_splits(
+            &mut self,
+            escrow_id: EscrowId,
+            splits: Vec<(AccountId, u16)>
+        ) -> Result<(), Error> {
+            let mut escrow = self.escrows.get(escrow_id).ok_or(Error::NotFound)?;
+
+            // Check if the caller is the buyer.
+            if self.env().caller() != escrow.buyer {
+                return Err(Error::NotBuyer);
+            }
+
+            // Check if the escrow can still be refunded.
+            if Self::is_terminal(&escrow.state) {
+                return Err(Error::InvalidState);
+            }
+
+            // Check the splits are real accounts whose shares sum to the whole refund.
+            if !splits.is_empty() {
+                if splits.len() > MAX_RECIPIENTS {
+                    return Err(Error::InvalidSplit);
+                }
+                let mut total: u32 = 0;
+                for (account, bps) in splits.iter() {
+                    Self::ensure_not_zero(*account)?;
+                    if *bps == 0 {
+                        return Err(Error::InvalidSplit);
+                    }
+                    total += u32::from(*bps);
+                }
+                if total != u32::from(MAX_BPS) {
+                    return Err(Error::InvalidSplit);
+                }
+            }
+
+            // Record the refund splits.
+            escrow.refund_splits = splits.clone();
+
+            // Save changes back to storage
+            self.escrows.insert(escrow_id, &escrow);
+
+            // Emit an event to notify about the refund splits.
+            let seq = self.next_seq()?;
+            self.env().emit_event(RefundSplitsSet { seq, escrow_id, splits });
+
+            Ok(())
+        }
+
+        /// Sets an alternate account to receive refunds instead of the buyer.
+        ///
+        /// # Arguments
+        ///
+        /// * `escrow_id` - The ID of the escrow.
+        /// * `refund_address` - The account that will receive refunds.
+        ///
+        /// # Returns
+        ///
+        /// * `Ok(())` - If the refund address was set.
+        /// * `Err(Error)` - An error if the operation failed.
+        #[ink(message)]
+        pub fn set_refund_address(
+            &mut self,
+            escrow_id: EscrowId,
+            refund_address: AccountId
+        ) -> Result<(), Error> {
+            let mut escrow = self.escrows.get(escrow_id).ok_or(Error::NotFound)?;
+
+            // Check if the caller is the buyer.
+            if self.env().caller() != escrow.buyer {
+                return Err(Error::NotBuyer);
+            }
+
+            // Check if the escrow can still be refunded.
+            if Self::is_terminal(&escrow.state) {
+                return Err(Error::InvalidState);
+            }
+
+            // Check if the refund address is a real account.
+            Self::ensure_not_zero(refund_address)?;
+
+            // Record the refund address.
+            escrow.refund_address = Some(refund_address);
+
+            // Save changes back to storage
+            self.escrows.insert(escrow_id, &escrow);
+
+            // Emit an event to notify about the refund address.
+            let seq = self.next_seq()?;
+            self.env().emit_event(RefundAddressSet { seq, escrow_id, refund_address });
+
+            Ok(())
+        }
+
+        /// Sets an alternate account to receive payouts instead of the seller.
+        ///
+        /// Approvals are still given by the seller. Split escrows always pay their recipients,
+        /// so they cannot set a payout address.
+        ///
+        /// # Arguments
+        ///
+        /// * `escrow_id` - The ID of the escrow.
+        /// * `payout_address` - The account that will receive payouts.
+        ///
+        /// # Returns
+        ///
+        /// * `Ok(())` - If the payout address was set.
+        /// * `Err(Error)` - An error if the operation failed.
+        #[ink(message)]
+        pub fn set_payout_address(
+            &mut self,
+            escrow_id: EscrowId,
+            payout_address: AccountId
+        ) -> Result<(), Error> {
+            let mut escrow = self.escrows.get(escrow_id).ok_or(Error::NotFound)?;
+
+            // Check if the caller is the seller.
+            if self.env().caller() != escrow.seller {
+                return Err(Error::NotSeller);
+            }
+
+            // Check if the escrow can still pay out to a single seller.
+            if Self::is_terminal(&escrow.state) || !escrow.recipients.is_empty() {
+                return Err(Error::InvalidState);
+            }
+
+            // Check if the payout address is a real account.
+            Self::ensure_not_zero(payout_address)?;
+
+            // Record the payout address.
+            escrow.payout_address = Some(payout_address);
+
+            // Save changes back to storage
+            self.escrows.insert(escrow_id, &escrow);
+
+            // Emit an event to notify about the payout address.
+            let seq = self.next_seq()?;
+            self.env().emit_event(PayoutAddressSet { seq, escrow_id, payout_address });
+
+            Ok(())
+        }
+
+        /// Transfers the buyer's position in a funded escrow to another account.
+        ///
+        /// The new buyer receives any refund and gives the buyer's approval, so the previous
+        /// buyer's approval, confirmation, refund address and refund splits are cleared, and
+        /// pending proposals are dropped.
+        ///
+        /// # Arguments
+        ///
+        /// * `escrow_id` - The ID of the escrow.
+        /// * `new_buyer` - The account ID of the new buyer.
+        ///
+        /// # Returns
+        ///
+        /// * `Ok(())` - If the position was transferred.
+        /// * `Err(Error)` - An error if the operation failed.
+        #[ink(message)]
+        pub fn transfer_buyer_position(
+            &mut self,
+            escrow_id: EscrowId,
+            new_buyer: AccountId
+        ) -> Result<(), Error> {
+            let mut escrow = self.escrows.get(escrow_id).ok_or(Error::NotFound)?;
+            // Get the caller's account ID.
+            let caller = self.env().caller();
+
+            // Check if the caller is the buyer.
+            if caller != escrow.buyer {
+                return Err(Error::NotBuyer);
+            }
+
+            // Check if the escrow is funded.
+            if escrow.state != EscrowState::Funded {
+                return Err(Error::InvalidState);
+            }
+
+            // Check if the new buyer is a real account other than the seller and the arbiter.
+            Self::ensure_not_zero(new_buyer)?;
+            if new_buyer == escrow.seller
+                || new_buyer == caller
+                || Some(new_buyer) == self.arbiter_of(&escrow)
+                || Some(new_buyer) == escrow.terms.oracle
+            {
+                return Err(Error::InvalidParticipants);
+            }
+
+            // Check if the new buyer allows the seller and may have another open escrow.
+            self.check_seller_allowed(new_buyer, escrow.seller)?;
+            self.check_open_limit(new_buyer)?;
+
+            // Hand over the position.
+            escrow.buyer = new_buyer;
+            escrow.buyer_approved = false;
+            escrow.buyer_approved_at = None;
+            escrow.high_value_confirmed = false;
+            escrow.refund_address = None;
+            escrow.refund_splits = Vec::new();
+            self.pending_amendments.remove(escrow_id);
+            self.pending_settlements.remove(escrow_id);
+            self.pending_extensions.remove(escrow_id);
+            self.pending_arbiters.remove(escrow_id);
+
+            // Move the escrow to the new buyer's open escrows and listing.
+            self.close_open(caller);
+            let open = self.open_counts.get(new_buyer).unwrap_or_default();
+            self.open_counts.insert(new_buyer, &open.saturating_add(1));
+            self.unlist_for_buyer(caller, escrow_id);
+            self.list_for_buyer(new_buyer, escrow_id);
+
+            // Save changes back to storage
+            self.escrows.insert(escrow_id, &escrow);
+
+            // Emit an event to notify about the transfer.
+            let seq = self.next_seq()?;
+            self.env().emit_event(BuyerTransferred { seq, escrow_id, from: caller, to: new_buyer });
+
+            Ok(())
+        }
+
+        /// Signs an amendment to the amount and release deadline of an escrow.
+        ///
+        /// The first party to call records the amendment. It is applied once the other
+        /// party calls with the same hash and terms. Calling again as the proposer replaces
+        /// the pending amendment. A funded escrow can only amend its deadline, and at most
+        /// `MAX_AMENDMENTS` amendments are applied per escrow.
+        ///
+        /// # Arguments
+        ///
+        /// * `escrow_id` - The ID of the escrow.
+        /// * `amendment_hash` - The hash of the signed off-chain amendment document.
+        /// * `new_amount` - The amended amount.
+        /// * `new_deadline` - The amended release deadline.
+        ///
+        /// # Returns
+        ///
+        /// * `Ok(())` - If the amendment was recorded or applied.
+        /// * `Err(Error)` - An error if the operation failed.
+        #[ink(message)]
+        pub fn amend_escrow(
+            &mut self,
+            escrow_id: EscrowId,
+            amendment_hash: Hash,
+            new_amount: Balance,
+            new_deadline: Option<u64>
+        ) -> Result<(), Error> {
+            let mut escrow = self.escrows.get(escrow_id).ok_or(Error::NotFound)?;
+            let caller = self.env().caller();
+
+            // Check if the caller is the buyer or the seller.
+            if caller != escrow.buyer && caller != escrow.seller {
+                return Err(Error::Unauthorized);
+            }
+
+            // Check if the escrow can still be amended.
+            if escrow.state != EscrowState::Created && escrow.state != EscrowState::Funded {
+                return Err(Error::InvalidState);
+            }
+
+            // Check if the amended amount is valid. Escrows holding funds, including installments,
+            // and split escrows must keep their amount.
+            let amount_locked = escrow.state == EscrowState::Funded ||
+                escrow.deposited > 0 ||
+                escrow.seller_deposited ||
+                !escrow.recipients.is_empty();
+            if new_amount == 0 || escrow.terms.keeper_reward > new_amount {
+                return Err(Error::InvalidAmount);
+            }
+            if amount_locked && new_amount != escrow.amount {
+                return Err(Error::InvalidAmount);
+            }
+
+            let amendment = Amendment {
+                amendment_hash,
+                amount: new_amount,
+                release_deadline: new_deadline,
+                proposer: caller,
+            };
+
+            // Record the amendment until the other party signs it.
+            let pending = match self.pending_amendments.get(escrow_id) {
+                Some(pending) if pending.proposer != caller => pending,
+                _ => {
+                    self.pending_amendments.insert(escrow_id, &amendment);
+                    let seq = self.next_seq()?;
+                    self.env().emit_event(AmendmentProposed {
+                        seq,
+                        escrow_id,
+                        amendment_hash,
+                        proposer: caller,
+                    });
+                    return Ok(());
+                }
+            };
+
+            // Check if both parties signed the same amendment.
+            if pending.amendment_hash != amendment_hash ||
+                pending.amount != new_amount ||
+                pending.release_deadline != new_deadline
+            {
+                return Err(Error::AmendmentMismatch);
+            }
+
+            // Check if the amendment history has room.
+            let mut history = self.amendment_history.get(escrow_id).unwrap_or_default();
+            if history.len() >= MAX_AMENDMENTS {
+                return Err(Error::InvalidState);
+            }
+
+            // Apply the amendment, discarding any outstanding amount proposal.
+            escrow.amount = new_amount;
+            escrow.terms.release_deadline = new_deadline;
+            escrow.proposed_amount = None;
+            history.push(pending);
+
+            // Save changes back to storage
+            self.escrows.insert(escrow_id, &escrow);
+            self.amendment_history.insert(escrow_id, &history);
+            self.pending_amendments.remove(escrow_id);
+
+            // Emit an event to notify about the amendment.
+            let seq = self.next_seq()?;
+            self.env().emit_event(Amended { seq, escrow_id, amendment_hash });
+
+            Ok(())
+        }
+
+        /// Disputes a funded escrow, handing the decision over to the arbiter.
+        ///
+        /// A disputed escrow can no longer be completed or canceled by the parties, but they can
+        /// still agree on a settlement.
+        ///
+        /// # Arguments
+        ///
+        /// * `escrow_id` - The ID of the escrow.
+        ///
+        /// # Returns
+        ///
+        /// * `Ok(())` - If the dispute was raised.
+        /// * `Err(Error)` - An error if the operation failed.
+        #[ink(message)]
+        pub fn raise_dispute(&mut self, escrow_id: EscrowId) -> Result<(), Error> {
+            // Check if the contract has been shut down.
+            self.ensure_running()?;
+
+            let mut escrow = self.escrows.get(escrow_id).ok_or(Error::NotFound)?;
+            // Get the caller's account ID.
+            let caller = self.env().caller();
+
+            // Check if the caller is the buyer or the seller.
+            if caller != escrow.buyer && caller != escrow.seller {
+                return Err(Error::Unauthorized);
+            }
+
+            // Check if the escrow is funded and there is an arbiter to resolve the dispute.
+            if escrow.state != EscrowState::Funded || self.arbiter_of(&escrow).is_none() {
+                return Err(Error::InvalidState);
+            }
+
+            // Update the escrow state.
+            self.set_state(escrow_id, &mut escrow, EscrowState::Disputed)?;
+            escrow.disputed_at = Some(self.now(&escrow));
+
+            // Save changes back to storage
+            self.escrows.insert(escrow_id, &escrow);
+
+            // Emit an event to notify about the dispute.
+            let seq = self.next_seq()?;
+            self.env().emit_event(DisputeRaised { seq, escrow_id, by: caller });
+
+            Ok(())
+        }
+
+        /// Posts the caller's dispute deposit into a disputed escrow whose terms require one.
+        ///
+        /// The arbiter can only rule once both parties posted their deposits. A settlement or
+        /// dispute timeout returns the posted deposits to the parties.
+        ///
+        /// # Arguments
+        ///
+        /// * `escrow_id` - The ID of the escrow.
+        ///
+        /// # Returns
+        ///
+        /// * `Ok(())` - If the deposit was posted.
+        /// * `Err(Error)` - An error if the operation failed.
+        #[ink(message, payable)]
+        pub fn post_dispute_deposit(&mut self, escrow_id: EscrowId) -> Result<(), Error> {
+            // Check if the contract has been shut down.
+            self.ensure_running()?;
+
+            let mut escrow = self.escrows.get(escrow_id).ok_or(Error::NotFound)?;
+            // Get the caller's account ID.
+            let caller = self.env().caller();
+
+            // Check if the caller is the buyer or the seller.
+            if caller != escrow.buyer && caller != escrow.seller {
+                return Err(Error::Unauthorized);
+            }
+
+            // Check if the escrow is disputed, requires a deposit and the caller has not posted it yet.
+            let posted = if caller == escrow.buyer {
+                escrow.buyer_dispute_deposited
+            } else {
+                escrow.seller_dispute_deposited
+            };
+            if escrow.state != EscrowState::Disputed || escrow.terms.dispute_deposit == 0 || posted {
+                return Err(Error::InvalidState);
+            }
+
+            // Check if the transferred value matches the deposit.
+            let deposit = escrow.terms.dispute_deposit;
+            if self.env().transferred_value() != deposit {
+                return Err(Error::InvalidAmount);
+            }
+
+            // Record the deposit.
+            if caller == escrow.buyer {
+                escrow.buyer_dispute_deposited = true;
+            } else {
+                escrow.seller_dispute_deposited = true;
+            }
+            self.total_locked = self.total_locked.saturating_add(deposit);
+            self.total_bonds = self.total_bonds.saturating_add(deposit);
+
+            // Save changes back to storage
+            self.escrows.insert(escrow_id, &escrow);
+
+            // Emit an event to notify about the deposit.
+            let seq = self.next_seq()?;
+            self.env().emit_event(DisputeDepositPosted {
+                seq,
+                escrow_id,
+                party: caller,
+                amount: self.event_amount(escrow_id, deposit),
+            });
+
+            Ok(())
+        }
+
+        /// Resolves a disputed escrow by splitting its amount between the buyer and the seller.
+        ///
+        /// The escrow's arbiter fee is paid to the arbiter first, and the two amounts split the
+        /// remainder. The seller's part is subject to the platform fee and is divided among the
+        /// recipients of a split escrow in proportion to their shares.
+        ///
+        /// If the escrow requires dispute deposits, both must be posted. The party awarded the
+        /// larger amount gets its deposit back and the other's is forfeited to the arbiter; an
+        /// even split refunds both.
+        ///
+        /// # Arguments
+        ///
+        /// * `escrow_id` - The ID of the escrow.
+        /// * `buyer_amount` - The amount refunded to the buyer.
+        /// * `seller_amount` - The amount released to the seller.
+        ///
+        /// The two amounts must sum to the escrow amount minus the arbiter fee.
+        ///
+        /// # Returns
+        ///
+        /// * `Ok(())` - If the dispute was resolved.
+        /// * `Err(Error)` - An error if the operation failed.
+        #[ink(message)]
+        pub fn resolve_dispute(
+            &mut self,
+            escrow_id: EscrowId,
+            buyer_amount: Balance,
+            seller_amount: Balance
+        ) -> Result<(), Error> {
+            // Check if the contract has been shut down.
+            self.ensure_running()?;
+
+            let mut escrow = self.escrows.get(escrow_id).ok_or(Error::NotFound)?;
+
+            // Check if the caller is the arbiter.
+            let caller = self.env().caller();
+            if Some(caller) != self.arbiter_of(&escrow) {
+                return Err(Error::Unauthorized);
+            }
+
+            // Check if the arbiter would resolve its own escrow.
+            if caller == escrow.buyer || caller == escrow.seller {
+                return Err(Error::InvalidParticipants);
+            }
+
+            // Check if the escrow is disputed.
+            if escrow.state != EscrowState::Disputed {
+                return Err(Error::InvalidState);
+            }
+
+            // Check if the amounts add up to the escrow amount after the arbiter fee.
+            let arbiter_fee = Self::apply_bps(escrow.amount, escrow.terms.arbiter_fee_bps)?;
+            if buyer_amount.checked_add(seller_amount) != Some(escrow.amount - arbiter_fee) {
+                return Err(Error::InvalidAmount);
+            }
+
+            // Check if both parties posted the required dispute deposit.
+            let deposit = escrow.terms.dispute_deposit;
+            if deposit > 0 && !(escrow.buyer_dispute_deposited && escrow.seller_dispute_deposited) {
+                return Err(Error::DisputeDepositMissing);
+            }
+
+            // Compensate the arbiter, then pay out both amounts.
+            if arbiter_fee > 0 {
+                self
+                    .env()
+                    .transfer(caller, arbiter_fee)
+                    .map_err(|_| Error::TransferFailed)?;
+            }
+            if seller_amount > 0 {
+                escrow.seller_received = self.pay_seller(&escrow, seller_amount)?;
+            }
+            if buyer_amount > 0 {
+                self.refund(&escrow, buyer_amount)?;
+            }
+
+            // Return the seller's stake, which the ruling does not divide.
+            self.release_stake(&escrow, escrow.seller)?;
+
+            // Refund the winner's dispute deposit and forfeit the loser's to the arbiter.
+            if deposit > 0 {
+                let (buyer_refund, seller_refund, forfeited) = match buyer_amount.cmp(&seller_amount) {
+                    core::cmp::Ordering::Greater => (deposit, 0, deposit),
+                    core::cmp::Ordering::Less => (0, deposit, deposit),
+                    core::cmp::Ordering::Equal => (deposit, deposit, 0),
+                };
+                for (account, value) in [(escrow.buyer, buyer_refund), (escrow.seller, seller_refund), (caller, forfeited)] {
+                    if value > 0 {
+                        self.env().transfer(account, value).map_err(|_| Error::TransferFailed)?;
+                    }
+                }
+            }
+
+            // Update the escrow state.
+            self.set_state(escrow_id, &mut escrow, EscrowState::Completed)?;
+
+            // Save changes back to storage
+            self.escrows.insert(escrow_id, &escrow);
+
+            // Emit an event to notify about the resolution.
+            let seq = self.next_seq()?;
+            self.env().emit_event(DisputeResolved {
+                seq,
+                escrow_id,
+                buyer_amount: self.event_amount(escrow_id, buyer_amount),
+                seller_amount: self.event_amount(escrow_id, seller_amount),
+                arbiter_fee: self.event_amount(escrow_id, arbiter_fee),
+            });
+
+            Ok(())
+        }
+
+        /// Refunds a disputed escrow to the buyer once its dispute timeout has passed without a
+        /// resolution or settlement.
+        ///
+        /// # Arguments
+        ///
+        /// * `escrow_id` - The ID of the escrow.
+        ///
+        /// # Returns
+        ///
+        /// * `Ok(())` - If the escrow was refunded.
+        /// * `Err(Error)` - An error if the operation failed.
+        #[ink(message)]
+        pub fn claim_dispute_timeout(&mut self, escrow_id: EscrowId) -> Result<(), Error> {
+            // Check if the contract has been shut down.
+            self.ensure_running()?;
+
+            let mut escrow = self.escrows.get(escrow_id).ok_or(Error::NotFound)?;
+
+            // Check if the caller is the buyer.
+            if self.env().caller() != escrow.buyer {
+                return Err(Error::NotBuyer);
+            }
+
+            // Check if the escrow is still disputed and can time out.
+            if escrow.state != EscrowState::Disputed {
+                return Err(Error::InvalidState);
+            }
+            let timeout = escrow.terms.dispute_timeout.ok_or(Error::InvalidState)?;
+            let disputed_at = escrow.disputed_at.ok_or(Error::InvalidState)?;
+
+            // Check if the timeout has passed.
+            if self.now(&escrow) < disputed_at.saturating_add(timeout) {
+                return Err(Error::DeadlineNotReached);
+            }
+
+            // Refund the buyer in full and return the seller's stake and the dispute deposits.
+            self.refund(&escrow, escrow.amount)?;
+            self.release_stake(&escrow, escrow.seller)?;
+            self.return_dispute_deposits(&escrow)?;
+
+            // Update the escrow state, dropping any settlement that was never accepted.
+            self.set_state(escrow_id, &mut escrow, EscrowState::Canceled)?;
+            self.pending_settlements.remove(escrow_id);
+
+            // Save the modified escrow back to storage
+            self.escrows.insert(escrow_id, &escrow);
+
+            // Emit events to notify about the cancellation and the timeout.
+            let seq = self.next_seq()?;
+            self.env().emit_event(Canceled {
+                seq,
+                escrow_id,
+                by: self.env().caller(),
+                reason: CancelReason::Expired,
+                refunded: self.event_amount(escrow_id, escrow.amount),
+                penalty: self.event_amount(escrow_id, 0),
+            });
+            let seq = self.next_seq()?;
+            self.env().emit_event(DisputeTimedOut {
+                seq,
+                escrow_id,
+                refunded: self.event_amount(escrow_id, escrow.amount),
+            });
+
+            Ok(())
+        }
+
+        /// Requests a later release deadline, which applies once the other party approves it.
+        ///
+        /// A later request replaces an earlier one.
+        ///
+        /// # Arguments
+        ///
+        /// * `escrow_id` - The ID of the escrow.
+        /// * `new_deadline` - The requested release deadline, in the escrow's deadline kind.
+        ///
+        /// # Returns
+        ///
+        /// * `Ok(())` - If the extension was requested.
+        /// * `Err(Error)` - An error if the operation failed.
+        #[ink(message)]
+        pub fn request_extension(
+            &mut self,
+            escrow_id: EscrowId,
+            new_deadline: u64
+        ) -> Result<(), Error> {
+            let escrow = self.escrows.get(escrow_id).ok_or(Error::NotFound)?;
+            // Get the caller's account ID.
+            let caller = self.env().caller();
+
+            // Check if the caller is the buyer or the seller.
+            if caller != escrow.buyer && caller != escrow.seller {
+                return Err(Error::Unauthorized);
+            }
+
+            // Check if the escrow is still open and the new deadline extends the current one.
+            if Self::is_terminal(&escrow.state) || !Self::extends_deadline(&escrow, new_deadline) {
+                return Err(Error::InvalidState);
+            }
+
+            // Record the request.
+            self.pending_extensions.insert(escrow_id, &(new_deadline, caller));
+
+            // Emit an event to notify about the request.
+            let seq = self.next_seq()?;
+            self.env().emit_event(ExtensionRequested { seq, escrow_id, new_deadline, requester: caller });
+
+            Ok(())
+        }
+
+        /// Approves the extension requested by the other party, applying the new deadline.
+        ///
+        /// # Arguments
+        ///
+        /// * `escrow_id` - The ID of the escrow.
+        ///
+        /// # Returns
+        ///
+        /// * `Ok(())` - If the new deadline was applied.
+        /// * `Err(Error)` - An error if the operation failed.
+        #[ink(message)]
+        pub fn approve_extension(&mut self, escrow_id: EscrowId) -> Result<(), Error> {
+            let mut escrow = self.escrows.get(escrow_id).ok_or(Error::NotFound)?;
+            // Get the caller's account ID.
+            let caller = self.env().caller();
+
+            // Check if the caller is the buyer or the seller.
+            if caller != escrow.buyer && caller != escrow.seller {
+                return Err(Error::Unauthorized);
+            }
+
+            // Check if there is a request from the other party.
+            let (new_deadline, requester) = self.pending_extensions.get(escrow_id).ok_or(Error::NotFound)?;
+            if requester == caller {
+                return Err(Error::Unauthorized);
+            }
+
+            // Check if the request still extends the deadline of an open escrow.
+            if Self::is_terminal(&escrow.state) || !Self::extends_deadline(&escrow, new_deadline) {
+                return Err(Error::InvalidState);
+            }
+
+            // Apply the new deadline.
+            escrow.terms.release_deadline = Some(new_deadline);
+            self.pending_extensions.remove(escrow_id);
+
+            // Save changes back to storage
+            self.escrows.insert(escrow_id, &escrow);
+
+            // Emit an event to notify about the extension.
+            let seq = self.next_seq()?;
+            self.env().emit_event(ExtensionApplied { seq, escrow_id, new_deadline });
+
+            Ok(())
+        }
+
+        /// Proposes an arbiter for a funded escrow without one, which applies once the other
+        /// party accepts it.
+        ///
+        /// A later proposal replaces an earlier one.
+        ///
+        /// # Arguments
+        ///
+        /// * `escrow_id` - The ID of the escrow.
+        /// * `arbiter` - The account ID of the proposed arbiter.
+        ///
+        /// # Returns
+        ///
+        /// * `Ok(())` - If the arbiter was proposed.
+        /// * `Err(Error)` - An error if the operation failed.
+        #[ink(message)]
+        pub fn propose_arbiter(&mut self, escrow_id: EscrowId, arbiter: AccountId) -> Result<(), Error> {
+            let escrow = self.escrows.get(escrow_id).ok_or(Error::NotFound)?;
+            // Get the caller's account ID.
+            let caller = self.env().caller();
+
+            // Check if the caller is the buyer or the seller.
+            if caller != escrow.buyer && caller != escrow.seller {
+                return Err(Error::Unauthorized);
+            }
+
+            // Check if the escrow is funded and has no arbiter yet.
+            if escrow.state != EscrowState::Funded || self.arbiter_of(&escrow).is_some() {
+                return Err(Error::InvalidState);
+            }
+
+            // Check if the arbiter is a real account that would not resolve its own escrow.
+            Self::ensure_not_zero(arbiter)?;
+            if arbiter == escrow.buyer || arbiter == escrow.seller {
+                return Err(Error::InvalidParticipants);
+            }
+
+            // Record the proposal.
+            self.pending_arbiters.insert(escrow_id, &(arbiter, caller));
+
+            // Emit an event to notify about the proposal.
+            let seq = self.next_seq()?;
+            self.env().emit_event(ArbiterProposed { seq, escrow_id, arbiter, proposer: caller });
+
+            Ok(())
+        }
+
+        /// Accepts the arbiter proposed by the other party, assigning it to the escrow.
+        ///
+        /// # Arguments
+        ///
+        /// * `escrow_id` - The ID of the escrow.
+        ///
+        /// # Returns
+        ///
+        /// * `Ok(())` - If the arbiter was assigned.
+        /// * `Err(Error)` - An error if the operation failed.
+        #[ink(message)]
+        pub fn accept_arbiter(&mut self, escrow_id: EscrowId) -> Result<(), Error> {
+            let mut escrow = self.escrows.get(escrow_id).ok_or(Error::NotFound)?;
+            // Get the caller's account ID.
+            let caller = self.env().caller();
+
+            // Check if the caller is the buyer or the seller.
+            if caller != escrow.buyer && caller != escrow.seller {
+                return Err(Error::Unauthorized);
+            }
+
+            // Check if there is a proposal from the other party.
+            let (arbiter, proposer) = self.pending_arbiters.get(escrow_id).ok_or(Error::NotFound)?;
+            if proposer == caller {
+                return Err(Error::Unauthorized);
+            }
+
+            // Check if the escrow is still funded and has no arbiter yet.
+            if escrow.state != EscrowState::Funded || self.arbiter_of(&escrow).is_some() {
+                return Err(Error::InvalidState);
+            }
+
+            // Assign the arbiter.
+            escrow.arbiter = Some(arbiter);
+            self.pending_arbiters.remove(escrow_id);
+
+            // Save changes back to storage
+            self.escrows.insert(escrow_id, &escrow);
+
+            // Emit an event to notify about the assignment.
+            let seq = self.next_seq()?;
+            self.env().emit_event(ArbiterSet { seq, escrow_id, arbiter });
+
+            Ok(())
+        }
+
+        /// Proposes to settle a funded or disputed escrow by splitting its amount between the parties.
+        ///
+        /// A later proposal replaces an earlier one. Split escrows cannot be settled this way,
+        /// since their payout is fixed by the recipients' shares.
+        ///
+        /// # Arguments
+        ///
+        /// * `escrow_id` - The ID of the escrow.
+        /// * `buyer_share` - The share refunded to the buyer.
+        /// * `seller_share` - The share paid to the seller.
+        ///
+        /// # Returns
+        ///
+        /// * `Ok(())` - If the settlement was proposed.
+        /// * `Err(Error)` - An error if the operation failed.
+        #[ink(message)]
+        pub fn propose_settlement(
+            &mut self,
+            escrow_id: EscrowId,
+            buyer_share: Balance,
+            seller_share: Balance
+        ) -> Result<(), Error> {
+            let escrow = self.escrows.get(escrow_id).ok_or(Error::NotFound)?;
+            // Get the caller's account ID.
+            let caller = self.env().caller();
+
+            // Check if the caller is the buyer or the seller.
+            if caller != escrow.buyer && caller != escrow.seller {
+                return Err(Error::Unauthorized);
+            }
+
+            // Check if the escrow holds funds and pays out to a single seller.
+            if !Self::holds_funds(&escrow.state) || !escrow.recipients.is_empty() {
+                return Err(Error::InvalidState);
+            }
+
+            // Check if the shares add up to the escrow amount.
+            if buyer_share.checked_add(seller_share) != Some(escrow.amount) {
+                return Err(Error::ShareSumMismatch);
+            }
+
+            // Record the proposal.
+            self.pending_settlements.insert(escrow_id, &Settlement {
+                buyer_share,
+                seller_share,
+                proposer: caller,
+            });
+
+            // Emit an event to notify about the proposal.
+            let seq = self.next_seq()?;
+            self.env().emit_event(SettlementProposed {
+                seq,
+                escrow_id,
+                buyer_share: self.event_amount(escrow_id, buyer_share),
+                seller_share: self.event_amount(escrow_id, seller_share),
+                proposer: caller,
+            });
+
+            Ok(())
+        }
+
+        /// Accepts the settlement proposed by the other party, paying out both shares.
+        ///
+        /// The seller share is paid like any release: to the payout address, minus the platform fee.
+        ///
+        /// # Arguments
+        ///
+        /// * `escrow_id` - The ID of the escrow.
+        ///
+        /// # Returns
+        ///
+        /// * `Ok(())` - If the settlement was executed.
+        /// * `Err(Error)` - An error if the operation failed.
+        #[ink(message)]
+        pub fn accept_settlement(&mut self, escrow_id: EscrowId) -> Result<(), Error> {
+            // Check if the contract has been shut down.
+            self.ensure_running()?;
+
+            let mut escrow = self.escrows.get(escrow_id).ok_or(Error::NotFound)?;
+            // Get the caller's account ID.
+            let caller = self.env().caller();
+
+            // Check if the caller is the buyer or the seller.
+            if caller != escrow.buyer && caller != escrow.seller {
+                return Err(Error::Unauthorized);
+            }
+
+            // Check if the escrow still holds funds.
+            if !Self::holds_funds(&escrow.state) {
+                return Err(Error::InvalidState);
+            }
+
+            // Check if there is a proposal from the other party.
+            let settlement = self.pending_settlements.get(escrow_id).ok_or(Error::NotFound)?;
+            if settlement.proposer == caller {
+                return Err(Error::Unauthorized);
+            }
+
+            // Pay out both shares.
+            if settlement.buyer_share > 0 {
+                self.refund(&escrow, settlement.buyer_share)?;
+            }
+            if settlement.seller_share > 0 {
+                escrow.seller_received = self.pay_seller(&escrow, settlement.seller_share)?;
+            }
+
+            // Return the seller's stake and the dispute deposits, which the settlement does not divide.
+            self.release_stake(&escrow, escrow.seller)?;
+            self.return_dispute_deposits(&escrow)?;
+
+            // Update the escrow state.
+            self.pending_settlements.remove(escrow_id);
+            self.set_state(escrow_id, &mut escrow, EscrowState::Completed)?;
+
+            // Save changes back to storage
+            self.escrows.insert(escrow_id, &escrow);
+
+            // Emit an event to notify about the settlement.
+            let seq = self.next_seq()?;
+            self.env().emit_event(SettlementExecuted {
+                seq,
+                escrow_id,
+                buyer_share: self.event_amount(escrow_id, settlement.buyer_share),
+                seller_share: self.event_amount(escrow_id, settlement.seller_share),
+            });
+
+            Ok(())
+        }
+
+        /// Withdraws the caller's approval of a funded escrow before it is completed.
+        ///
+        /// # Arguments
+        ///
+        /// * `escrow_id` - The ID of the escrow.
+        ///
+        /// # Returns
+        ///
+        /// * `Ok(())` - If the approval was withdrawn.
+        /// * `Err(Error)` - An error if the operation failed.
+        #[ink(message)]
+        pub fn revoke_approval(&mut self, escrow_id: EscrowId) -> Result<(), Error> {
+            let mut escrow = self.escrows.get(escrow_id).ok_or(Error::NotFound)?;
+            // Get the caller's account ID.
+            let caller = self.env().caller();
+
+            // Check if the escrow is still awaiting approvals.
+            if escrow.state != EscrowState::Funded {
+                return Err(Error::InvalidState);
+            }
+
+            // Clear the caller's approval, if there is one.
+            match caller {
+                _ if caller == escrow.buyer => {
+                    if !escrow.buyer_approved {
+                        return Err(Error::InvalidState);
+                    }
+                    escrow.buyer_approved = false;
+                    escrow.buyer_approved_at = None;
+                }
+                _ if caller == escrow.seller => {
+                    if !escrow.seller_approved {
+                        return Err(Error::InvalidState);
+                    }
+                    escrow.seller_approved = false;
+                    escrow.seller_approved_at = None;
+                }
+                _ => {
+                    return Err(Error::Unauthorized);
+                }
+            }
+
+            // Save changes back to storage
+            self.escrows.insert(escrow_id, &escrow);
+
+            // Emit an event to notify about the withdrawn approval.
+            let seq = self.next_seq()?;
+            self.env().emit_event(ApprovalRevoked { seq, escrow_id, party: caller });
+
+            Ok(())
+        }
+
+        /// Clears a participant's approval once it is older than the escrow's approval TTL.
+        ///
+        /// Anyone may call this, which keeps the stored approvals consistent with their expiry.
+        ///
+        /// # Arguments
+        ///
+        /// * `escrow_id` - The ID of the escrow.
+        /// * `party` - The account ID of the participant whose approval is stale.
+        ///
+        /// # Returns
+        ///
+        /// * `Ok(())` - If the stale approval was cleared.
+        /// * `Err(Error)` - An error if the operation failed.
+        #[ink(message)]
+        pub fn prune_stale_approval(
+            &mut self,
+            escrow_id: EscrowId,
+            party: AccountId
+        ) -> Result<(), Error> {
+            let mut escrow = self.escrows.get(escrow_id).ok_or(Error::NotFound)?;
+
+            // Approvals only matter while the escrow is funded.
+            if escrow.state != EscrowState::Funded {
+                return Err(Error::InvalidState);
+            }
+
+            // Look up the approval timestamp of the given party.
+            let approved_at = match party {
+                _ if party == escrow.buyer => escrow.buyer_approved_at,
+                _ if party == escrow.seller => escrow.seller_approved_at,
+                _ => {
+                    return Err(Error::Unauthorized);
+                }
+            };
+
+            // Check if there is a stale approval to prune.
+            if !self.is_stale(&escrow, approved_at) {
+                return Err(Error::InvalidState);
+            }
+
+            // Clear the approval.
+            if party == escrow.buyer {
+                escrow.buyer_approved = false;
+                escrow.buyer_approved_at = None;
+            } else {
+                escrow.seller_approved = false;
+                escrow.seller_approved_at = None;
+            }
+
+            // Save changes back to storage
+            self.escrows.insert(escrow_id, &escrow);
+
+            // Emit an event to notify about the expired approval.
+            let seq = self.next_seq()?;
+            self.env().emit_event(ApprovalExpired { seq, escrow_id, party });
+
+            Ok(())
+        }
+
+        /// Sweeps a funded escrow whose release deadline has passed.
+        ///
+        /// Anyone may call this. The caller receives the escrow's keeper reward and the
+        /// rest of the amount is refunded to the buyer.
+        ///
+        /// # Arguments
+        ///
+        /// * `escrow_id` - The ID of the escrow.
+        ///
+        /// # Returns
+        ///
+        /// * `Ok(())` - If the escrow was successfully swept.
+        /// * `Err(Error)` - An error if the operation failed.
+        #[ink(message)]
+        pub fn sweep_expired(&mut self, escrow_id: EscrowId) -> Result<(), Error> {
+            // Check if the contract has been shut down.
+            self.ensure_running()?;
+
+            let mut escrow = self.escrows.get(escrow_id).ok_or(Error::NotFound)?;
+            // Get the caller's account ID (the keeper).
+            let keeper = self.env().caller();
+
+            // Check if the escrow is in the correct state, which also prevents double sweeps.
+            if escrow.state != EscrowState::Funded {
+                return Err(Error::InvalidState);
+            }
+
+            // Check if the release deadline has passed.
+            let deadline = escrow.terms.release_deadline.ok_or(Error::InvalidState)?;
+            if self.now(&escrow) <= deadline {
+                return Err(Error::DeadlineNotReached);
+            }
+
+            // Refund the buyer minus the keeper reward.
+            let reward = escrow.terms.keeper_reward;
+            self.refund(&escrow, escrow.amount - reward)?;
+
+            // Pay the keeper reward.
+            if reward > 0 {
+                self.env().transfer(keeper, reward).map_err(|_| Error::TransferFailed)?;
+            }
+
+            // Return the seller's stake.
+            self.release_stake(&escrow, escrow.seller)?;
+
+            // Update the escrow state.
+            self.set_state(escrow_id, &mut escrow, EscrowState::Canceled)?;
+
+            // Save the modified escrow back to storage
+            self.escrows.insert(escrow_id, &escrow);
+
+            // Emit events to notify about the cancellation and the sweep.
+            let seq = self.next_seq()?;
+            self.env().emit_event(Canceled {
+                seq,
+                escrow_id,
+                by: keeper,
+                reason: CancelReason::Expired,
+                refunded: self.event_amount(escrow_id, escrow.amount - reward),
+                penalty: self.event_amount(escrow_id, 0),
+            });
+            let seq = self.next_seq()?;
+            self.env().emit_event(Swept { seq, escrow_id, keeper });
+
+            Ok(())
+        }
+
+        /// Removes completed, canceled and returned escrows from storage.
+        ///
+        /// Anyone may call this, since terminal escrows no longer hold funds. IDs that are
+        /// unknown or not in a terminal state are skipped, as are completed escrows whose return
+        /// window is still open.
+        ///
+        /// # Arguments
+        ///
+        /// * `ids` - The IDs of the escrows to purge, at most `MAX_BATCH_SIZE`.
+        ///
+        /// # Returns
+        ///
+        /// * `Ok(u32)` - The number of escrows that were purged.
+        /// * `Err(Error)` - `BatchTooLarge` if too many IDs were given.
+        #[ink(message)]
+        pub fn purge_batch(&mut self, ids: Vec<EscrowId>) -> Result<u32, Error> {
+            // Check if the batch is within bounds.
+            if ids.len() > MAX_BATCH_SIZE as usize {
+                return Err(Error::BatchTooLarge);
+            }
+
+            let mut purged = 0;
+            for escrow_id in ids {
+                // Only remove escrows in a terminal state.
+                if let Some(escrow) = self.escrows.get(escrow_id) {
+                    if self.is_purgeable(&escrow) {
+                        self.remove_escrow(escrow_id, escrow.state);
+                        purged += 1;
+                    }
+                }
+            }
+
+            Ok(purged)
+        }
+
+        /// Executes the automatic action that is due on each of the given escrows.
+        ///
+        /// Anyone may call this. Escrows that do not exist or have no action due are reported
+        /// per escrow and skipped, as nothing was changed for them. An action that fails once it
+        /// has started fails the whole batch, so funds are never moved by half a batch.
+        ///
+        /// # Arguments
+        ///
+        /// * `ids` - The IDs of the escrows to process, at most `MAX_BATCH_SIZE`.
+        ///
+        /// # Returns
+        ///
+        /// * `Ok(Vec<ProcessOutcome>)` - The state of each escrow after its action, or why no
+        ///   action was taken.
+        /// * `Err(Error)` - An error if the batch is too large or an action failed.
+        #[ink(message)]
+        pub fn process_due(
+            &mut self,
+            ids: Vec<EscrowId>
+        ) -> Result<Vec<ProcessOutcome>, Error> {
+            // Check if the batch is within bounds.
+            if ids.len() > MAX_BATCH_SIZE as usize {
+                return Err(Error::BatchTooLarge);
+            }
+            // Check if the contract has been shut down.
+            self.ensure_running()?;
+
+            let mut outcomes = Vec::new();
+            for escrow_id in ids {
+                // Skip escrows without a due action, failing the batch once an action has started.
+                let outcome = match self.due_action(escrow_id) {
+                    Ok(action) => Ok(self.run_auto_action(escrow_id, action)?),
+                    Err(error) => Err(error),
+                };
+                outcomes.push((escrow_id, outcome));
+            }
+
+            Ok(outcomes)
+        }
+
+        // --- Helper functions ---
+
+        /// Checks that a buyer enforcing its allowlist allows the seller.
+        fn check_seller_allowed(&self, buyer: AccountId, seller: AccountId) -> Result<(), Error> {
+            if self.allowlist_enabled.contains(buyer)
+                && !self.allowed_sellers.get(buyer).unwrap_or_default().contains(&seller)
+            {
+                return Err(Error::SellerNotAllowed);
+            }
+            Ok(())
+        }
+
+        /// Rejects the zero account, to which funds sent would be lost.
+        fn ensure_not_zero(account: AccountId) -> Result<(), Error> {
+            if account == AccountId::from([0u8; 32]) {
+                return Err(Error::ZeroAddress);
+            }
+            Ok(())
+        }
+
+        /// Checks that the caller is the owner and that no admins need to confirm its actions.
+        fn check_sole_owner(&self) -> Result<(), Error> {
+            if self.env().caller() != self.owner || self.admin_threshold > 0 {
+                return Err(Error::Unauthorized);
+            }
+            Ok(())
+        }
+
+        /// Sets the platform fee once the caller is authorized.
+        fn apply_fee(&mut self, fee_bps: u16) -> Result<(), Error> {
+            // Check if the fee is a valid share.
+            if fee_bps > MAX_BPS {
+                return Err(Error::InvalidFee);
+            }
+
+            let old_fee_bps = core::mem::replace(&mut self.fee_bps, fee_bps);
+
+            // Emit an event to notify about the fee.
+            let seq = self.next_seq()?;
+            self.env().emit_event(FeeUpdated { seq, old_fee_bps, new_fee_bps: fee_bps });
+
+            Ok(())
+        }
+
+        /// Shuts the contract down once the caller is authorized.
+        fn apply_shutdown(&mut self) -> Result<(), Error> {
+            // Check if the contract has been shut down already.
+            self.ensure_running()?;
+
+            self.shut_down = true;
+
+            // Emit an event to notify about the shutdown.
+            let seq = self.next_seq()?;
+            self.env().emit_event(Shutdown { seq, by: self.env().caller() });
+
+            Ok(())
+        }
+
+        /// Replaces the contract code once the caller is authorized.
+        fn apply_code_hash(&mut self, code_hash: Hash) -> Result<(), Error> {
+            let old_code_hash = self.env().own_code_hash().map_err(|_| Error::UpgradeFailed)?;
+            self.env().set_code_hash(&code_hash).map_err(|_| Error::UpgradeFailed)?;
+            self.code_hash = Some(code_hash);
+
+            // Emit an event to notify about the upgrade.
+            let seq = self.next_seq()?;
+            self.env().emit_event(CodeUpgraded { seq, old_code_hash, new_code_hash: code_hash });
+
+            Ok(())
+        }
+
+        /// Sets the open escrow limit per buyer once the caller is authorized.
+        fn apply_max_open_per_buyer(&mut self, max_open_per_buyer: u32) -> Result<(), Error> {
+            let old_max_open_per_buyer = core::mem::replace(&mut self.max_open_per_buyer, max_open_per_buyer);
+
+            // Emit an event to notify about the limit.
+            let seq = self.next_seq()?;
+            self.env().emit_event(LimitsUpdated {
+                seq,
+                old_max_open_per_buyer,
+                new_max_open_per_buyer: max_open_per_buyer,
+                old_high_value_threshold: self.high_value_threshold,
+                new_high_value_threshold: self.high_value_threshold,
+            });
+
+            Ok(())
+        }
+
+        /// Enables or disables ID recycling once the caller is authorized.
+        fn apply_id_recycling(&mut self, enabled: bool) -> Result<(), Error> {
+            self.recycle_ids = enabled;
+
+            // Emit an event to notify about the setting.
+            let seq = self.next_seq()?;
+            self.env().emit_event(IdRecyclingSet { seq, enabled });
+
+            Ok(())
+        }
+
+        /// Sets the high-value threshold once the caller is authorized.
+        fn apply_high_value_threshold(&mut self, threshold: Balance) -> Result<(), Error> {
+            let old_high_value_threshold = core::mem::replace(&mut self.high_value_threshold, threshold);
+
+            // Emit an event to notify about the threshold.
+            let seq = self.next_seq()?;
+            self.env().emit_event(LimitsUpdated {
+                seq,
+                old_max_open_per_buyer: self.max_open_per_buyer,
+                new_max_open_per_buyer: self.max_open_per_buyer,
+                old_high_value_threshold,
+                new_high_value_threshold: threshold,
+            });
+
+            Ok(())
+        }
+
+        /// Sets the refund lock period once the caller is authorized.
+        fn apply_refund_lock_period(&mut self, period: u64) -> Result<(), Error> {
+            let old_period = core::mem::replace(&mut self.refund_lock_period, period);
+
+            // Emit an event to notify about the period.
+            let seq = self.next_seq()?;
+            self.env().emit_event(RefundLockPeriodUpdated { seq, old_period, new_period: period });
+
+            Ok(())
+        }
+
+        /// Sets or removes a buyer's fee override once the caller is authorized.
+        fn apply_fee_override(&mut self, account: AccountId, fee_bps: u16) -> Result<(), Error> {
+            // Remove the override when given the sentinel.
+            if fee_bps == FEE_OVERRIDE_REMOVED {
+                let old_fee_bps = self.fee_overrides.take(account);
+                if let Some(old) = old_fee_bps {
+                    self.toggle_fee_override(account, old);
+                }
+                let seq = self.next_seq()?;
+                self.env().emit_event(FeeOverrideSet { seq, account, old_fee_bps, fee_bps: None });
+                return Ok(());
+            }
+
+            // Check if the fee is a valid share.
+            if fee_bps > MAX_BPS {
+                return Err(Error::InvalidFee);
+            }
+
+            // Replace the previous override in the digest.
+            let old_fee_bps = self.fee_overrides.get(account);
+            if let Some(old) = old_fee_bps {
+                self.toggle_fee_override(account, old);
+            }
+            self.fee_overrides.insert(account, &fee_bps);
+            self.toggle_fee_override(account, fee_bps);
+
+            // Emit an event to notify about the override.
+            let seq = self.next_seq()?;
+            self.env().emit_event(FeeOverrideSet { seq, account, old_fee_bps, fee_bps: Some(fee_bps) });
+
+            Ok(())
+        }
+
+        /// Drains a batch of escrows of the shut-down contract once the caller is authorized.
+        fn apply_drain(&mut self, to: AccountId, max: u32) -> Result<u32, Error> {
+            // Check if the contract has been shut down.
+            if !self.shut_down {
+                return Err(Error::NotShutDown);
+            }
+            // Check if the safe address is a real account.
+            Self::ensure_not_zero(to)?;
+
+            // Cancel funded and disputed escrows in ID order.
+            let mut budget = max.min(MAX_BATCH_SIZE);
+            let mut canceled: u32 = 0;
+            for state in [EscrowState::Funded, EscrowState::Disputed] {
+                let mut from = 0;
+                while budget > 0 {
+                    let Some(escrow_id) = self.next_in_state(state, from) else {
+                        break;
+                    };
+                    self.drain_escrow(escrow_id)?;
+                    from = escrow_id.saturating_add(1);
+                    budget -= 1;
+                    canceled = canceled.saturating_add(1);
+                }
+            }
+
+            // Then cancel unfunded escrows holding installments or a seller stake, resuming after
+            // the last created escrow checked by an earlier call.
+            while budget > 0 {
+                let Some(escrow_id) = self.next_in_state(EscrowState::Created, self.drain_cursor) else {
+                    break;
+                };
+                budget -= 1;
+                self.drain_cursor = escrow_id.saturating_add(1);
+                let escrow = self.escrows.get(escrow_id).ok_or(Error::NotFound)?;
+                if escrow.deposited > 0 || escrow.seller_deposited {
+                    self.drain_escrow(escrow_id)?;
+                    canceled = canceled.saturating_add(1);
+                }
+            }
+
+            let remaining = self
+                .state_counts
+                .get(EscrowState::Funded)
+                .unwrap_or_default()
+                .saturating_add(self.state_counts.get(EscrowState::Disputed).unwrap_or_default())
+                .saturating_add(self.count_in_state_from(EscrowState::Created, self.drain_cursor));
+
+            // Transfer the entire balance once no escrow is left to cancel.
+            let mut amount = 0;
+            if remaining == 0 {
+                amount = self.env().balance();
+                self.total_locked = 0;
+                self.total_bonds = 0;
+                self.total_pending_refunds = 0;
+                self.env().transfer(to, amount).map_err(|_| Error::TransferFailed)?;
+            }
+
+            // Emit an event to notify about the drained batch.
+            let seq = self.next_seq()?;
+            self.env().emit_event(EmergencyDrained { seq, to, amount, canceled, remaining });
+
+            Ok(remaining)
+        }
+
+        /// Cancels an escrow holding funds for the emergency drain, refunding nothing.
+        fn drain_escrow(&mut self, escrow_id: EscrowId) -> Result<(), Error> {
+            let mut escrow = self.escrows.get(escrow_id).ok_or(Error::NotFound)?;
+
+            // Unlock the installments and stake of an escrow that is not yet funded.
+            if escrow.state == EscrowState::Created {
+                self.total_locked = self.total_locked.saturating_sub(escrow.deposited);
+                if escrow.seller_deposited {
+                    self.total_locked = self.total_locked.saturating_sub(escrow.seller_amount);
+                    self.total_bonds = self.total_bonds.saturating_sub(escrow.seller_amount);
+                }
+            }
+            self.set_state(escrow_id, &mut escrow, EscrowState::Canceled)?;
+
+            // Save changes back to storage
+            self.escrows.insert(escrow_id, &escrow);
+
+            // Emit an event to notify about the cancellation, refunding nothing.
+            let seq = self.next_seq()?;
+            self.env().emit_event(Canceled {
+                seq,
+                escrow_id,
+                by: self.env().caller(),
+                reason: CancelReason::Emergency,
+                refunded: self.event_amount(escrow_id, 0),
+                penalty: self.event_amount(escrow_id, 0),
+            });
+
+            Ok(())
+        }
+
+        /// Fails once the owner has shut the contract down.
+        fn ensure_running(&self) -> Result<(), Error> {
+            if self.shut_down {
+                return Err(Error::ShutDown);
+            }
+            Ok(())
+        }
+
+        /// Returns the position of the first settled escrow at or after a timestamp.
+        fn settled_position(&self, timestamp: u64) -> u32 {
+            let (mut low, mut high) = (0, self.settled_count);
+            while low < high {
+                let middle = low + (high - low) / 2;
+                match self.settled.get(middle) {
+                    Some((settled_at, _)) if settled_at < timestamp => low = middle + 1,
+                    _ => high = middle,
+                }
+            }
+            low
+        }
+
+        /// Returns the funds a funded escrow holds: the buyer's amount and its bonds.
+        fn held_amount(escrow: &Escrow) -> Balance {
+            escrow.amount.saturating_add(Self::bonded_amount(escrow))
+        }
+
+        /// Returns the bonds a funded escrow holds: the seller's stake and the posted dispute deposits.
+        fn bonded_amount(escrow: &Escrow) -> Balance {
+            let posted = u128::from(escrow.buyer_dispute_deposited) + u128::from(escrow.seller_dispute_deposited);
+            escrow.seller_amount.saturating_add(escrow.terms.dispute_deposit.saturating_mul(posted))
+        }
+
+        /// Moves an escrow to `Funded` once the buyer's amount and the seller's stake, if any,
+        /// are deposited, locking its held amount in place of the individual deposits.
+        fn fund_if_ready(&mut self, escrow_id: EscrowId, escrow: &mut Escrow) -> Result<(), Error> {
+            let staked = escrow.seller_amount == 0 || escrow.seller_deposited;
+            if escrow.deposited < escrow.amount || !staked {
+                return Ok(());
+            }
+
+            self.total_locked = self.total_locked.saturating_sub(Self::held_amount(escrow));
+            self.set_state(escrow_id, escrow, EscrowState::Funded)?;
+            escrow.ever_funded = true;
+            escrow.funded_at = Some(self.now(escrow));
+            Ok(())
+        }
+
+        /// Checks whether an escrow is below the high-value threshold or was confirmed by the buyer.
+        fn check_high_value_confirmed(&self, escrow: &Escrow) -> Result<(), Error> {
+            let high_value = self.high_value_threshold > 0 && escrow.amount >= self.high_value_threshold;
+            if high_value && !escrow.high_value_confirmed {
+                return Err(Error::ConfirmationRequired);
+            }
+            Ok(())
+        }
+
+        /// Checks that the oracle of an oracle-gated escrow confirmed delivery.
+        fn check_delivery_confirmed(escrow: &Escrow) -> Result<(), Error> {
+            if escrow.terms.oracle.is_some() && !escrow.delivery_confirmed {
+                return Err(Error::DeliveryNotConfirmed);
+            }
+            Ok(())
+        }
+
+        /// Clears the approvals of an escrow that are older than its approval TTL.
+        ///
+        /// Returns the parties whose approval was cleared.
+        fn expire_stale_approvals(&self, escrow: &mut Escrow) -> Vec<AccountId> {
+            let mut expired = Vec::new();
+            if escrow.buyer_approved && self.is_stale(escrow, escrow.buyer_approved_at) {
+                escrow.buyer_approved = false;
+                escrow.buyer_approved_at = None;
+                expired.push(escrow.buyer);
+            }
+            if escrow.seller_approved && self.is_stale(escrow, escrow.seller_approved_at) {
+                escrow.seller_approved = false;
+                escrow.seller_approved_at = None;
+                expired.push(escrow.seller);
+            }
+            expired
+        }
+
+        /// Returns the ID for a new escrow, reusing a discarded ID if recycling is enabled.
+        fn allocate_id(&mut self) -> Result<EscrowId, Error> {
+            // Reuse the most recently freed ID.
+            if self.recycle_ids && self.free_count > 0 {
+                self.free_count -= 1;
+                return self.free_ids.take(self.free_count).ok_or(Error::NotFound);
+            }
+
+            // Get the next ID, handling potential overflow.
+            let escrow_id = self.next_id;
+            self.next_id = escrow_id.checked_add(1).ok_or(Error::IdOverflow)?;
+            Ok(escrow_id)
+        }
+
+        /// Returns the arbiter of an escrow: the one agreed on by the parties, or the default arbiter.
+        fn arbiter_of(&self, escrow: &Escrow) -> Option<AccountId> {
+            escrow.arbiter.or(self.default_arbiter)
+        }
+
+        /// Returns the sequence number for the next event and advances the counter.
+        fn next_seq(&mut self) -> Result<u64, Error> {
+            let seq = self.seq;
+            self.seq = seq.checked_add(1).ok_or(Error::IdOverflow)?;
+            Ok(seq)
+        }
+
+        /// Checks whether a deadline is strictly later than the escrow's current release deadline.
+        fn extends_deadline(escrow: &Escrow, new_deadline: u64) -> bool {
+            escrow.terms.release_deadline.is_some_and(|deadline| new_deadline > deadline)
+        }
+
+        /// Returns the automatic action that is due on an escrow, without changing anything.
+        fn due_action(&self, escrow_id: EscrowId) -> Result<AutoAction, Error> {
+            let escrow = self.escrows.get(escrow_id).ok_or(Error::NotFound)?;
+            let (action, at) = Self::next_auto_action(&escrow).ok_or(Error::InvalidState)?;
+
+            // Check if the action is due.
+            if self.now(&escrow) < at {
+                return Err(Error::DeadlineNotReached);
+            }
+            Ok(action)
+        }
+
+        /// Executes a due automatic action on an escrow, returning its new state.
+        fn run_auto_action(
+            &mut self,
+            escrow_id: EscrowId,
+            action: AutoAction
+        ) -> Result<EscrowState, Error> {
+            let escrow = self.escrows.get(escrow_id).ok_or(Error::NotFound)?;
+            match action {
+                AutoAction::Sweep => self.sweep_expired(escrow_id)?,
+                AutoAction::Finalize => self.finalize(escrow_id)?,
+                AutoAction::ApprovalExpiry => {
+                    let party = if self.is_stale(&escrow, escrow.buyer_approved_at) {
+                        escrow.buyer
+                    } else {
+                        escrow.seller
+                    };
+                    self.prune_stale_approval(escrow_id, party)?
+                }
+            }
+
+            self.get_escrow_state(escrow_id).ok_or(Error::NotFound)
+        }
+
+        /// Returns the soonest automatic action of an escrow and when it becomes callable.
+        fn next_auto_action(escrow: &Escrow) -> Option<(AutoAction, u64)> {
+
+            // Automatic actions only apply to funded escrows.
+            if escrow.state != EscrowState::Funded {
+                return None;
+            }
+            let mut timers = Vec::new();
+            // Sweeping is possible right after the release deadline.
+            if let Some(deadline) = escrow.terms.release_deadline {
+                timers.push((AutoAction::Sweep, deadline.saturating_add(1)));
+            }
+            // Approvals can be pruned right after their TTL.
+            if let Some(ttl) = escrow.terms.approval_ttl {
+                let approvals = [escrow.buyer_approved_at, escrow.seller_approved_at];
+                for approved_at in approvals.into_iter().flatten() {
+                    let expires_at = approved_at.saturating_add(ttl).saturating_add(1);
+                    timers.push((AutoAction::ApprovalExpiry, expires_at));
+                }
+            }
+
+            // Finalizing is possible once the auto-release period has passed since the buyer approved.
+            if let (Some(period), Some(approved_at)) =
+                (escrow.terms.auto_release_period, escrow.buyer_approved_at)
+            {
+                timers.push((AutoAction::Finalize, approved_at.saturating_add(period)));
+            }
+
+            timers
+                .into_iter()
+                .min_by_key(|(_, at)| *at)
+        }
+
+        /// Moves an escrow to a new state, keeping the bookkeeping derived from states consistent.
+        ///
+        /// Every state transition goes through here, which maintains:
+        /// - the index of each state's escrows,
+        /// - the locked total of all escrows holding funds,
+        /// - the history of each escrow's state changes and the index of settled escrows,
+        /// - seller reputation and the number of open escrows per buyer.
+        ///
+        /// It also emits the `StateChanged` event shared by all transitions. Paying out the
+        /// funds, including the seller's stake of a mutual-stake escrow, is left to the caller.
+        fn set_state(&mut self, escrow_id: EscrowId, escrow: &mut Escrow, state: EscrowState) -> Result<(), Error> {
+            let was_held = Self::holds_funds(&escrow.state);
+            if !was_held && Self::holds_funds(&state) {
+                self.total_locked = self.total_locked.saturating_add(Self::held_amount(escrow));
+            } else if was_held && !Self::holds_funds(&state) {
+                self.total_locked = self.total_locked.saturating_sub(Self::held_amount(escrow));
+                self.total_bonds = self.total_bonds.saturating_sub(Self::bonded_amount(escrow));
+            }
+
+            // Move the escrow to the index of its new state.
+            self.unindex_state(escrow_id, escrow.state);
+            self.index_state(escrow_id, state)?;
+
+            // Keep the buyer's open escrow count in step.
+            let was_open = !Self::is_terminal(&escrow.state);
+            if was_open && Self::is_terminal(&state) && escrow.open_counted {
+                self.close_open(escrow.buyer);
+            } else if !was_open && !Self::is_terminal(&state) {
+                let open = self.open_counts.get(escrow.buyer).unwrap_or_default();
+                self.open_counts.insert(escrow.buyer, &open.saturating_add(1));
+                escrow.open_counted = true;
+            }
+
+            // Count the settlement towards the seller's reputation.
+            if state == EscrowState::Completed || (was_held && state == EscrowState::Canceled) {
+                let (mut completed, mut canceled) = self.reputation.get(escrow.seller).unwrap_or_default();
+                if state == EscrowState::Completed {
+                    completed = completed.saturating_add(1);
+                } else {
+                    canceled = canceled.saturating_add(1);
+                }
+                self.reputation.insert(escrow.seller, &(completed, canceled));
+            }
+
+            // Open the return window once the escrow completes.
+            if state == EscrowState::Completed {
+                escrow.return_deadline = escrow.terms.return_window.map(|window| self.now(escrow).saturating_add(window));
+            }
+
+            // Record when the escrow settles, in the index ordered by settlement time.
+            if !Self::is_terminal(&escrow.state) && Self::is_terminal(&state) {
+                let settled_at = self.env().block_timestamp();
+                escrow.settled_at = Some(settled_at);
+                self.settled.insert(self.settled_count, &(settled_at, escrow_id));
+                self.settled_count = self.settled_count.saturating_add(1);
+            } else if Self::is_terminal(&escrow.state) && !Self::is_terminal(&state) {
+                escrow.settled_at = None;
+            }
+
+            // Record the transition, keeping only the most recent entries.
+            let mut history = self.history.get(escrow_id).unwrap_or_default();
+            if history.len() >= MAX_HISTORY {
+                history.remove(0);
+            }
+            history.push(StateChange {
+                from_state: escrow.state,
+                to_state: state,
+                timestamp: self.env().block_timestamp(),
+                actor: self.env().caller(),
+            });
+            self.history.insert(escrow_id, &history);
+
+            // Emit an event to notify about the transition.
+            let seq = self.next_seq()?;
+            self.env().emit_event(StateChanged { seq, escrow_id, from: escrow.state, to: state });
+
+            escrow.state = state;
+            Ok(())
+        }
+
+        /// Decrements a buyer's number of open escrows.
+        fn close_open(&mut self, buyer: AccountId) {
+            let open = self.open_counts.get(buyer).unwrap_or_default();
+            self.open_counts.insert(buyer, &open.saturating_sub(1));
+        }
+
+        /// Checks whether a buyer may have another open escrow.
+        fn check_open_limit(&self, buyer: AccountId) -> Result<(), Error> {
+            let open = self.open_counts.get(buyer).unwrap_or_default();
+            if self.max_open_per_buyer > 0 && open >= self.max_open_per_buyer {
+                return Err(Error::TooManyOpenEscrows);
+            }
+            Ok(())
+        }
+
+        /// Marks an escrow in a state's index.
+        fn index_state(&mut self, escrow_id: EscrowId, state: EscrowState) -> Result<(), Error> {
+            let key = (state, escrow_id / STATE_WORD_BITS);
+            let bit = 1u128 << (escrow_id % STATE_WORD_BITS);
+            let word = self.state_words.get(key).unwrap_or_default();
+            if word & bit != 0 {
+                return Ok(());
+            }
+            let count = self.state_counts.get(state).unwrap_or_default();
+            self.state_counts.insert(state, &count.checked_add(1).ok_or(Error::Overflow)?);
+            self.state_words.insert(key, &(word | bit));
+            Ok(())
+        }
+
+        /// Clears an escrow from a state's index, leaving the other entries in place.
+        fn unindex_state(&mut self, escrow_id: EscrowId, state: EscrowState) {
+            let key = (state, escrow_id / STATE_WORD_BITS);
+            let bit = 1u128 << (escrow_id % STATE_WORD_BITS);
+            let word = self.state_words.get(key).unwrap_or_default();
+            if word & bit == 0 {
+                return;
+            }
+            if word == bit {
+                self.state_words.remove(key);
+            } else {
+                self.state_words.insert(key, &(word & !bit));
+            }
+            let count = self.state_counts.get(state).unwrap_or_default();
+            self.state_counts.insert(state, &count.saturating_sub(1));
+        }
+
+        /// Appends an escrow to the escrows listed for a buyer.
+        fn list_for_buyer(&mut self, buyer: AccountId, escrow_id: EscrowId) {
+            let buyer_count = self.buyer_counts.get(buyer).unwrap_or_default();
+            self.buyer_escrows.insert((buyer, buyer_count), &escrow_id);
+            self.buyer_positions.insert((buyer, escrow_id), &buyer_count);
+            self.buyer_counts.insert(buyer, &buyer_count.saturating_add(1));
+        }
+
+        /// Appends an escrow to the escrows listed for a seller.
+        fn list_for_seller(&mut self, seller: AccountId, escrow_id: EscrowId) {
+            let seller_count = self.seller_counts.get(seller).unwrap_or_default();
+            self.seller_escrows.insert((seller, seller_count), &escrow_id);
+            self.seller_positions.insert((seller, escrow_id), &seller_count);
+            self.seller_counts.insert(seller, &seller_count.saturating_add(1));
+        }
+
+        /// Removes an escrow from the escrows listed for a buyer.
+        ///
+        /// The buyer's last listed escrow takes the freed position, so the listing stays contiguous.
+        fn unlist_for_buyer(&mut self, buyer: AccountId, escrow_id: EscrowId) {
+            let Some(position) = self.buyer_positions.take((buyer, escrow_id)) else {
+                return;
+            };
+            let last = self.buyer_counts.get(buyer).unwrap_or_default().saturating_sub(1);
+            if position != last {
+                if let Some(moved) = self.buyer_escrows.get((buyer, last)) {
+                    self.buyer_escrows.insert((buyer, position), &moved);
+                    self.buyer_positions.insert((buyer, moved), &position);
+                }
+            }
+            self.buyer_escrows.remove((buyer, last));
+            self.buyer_counts.insert(buyer, &last);
+        }
+
+        /// Removes an escrow from the escrows listed for a seller.
+        ///
+        /// The seller's last listed escrow takes the freed position, so the listing stays contiguous.
+        fn unlist_for_seller(&mut self, seller: AccountId, escrow_id: EscrowId) {
+            let Some(position) = self.seller_positions.take((seller, escrow_id)) else {
+                return;
+            };
+            let last = self.seller_counts.get(seller).unwrap_or_default().saturating_sub(1);
+            if position != last {
+                if let Some(moved) = self.seller_escrows.get((seller, last)) {
+                    self.seller_escrows.insert((seller, position), &moved);
+                    self.seller_positions.insert((seller, moved), &position);
+                }
+            }
+            self.seller_escrows.remove((seller, last));
+            self.seller_counts.insert(seller, &last);
+        }
+
+        /// Removes an escrow from storage with its state index entry, pending proposals and history.
+        fn remove_escrow(&mut self, escrow_id: EscrowId, state: EscrowState) {
+            self.escrows.remove(escrow_id);
+            self.unindex_state(escrow_id, state);
+            self.pending_amendments.remove(escrow_id);
+            self.pending_extensions.remove(escrow_id);
+            self.pending_settlements.remove(escrow_id);
+            self.pending_arbiters.remove(escrow_id);
+            self.amendment_history.remove(escrow_id);
+            self.history.remove(escrow_id);
+        }
+
+        /// Returns the lowest ID from `from` onwards in a state's index.
+        fn next_in_state(&self, state: EscrowState, from: EscrowId) -> Option<EscrowId> {
+            let last_word = self.next_id.checked_sub(1)? / STATE_WORD_BITS;
+            let mut word_index = from / STATE_WORD_BITS;
+            // Ignore the IDs before `from` in its own word.
+            let mut mask = u128::MAX << (from % STATE_WORD_BITS);
+            while word_index <= last_word {
+                let word = self.state_words.get((state, word_index)).unwrap_or_default() & mask;
+                if word != 0 {
+                    return Some(word_index * STATE_WORD_BITS + EscrowId::from(word.trailing_zeros()));
+                }
+                word_index += 1;
+                mask = u128::MAX;
+            }
+            None
+        }
+
+        /// Returns the number of IDs from `from` onwards in a state's index.
+        fn count_in_state_from(&self, state: EscrowState, from: EscrowId) -> u32 {
+            let Some(last_id) = self.next_id.checked_sub(1) else {
+                return 0;
+            };
+            let mut mask = u128::MAX << (from % STATE_WORD_BITS);
+            let mut count: u32 = 0;
+            for word_index in from / STATE_WORD_BITS..=last_id / STATE_WORD_BITS {
+                let word = self.state_words.get((state, word_index)).unwrap_or_default() & mask;
+                count = count.saturating_add(word.count_ones());
+                mask = u128::MAX;
+            }
+            count
+        }
+
+        /// Returns up to `limit` IDs of a state's index from `start` onwards, in ID order.
+        fn state_page(&self, state: EscrowState, start: EscrowId, limit: u32) -> Vec<EscrowId> {
+            let mut page = Vec::new();
+            let mut from = start;
+            while page.len() < limit.min(MAX_BATCH_SIZE) as usize {
+                let Some(escrow_id) = self.next_in_state(state, from) else {
+                    break;
+                };
+                page.push(escrow_id);
+                from = escrow_id.saturating_add(1);
+            }
+            page
+        }
+
+        /// Returns `bps` basis points of `amount`, rounded down.
+        fn apply_bps(amount: Balance, bps: u16) -> Result<Balance, Error> {
+            amount
+                .checked_mul(Balance::from(bps))
+                .map(|scaled| scaled / Balance::from(MAX_BPS))
+                .ok_or(Error::InvalidAmount)
+        }
+
+        /// Validates and stores a new escrow in the `Created` state.
+        ///
+        /// # Arguments
+        ///
+        /// * `buyer` - The account ID of the buyer.
+        /// * `seller` - The account ID of the seller.
+        /// * `amount` - The agreed amount to be transferred.
+        /// * `terms` - The optional terms of the escrow.
+        /// * `recipients` - The payout split, empty to pay the seller in full.
+        ///
+        /// # Returns
+        ///
+        /// * `Ok(EscrowId)` - The ID of the newly created escrow.
+        /// * `Err(Error)` - An error if the operation failed.
+        fn create_escrow(
+            &mut self,
+            buyer: AccountId,
+            seller: AccountId,
+            amount: Balance,
+            terms: EscrowTerms,
+            recipients: Vec<(AccountId, Balance)>
+        ) -> Result<EscrowId, Error> {
+            // Check if the contract has been shut down.
+            self.ensure_running()?;
+
+            // Check if the buyer, seller and oracle are real accounts.
+            Self::ensure_not_zero(buyer)?;
+            Self::ensure_not_zero(seller)?;
+            if let Some(oracle) = terms.oracle {
+                Self::ensure_not_zero(oracle)?;
+            }
+            // Check if the buyer and seller are the same account.
+            if buyer == seller {
+                return Err(Error::InvalidParticipants);
+            }
+            // Check if the buyer allows the seller.
+            self.check_seller_allowed(buyer, seller)?;
+            // Check if the arbiter would resolve its own escrow.
+            if self.default_arbiter.is_some_and(|arbiter| arbiter == buyer || arbiter == seller) {
+                return Err(Error::InvalidParticipants);
+            }
+            // Check if the oracle would confirm its own delivery.
+            if terms.oracle.is_some_and(|oracle| oracle == buyer || oracle == seller) {
+                return Err(Error::InvalidParticipants);
+            }
+            // Check the same for every other recipient of a split escrow.
+            for (recipient, _) in recipients.iter() {
+                self.check_seller_allowed(buyer, *recipient)?;
+                if Some(*recipient) == self.default_arbiter || Some(*recipient) == terms.oracle {
+                    return Err(Error::InvalidParticipants);
+                }
+            }
+            // Check if the amount is non-zero.
+            if amount == 0 {
+                return Err(Error::InvalidAmount);
+            }
+            // Check if the keeper reward can be paid out of the amount.
+            if terms.keeper_reward > amount {
+                return Err(Error::InvalidAmount);
+            }
+            // Check if the buyer may open another escrow, unless the seller invoices the buyer.
+            let open_counted = self.env().caller() == buyer;
+            if open_counted {
+                self.check_open_limit(buyer)?;
+            }
+            // Check if the cancellation penalty and arbiter fee are valid shares.
+            if terms.cancellation_penalty_bps > MAX_BPS || terms.arbiter_fee_bps > MAX_BPS {
+                return Err(Error::InvalidFee);
+            }
+            // Check if the memo fits.
+            if terms.memo.as_ref().is_some_and(|memo| memo.len() > MAX_MEMO_LEN) {
+                return Err(Error::MemoTooLong);
+            }
+
+            // Get the next available escrow ID.
+            let escrow_id = self.allocate_id()?;
+
+            // Create the new escrow data.
+            let escrow = Escrow {
+                buyer,
+                seller,
+                amount,
+                buyer_approved: false,
+                seller_approved: false,
+                state: EscrowState::Created,
+                terms,
+                buyer_approved_at: None,
+                seller_approved_at: None,
+                proposed_amount: None,
+                recipients,
+                deposit_mismatches: 0,
+                refund_address: None,
+                refund_splits: Vec::new(),
+                ever_funded: false,
+                funded_at: None,
+                arbiter: None,
+                deposited: 0,
+                payout_address: None,
+                disputed_at: None,
+                high_value_confirmed: false,
+                seller_amount: 0,
+                seller_deposited: false,
+                settled_at: None,
+                delivery_confirmed: false,
+                return_deadline: None,
+                return_requested: false,
+                seller_received: 0,
+                open_counted,
+                buyer_dispute_deposited: false,
+                seller_dispute_deposited: false,
+            };
+
+            // Insert the escrow data into the storage mapping.
+            self.escrows.insert(escrow_id, &escrow);
+            self.index_state(escrow_id, EscrowState::Created)?;
+            if open_counted {
+                let open = self.open_counts.get(buyer).unwrap_or_default();
+                self.open_counts.insert(buyer, &open.saturating_add(1));
+            }
+
+            // Record the escrow under both participants.
+            self.list_for_buyer(buyer, escrow_id);
+            self.list_for_seller(seller, escrow_id);
+
+            Ok(escrow_id)
+        }
+
+        /// Checks that the caller can approve an escrow, without changing anything.
+        ///
+        /// Returns the escrow with the caller's approval recorded and any stale approvals
+        /// cleared, alongside the parties whose approvals expired.
+        fn prepare_completion(&self, escrow_id: EscrowId) -> Result<(Escrow, Vec<AccountId>), Error> {
+            // Check if the contract has been shut down.
+            self.ensure_running()?;
+
+            // Get owned Escrow value
+            let mut escrow = self.escrows.get(escrow_id).ok_or(Error::NotFound)?;
+
+            // Check if the escrow is in the correct state.
+            if escrow.state != EscrowState::Funded {
+                return Err(Error::InvalidState);
+            }
+
+            // Expire approvals older than the approval TTL, so they must be given again.
+            let expired = self.expire_stale_approvals(&mut escrow);
+
+            // Pass owned value to approve function and get updated escrow
+            escrow = self.approve(escrow, self.env().caller())?;
+
+            // Check if the final release is gated on a block height that has not been reached,
+            // on the buyer's confirmation of a high-value escrow or on the oracle's confirmation of delivery.
+            if Self::has_required_approvals(&escrow) {
+                if let Some(release_block) = escrow.terms.release_after_block {
+                    if self.env().block_number() < release_block {
+                        return Err(Error::BlockNotReached);
+                    }
+                }
+                self.check_high_value_confirmed(&escrow)?;
+                Self::check_delivery_confirmed(&escrow)?;
+            }
+
+            Ok((escrow, expired))
+        }
+
+        /// Saves an approval prepared by `prepare_completion`, completing the escrow once the
+        /// approvals required by its approval mode are present.
+        ///
+        /// The seller is paid only after the escrow has been saved as completed.
+        fn apply_completion(
+            &mut self,
+            escrow_id: EscrowId,
+            mut escrow: Escrow,
+            expired: Vec<AccountId>
+        ) -> Result<(), Error> {
+            // Emit an event for each approval that expired.
+            for party in expired {
+                let seq = self.next_seq()?;
+                self.env().emit_event(ApprovalExpired { seq, escrow_id, party });
+            }
+
+            // Save changes back to storage
+            self.escrows.insert(escrow_id, &escrow);
+
+            // Check if the parties required by the approval mode have approved.
+            if Self::has_required_approvals(&escrow) {
+                // Work out the payout to the seller.
+                let (transfers, fee) = self.seller_transfers(&escrow, escrow.amount)?;
+                escrow.seller_received = escrow.amount - fee;
+
+                // Update the escrow state.
+                self.set_state(escrow_id, &mut escrow, EscrowState::Completed)?;
+
+                // Save changes back to storage
+                self.escrows.insert(escrow_id, &escrow);
+
+                // Emit an event to notify about the completion.
+                let seq = self.next_seq()?;
+                self.env().emit_event(Completed {
+                    seq,
+                    escrow_id,
+                    approver: self.env().caller(),
+                    amount: self.event_amount(escrow_id, escrow.amount),
+                });
+
+                // Transfer the funds to the seller and the seller's stake to the buyer.
+                for (account, value) in transfers {
+                    self.env().transfer(account, value).map_err(|_| Error::TransferFailed)?;
+                }
+                self.release_stake(&escrow, escrow.buyer)?;
+            }
+
+            Ok(())
+        }
+
+        /// Transfers an amount of the escrow to the seller minus the platform fee.
+        ///
+        /// Returns what the seller's recipients received, net of the fee.
+        fn pay_seller(&self, escrow: &Escrow, amount: Balance) -> Result<Balance, Error> {
+            let (transfers, fee) = self.seller_transfers(escrow, amount)?;
+            for (account, value) in transfers {
+                self.env().transfer(account, value).map_err(|_| Error::TransferFailed)?;
+            }
+            Ok(amount - fee)
+        }
+
+        /// Computes the transfers paying an amount of the escrow to the seller.
+        ///
+        /// Split escrows pay each recipient in proportion to their share, with the last
+        /// recipient receiving any rounding remainder. The platform fee collected from all
+        /// recipients is paid to the owner last, and is returned alongside the transfers.
+        fn seller_transfers(
+            &self,
+            escrow: &Escrow,
+            amount: Balance
+        ) -> Result<(Vec<(AccountId, Balance)>, Balance), Error> {
+            let fee_bps = self.fee_overrides.get(escrow.buyer).unwrap_or(self.fee_bps);
+            let payouts = match escrow.recipients.split_last() {
+                None => ink::prelude::vec![(Self::payout_recipient(escrow), amount)],
+                Some(_) if amount == escrow.amount => escrow.recipients.clone(),
+                Some(((last, _), rest)) => {
+                    let mut payouts = Vec::new();
+                    let mut remaining = amount;
+                    for (recipient, share) in rest.iter() {
+                        let part = share
+                            .checked_mul(amount)
+                            .map(|scaled| scaled / escrow.amount)
+                            .ok_or(Error::InvalidAmount)?;
+                        payouts.push((*recipient, part));
+                        remaining = remaining.checked_sub(part).ok_or(Error::Overflow)?;
+                    }
+                    payouts.push((*last, remaining));
+                    payouts
+                }
+            };
+
+            // Pay each recipient their share minus the platform fee.
+            let mut transfers = Vec::new();
+            let mut fee_total: Balance = 0;
+            for (recipient, share) in payouts {
+                let fee = Self::apply_bps(share, fee_bps)?;
+                transfers.push((recipient, share - fee));
+                fee_total = fee_total.checked_add(fee).ok_or(Error::Overflow)?;
+            }
+
+            // Collect the platform fee.
+            if fee_total > 0 {
+                transfers.push((self.owner, fee_total));
+            }
+            Ok((transfers, fee_total))
+        }
+
+        /// Checks whether the approvals required by the escrow's approval mode are present.
+        fn has_required_approvals(escrow: &Escrow) -> bool {
+            match escrow.terms.approval_mode {
+                ApprovalMode::BothParties => escrow.buyer_approved && escrow.seller_approved,
+                ApprovalMode::BuyerOnly => escrow.buyer_approved,
+                ApprovalMode::SellerOnly => escrow.seller_approved,
+            }
+        }
+
+        /// Transfers the seller's stake of a funded mutual-stake escrow, if any, to an account.
+        fn release_stake(&self, escrow: &Escrow, to: AccountId) -> Result<(), Error> {
+            if escrow.seller_amount > 0 {
+                self.env().transfer(to, escrow.seller_amount).map_err(|_| Error::TransferFailed)?;
+            }
+            Ok(())
+        }
+
+        /// Returns the dispute deposits of an escrow to the parties that posted them.
+        fn return_dispute_deposits(&self, escrow: &Escrow) -> Result<(), Error> {
+            let posted = [
+                (escrow.buyer, escrow.buyer_dispute_deposited),
+                (escrow.seller, escrow.seller_dispute_deposited),
+            ];
+            for (account, _) in posted.into_iter().filter(|(_, deposited)| *deposited) {
+                self
+                    .env()
+                    .transfer(account, escrow.terms.dispute_deposit)
+                    .map_err(|_| Error::TransferFailed)?;
+            }
+            Ok(())
+        }
+
+        /// Transfers an amount owed by an escrow to an account, keeping it claimable with
+        /// `claim_refund` if the transfer fails.
+        fn transfer_or_owe(&mut self, escrow_id: EscrowId, account: AccountId, amount: Balance) -> Result<(), Error> {
+            if self.env().transfer(account, amount).is_err() {
+                let owed = self.pending_refunds.get(account).unwrap_or_default();
+                self.pending_refunds.insert(account, &owed.saturating_add(amount));
+                self.total_locked = self.total_locked.saturating_add(amount);
+                self.total_pending_refunds = self.total_pending_refunds.saturating_add(amount);
+                let seq = self.next_seq()?;
+                self.env().emit_event(RefundPending {
+                    seq,
+                    escrow_id,
+                    account,
+                    amount: self.event_amount(escrow_id, amount),
+                });
+            }
+            Ok(())
+        }
+
+        /// Refunds an amount of an escrow, split across its refund splits if set.
+        ///
+        /// The last split receives any rounding remainder, so the full amount is always paid out.
+        fn refund(&self, escrow: &Escrow, amount: Balance) -> Result<(), Error> {
+            for (account, share) in Self::refund_payouts(escrow, amount)? {
+                self.env().transfer(account, share).map_err(|_| Error::TransferFailed)?;
+            }
+            Ok(())
+        }
+
+        /// Splits a refund of an escrow into the amounts owed to each refund recipient.
+        fn refund_payouts(escrow: &Escrow, amount: Balance) -> Result<Vec<(AccountId, Balance)>, Error> {
+            let Some(((last, _), rest)) = escrow.refund_splits.split_last() else {
+                return Ok(ink::prelude::vec![(Self::refund_recipient(escrow), amount)]);
+            };
+            let mut payouts = Vec::new();
+            let mut remaining = amount;
+            for (account, bps) in rest.iter() {
+                let share = Self::apply_bps(amount, *bps)?;
+                payouts.push((*account, share));
+                remaining -= share;
+            }
+            payouts.push((*last, remaining));
+            Ok(payouts)
+        }
+
+        /// Returns the account that receives payouts of an escrow without split recipients.
+        fn payout_recipient(escrow: &Escrow) -> AccountId {
+            escrow.payout_address.unwrap_or(escrow.seller)
+        }
+
+        /// Returns the account that receives refunds of an escrow.
+        fn refund_recipient(escrow: &Escrow) -> AccountId {
+            escrow.refund_address.unwrap_or(escrow.buyer)
+        }
+
+        /// Checks whether the contract holds the funds of an escrow in this state.
+        fn holds_funds(state: &EscrowState) -> bool {
+            matches!(state, EscrowState::Funded | EscrowState::Disputed)
+        }
+
+        /// Checks whether an escrow state is final.
+        fn is_terminal(state: &EscrowState) -> bool {
+            matches!(state, EscrowState::Completed | EscrowState::Canceled | EscrowState::Returned)
+        }
+
+        /// Checks whether an escrow is terminal and can no longer be returned.
+        fn is_purgeable(&self, escrow: &Escrow) -> bool {
+            let returnable = escrow.state == EscrowState::Completed
+                && escrow.return_deadline.is_some_and(|deadline| self.now(escrow) <= deadline);
+            Self::is_terminal(&escrow.state) && !returnable
+        }
+
+        /// Adds a fee override to the digest, or removes it if it was already included.
+        fn toggle_fee_override(&mut self, account: AccountId, fee_bps: u16) {
+            let mut hash = <Blake2x256 as HashOutput>::Type::default();
+            ink::env::hash_encoded::<Blake2x256, _>(&(account, fee_bps), &mut hash);
+            for (digest, byte) in self.fee_override_digest.iter_mut().zip(hash) {
+                *digest ^= byte;
+            }
+        }
+
+        /// Returns the amount as it should be published in events.
+        fn event_amount(&self, escrow_id: EscrowId, amount: Balance) -> EventAmount {
+            if !self.obfuscate_amounts {
+                return EventAmount::Clear(amount);
+            }
+            let mut commitment = <Blake2x256 as HashOutput>::Type::default();
+            ink::env::hash_encoded::<Blake2x256, _>(&(escrow_id, amount), &mut commitment);
+            EventAmount::Commitment(Hash::from(commitment))
+        }
+
+        /// Returns the current moment on the clock the escrow's deadlines are measured against.
+        fn now(&self, escrow: &Escrow) -> u64 {
+            match escrow.terms.deadline_kind {
+                DeadlineKind::Timestamp => self.env().block_timestamp(),
+                DeadlineKind::BlockNumber => u64::from(self.env().block_number()),
+            }
+        }
+
+        /// Checks whether an approval given at `approved_at` is older than the escrow's approval TTL.
+        fn is_stale(&self, escrow: &Escrow, approved_at: Option<u64>) -> bool {
+            match (approved_at, escrow.terms.approval_ttl) {
+                (Some(approved_at), Some(ttl)) =>
+                    self.now(escrow) > approved_at.saturating_add(ttl),
+                _ => false,
+            }
+        }
+
+        /// Approves an escrow transaction for a given party.
+        ///
+        /// # Arguments
+        ///
+        /// * `escrow` - A mutable reference to the escrow data.
+        /// * `caller` - The account ID of the party approving.
+        ///
+        /// # Returns
+        ///
+        /// * `Ok(())` - If the approval was successful.
+        /// * `Err(Error)` - An error if the operation failed.
+        fn approve(&self, mut escrow: Escrow, caller: AccountId) -> Result<Escrow, Error> {
+            // Match the caller to the buyer or seller.
+            match caller {
+                // If the caller is the buyer.
+                _ if caller == escrow.buyer => {
+                    // Check if the buyer has already approved.
+                    if escrow.buyer_approved {
+                        return Err(Error::AlreadyApproved);
+                    }
+                    // Update the buyer's approval status.
+                    escrow.buyer_approved = true;
+                    escrow.buyer_approved_at = Some(self.now(&escrow));
+                }
+                // If the caller is the seller.
+                _ if caller == escrow.seller => {
+                    // Check if the seller has already approved.
+                    if escrow.seller_approved {
+                        return Err(Error::AlreadyApproved);
+                    }
+                    // Update the seller's approval status.
+                    escrow.seller_approved = true;
+                    escrow.seller_approved_at = Some(self.now(&escrow));
+                }
+                // If the caller is neither the buyer nor the seller.
+                _ => {
+                    return Err(Error::Unauthorized);
+                }
+            }
+            Ok(escrow)
+        }
+
+        /// Returns the data of an escrow.
+        ///
+        /// When amounts are obfuscated, only the buyer and seller can read the escrow.
+        ///
+        /// # Arguments
+        ///
+        /// * `escrow_id` - The ID of the escrow.
+        ///
+        /// # Returns
+        ///
+        /// * `Some(Escrow)` - The escrow data.
+        /// * `None` - If the escrow does not exist or the caller may not read it.
+        #[ink(message)]
+        pub fn get_escrow(&self, escrow_id: EscrowId) -> Option<Escrow> {
+            let escrow = self.escrows.get(escrow_id)?;
+            let caller = self.env().caller();
+            if self.obfuscate_amounts && caller != escrow.buyer && caller != escrow.seller {
+                return None;
+            }
+            Some(escrow)
+        }
+
+        /// Returns the data of several escrows at once.
+        ///
+        /// Each ID is resolved as by `get_escrow`. Only the first `MAX_BATCH_SIZE` IDs are
+        /// read, so the result may be shorter than the input.
+        ///
+        /// # Arguments
+        ///
+        /// * `ids` - The IDs of the escrows.
+        ///
+        /// # Returns
+        ///
+        /// * `Vec<Option<Escrow>>` - The escrow for each requested ID, in request order.
+        #[ink(message)]
+        pub fn get_escrows(&self, ids: Vec<EscrowId>) -> Vec<Option<Escrow>> {
+            ids.into_iter()
+                .take(MAX_BATCH_SIZE as usize)
+                .map(|escrow_id| self.get_escrow(escrow_id))
+                .collect()
+        }
+
+        /// Checks whether a specific participant has approved an escrow.
+        ///
+        /// # Arguments
+        ///
+        /// * `escrow_id` - The ID of the escrow.
+        /// * `who` - The account ID of the participant.
+        ///
+        /// # Returns
+        ///
+        /// * `Ok(bool)` - The approval flag of the buyer or seller.
+        /// * `Err(Error)` - `NotFound` for unknown escrows, `Unauthorized` if `who` is not a participant.
+        #[ink(message)]
+        pub fn has_approved(&self, escrow_id: EscrowId, who: AccountId) -> Result<bool, Error> {
+            let escrow = self.escrows.get(escrow_id).ok_or(Error::NotFound)?;
+
+            // Match the account to the buyer or seller.
+            match who {
+                _ if who == escrow.buyer => Ok(escrow.buyer_approved),
+                _ if who == escrow.seller => Ok(escrow.seller_approved),
+                _ => Err(Error::Unauthorized),
+            }
+        }
+
+        /// Returns how the caller relates to an escrow.
+        ///
+        /// # Arguments
+        ///
+        /// * `escrow_id` - The ID of the escrow.
+        ///
+        /// # Returns
+        ///
+        /// * `Ok(Role)` - The caller's role in the escrow.
+        /// * `Err(Error)` - `NotFound` for unknown escrows.
+        #[ink(message)]
+        pub fn my_role(&self, escrow_id: EscrowId) -> Result<Role, Error> {
+            let escrow = self.escrows.get(escrow_id).ok_or(Error::NotFound)?;
+            let caller = self.env().caller();
+
+            // Match the caller to the participants.
+            match caller {
+                _ if caller == escrow.buyer => Ok(Role::Buyer),
+                _ if caller == escrow.seller => Ok(Role::Seller),
+                _ if Some(caller) == self.arbiter_of(&escrow) => Ok(Role::Arbiter),
+                _ => Ok(Role::Observer),
+            }
+        }
+
+        /// Checks whether a participant can approve an escrow with `complete_escrow` right now.
+        ///
+        /// Stale approvals count as missing, as in `pending_approvers`, since `complete_escrow`
+        /// expires them and takes a new approval.
+        ///
+        /// # Arguments
+        ///
+        /// * `escrow_id` - The ID of the escrow.
+        /// * `caller` - The account ID of the participant.
+        ///
+        /// # Returns
+        ///
+        /// * `Ok(bool)` - Whether the escrow is funded and `caller` has not approved it yet.
+        /// * `Err(Error)` - `NotFound` for unknown escrows, `Unauthorized` if `caller` is not a participant.
+        #[ink(message)]
+        pub fn can_complete(&self, escrow_id: EscrowId, caller: AccountId) -> Result<bool, Error> {
+            let mut escrow = self.escrows.get(escrow_id).ok_or(Error::NotFound)?;
+            self.expire_stale_approvals(&mut escrow);
+
+            // Match the account to the buyer or seller.
+            let approved = match caller {
+                _ if caller == escrow.buyer => escrow.buyer_approved,
+                _ if caller == escrow.seller => escrow.seller_approved,
+                _ => return Err(Error::Unauthorized),
+            };
+            Ok(escrow.state == EscrowState::Funded && !approved)
+        }
+
+        /// Returns when an account can first get funds out of an escrow on its own.
+        ///
+        /// The buyer can take back deposits of an unfunded escrow at once, cancel a funded escrow
+        /// after the refund lock period and reclaim a disputed escrow after its dispute timeout.
+        /// The seller can take back its stake, by canceling once the seller cancellation delay
+        /// has passed, and is paid once the auto-release period has passed since the buyer's
+        /// approval, if that approval stays valid and the release needs no further confirmation.
+        /// Block heights set with `release_after_block` are not considered.
+        ///
+        /// # Arguments
+        ///
+        /// * `escrow_id` - The ID of the escrow.
+        /// * `account` - The account ID of the buyer or seller.
+        ///
+        /// # Returns
+        ///
+        /// * `Some(u64)` - The moment, measured per the escrow's deadline kind, which is the
+        ///   current one if the funds can be withdrawn already.
+        /// * `None` - If the escrow does not exist or the account cannot withdraw on its own.
+        #[ink(message)]
+        pub fn withdrawable_at(&self, escrow_id: EscrowId, account: AccountId) -> Option<u64> {
+            let escrow = self.escrows.get(escrow_id)?;
+            let now = self.now(&escrow);
+            let at = match escrow.state {
+                EscrowState::Created if account == escrow.buyer && escrow.deposited > 0 => Some(now),
+                EscrowState::Created if account == escrow.seller && escrow.seller_deposited => Some(now),
+                EscrowState::Funded if account == escrow.buyer => {
+                    Some(escrow.funded_at.map_or(now, |funded_at| funded_at.saturating_add(self.refund_lock_period)))
+                }
+                EscrowState::Funded if account == escrow.seller => {
+                    // The release needs a buyer approval that is still valid once the period has passed.
+                    let release = match (escrow.terms.auto_release_period, escrow.buyer_approved_at) {
+                        (Some(period), Some(approved_at))
+                            if !self.is_stale(&escrow, Some(approved_at))
+                                && escrow.terms.approval_ttl.is_none_or(|ttl| period <= ttl)
+                                && self.check_high_value_confirmed(&escrow).is_ok()
+                                && Self::check_delivery_confirmed(&escrow).is_ok() =>
+                        {
+                            Some(approved_at.saturating_add(period))
+                        }
+                        _ => None,
+                    };
+                    let stake_returned = escrow.funded_at.filter(|_| escrow.seller_amount > 0).map(|funded_at| {
+                        funded_at.saturating_add(escrow.terms.seller_cancel_delay.unwrap_or_default())
+                    });
+                    release.into_iter().chain(stake_returned).min()
+                }
+                EscrowState::Disputed if account == escrow.buyer => {
+                    match (escrow.terms.dispute_timeout, escrow.disputed_at) {
+                        (Some(timeout), Some(disputed_at)) => Some(disputed_at.saturating_add(timeout)),
+                        _ => None,
+                    }
+                }
+                _ => None,
+            };
+            at.map(|at| at.max(now))
+        }
+
+        /// Previews the transfers `complete_escrow` would make if the escrow completed now.
+        ///
+        /// Includes the seller or split recipients, the platform fee paid to the owner and the
+        /// seller's stake of a mutual-stake escrow paid to the buyer.
+        /// The arbiter is only paid when resolving a dispute, so never appears here.
+        ///
+        /// # Arguments
+        ///
+        /// * `escrow_id` - The ID of the escrow.
+        ///
+        /// # Returns
+        ///
+        /// * `Ok(Vec<(AccountId, Balance)>)` - The recipients and amounts, in transfer order.
+        /// * `Err(Error)` - `NotFound` for unknown escrows or escrows the caller may not read as in
+        ///   `get_escrow`, `InvalidState` if the escrow is not funded.
+        #[ink(message)]
+        pub fn simulate_completion(&self, escrow_id: EscrowId) -> Result<Vec<(AccountId, Balance)>, Error> {
+            let escrow = self.get_escrow(escrow_id).ok_or(Error::NotFound)?;
+
+            // Check if the escrow is in the correct state.
+            if escrow.state != EscrowState::Funded {
+                return Err(Error::InvalidState);
+            }
+
+            // Include the seller's stake of a mutual-stake escrow, which goes to the buyer.
+            let (mut transfers, _) = self.seller_transfers(&escrow, escrow.amount)?;
+            if escrow.seller_amount > 0 {
+                transfers.push((escrow.buyer, escrow.seller_amount));
+            }
+            Ok(transfers)
+        }
+
+        /// Lists the participants whose approval a funded escrow is still waiting on.
+        ///
+        /// Only the parties required by the escrow's approval mode are considered. Stale
+        /// approvals count as missing.
+        ///
+        /// # Arguments
+        ///
+        /// * `escrow_id` - The ID of the escrow.
+        ///
+        /// # Returns
+        ///
+        /// * `Ok(Vec<AccountId>)` - The pending approvers, empty if the escrow is not funded.
+        /// * `Err(Error)` - `NotFound` for unknown escrows.
+        #[ink(message)]
+        pub fn pending_approvers(&self, escrow_id: EscrowId) -> Result<Vec<AccountId>, Error> {
+            let mut escrow = self.escrows.get(escrow_id).ok_or(Error::NotFound)?;
+
+            // Only funded escrows can be approved.
+            let mut pending = Vec::new();
+            if escrow.state != EscrowState::Funded {
+                return Ok(pending);
+            }
+
+            // Collect the required parties that have not approved yet.
+            let (buyer_required, seller_required) = match escrow.terms.approval_mode {
+                ApprovalMode::BothParties => (true, true),
+                ApprovalMode::BuyerOnly => (true, false),
+                ApprovalMode::SellerOnly => (false, true),
+            };
+            self.expire_stale_approvals(&mut escrow);
+            if buyer_required && !escrow.buyer_approved {
+                pending.push(escrow.buyer);
+            }
+            if seller_required && !escrow.seller_approved {
+                pending.push(escrow.seller);
+            }
+            Ok(pending)
+        }
+
+        /// Returns the participants of an escrow without the rest of its data.
+        ///
+        /// # Arguments
+        ///
+        /// * `escrow_id` - The ID of the escrow.
+        ///
+        /// # Returns
+        ///
+        /// * `Some(Participants)` - The buyer, seller and arbiter of the escrow.
+        /// * `None` - If the escrow does not exist.
+        #[ink(message)]
+        pub fn get_participants(&self, escrow_id: EscrowId) -> Option<Participants> {
+            self.escrows.get(escrow_id).map(|escrow| Participants {
+                buyer: escrow.buyer,
+                seller: escrow.seller,
+                arbiter: self.arbiter_of(&escrow),
+            })
+        }
+
+        /// Returns the contract's obligations broken down by category.
+        ///
+        /// The categories add up to `get_total_locked`, so auditors can reconcile them against
+        /// `get_contract_balance` in one call. Platform fees are transferred to the owner with
+        /// each release, so the contract never holds any.
+        ///
+        /// # Returns
+        ///
+        /// * `Liabilities` - The locked principal, posted bonds and pending withdrawals.
+        #[ink(message)]
+        pub fn get_liabilities(&self) -> Liabilities {
+            let posted_bonds = self.total_bonds;
+            let pending_withdrawals = self.total_pending_refunds;
+            Liabilities {
+                locked_principal: self.total_locked.saturating_sub(posted_bonds).saturating_sub(pending_withdrawals),
+                posted_bonds,
+                pending_withdrawals,
+            }
+        }
+
+        /// Returns a hash of the contract's current configuration.
+        ///
+        /// Covers the owner, the platform fee and fee overrides, the open escrow limit, ID
+        /// recycling, the high-value threshold, the refund lock period, the shutdown, the admins
+        /// and their threshold, the code hash last set with `set_code_hash`, the default arbiter
+        /// and amount obfuscation. Clients compare the hash with a cached value to know when to
+        /// read the settings again.
+        ///
+        /// # Returns
+        ///
+        /// * `[u8; 32]` - The Blake2x256 hash of the configuration.
+        #[ink(message)]
+        pub fn config_hash(&self) -> [u8; 32] {
+            let mut hash = <Blake2x256 as HashOutput>::Type::default();
+            ink::env::hash_encoded::<Blake2x256, _>(
+                &(
+                    self.owner,
+                    self.fee_bps,
+                    self.fee_override_digest,
+                    self.max_open_per_buyer,
+                    self.recycle_ids,
+                    self.high_value_threshold,
+                    self.refund_lock_period,
+                    self.shut_down,
+                    self.admins_digest,
+                    self.admin_threshold,
+                    self.code_hash,
+                    self.default_arbiter,
+                    self.obfuscate_amounts,
+                ),
+                &mut hash
+            );
+            hash
+        }
+
+        /// Returns the accounts that would receive the funds of an escrow, resolving any payout and
+        /// refund address overrides.
+        ///
+        /// Split escrows pay their recipients and split refunds instead, as listed by `get_escrow`.
+        ///
+        /// # Arguments
+        ///
+        /// * `escrow_id` - The ID of the escrow.
+        ///
+        /// # Returns
+        ///
+        /// * `Ok((AccountId, AccountId))` - The seller's payout destination and the buyer's refund destination.
+        /// * `Err(Error)` - `NotFound` if the escrow does not exist.
+        #[ink(message)]
+        pub fn effective_recipients(&self, escrow_id: EscrowId) -> Result<(AccountId, AccountId), Error> {
+            let escrow = self.escrows.get(escrow_id).ok_or(Error::NotFound)?;
+            Ok((Self::payout_recipient(&escrow), Self::refund_recipient(&escrow)))
+        }
+
+        /// Lists the IDs of funded escrows, which currently hold funds, ordered by ID.
+        ///
+        /// The next page starts after the last ID returned.
+        ///
+        /// # Arguments
+        ///
+        /// * `start` - The lowest ID to list.
+        /// * `limit` - The number of IDs to return, capped at `MAX_BATCH_SIZE`.
+        ///
+        /// # Returns
+        ///
+        /// * `Vec<EscrowId>` - The funded escrows from `start` onwards.
+        #[ink(message)]
+        pub fn get_active_escrows(&self, start: EscrowId, limit: u32) -> Vec<EscrowId> {
+            self.state_page(EscrowState::Funded, start, limit)
+        }
+
+        /// Lists the IDs of funded escrows whose release deadline is earlier than a timestamp, so
+        /// keepers can sweep them once it passes.
+        ///
+        /// Reads the same page as `get_active_escrows` and keeps the matching escrows, so a
+        /// page may hold fewer IDs than `limit`. Escrows whose deadlines are measured in blocks
+        /// are skipped.
+        ///
+        /// # Arguments
+        ///
+        /// * `deadline_ts` - The timestamp the release deadline must be earlier than.
+        /// * `start` - The lowest ID to check.
+        /// * `limit` - The number of funded escrows to check, capped at `MAX_BATCH_SIZE`.
+        ///
+        /// # Returns
+        ///
+        /// * `Vec<EscrowId>` - The expiring escrows on the requested page.
+        #[ink(message)]
+        pub fn get_expiring_before(&self, deadline_ts: u64, start: EscrowId, limit: u32) -> Vec<EscrowId> {
+            self.state_page(EscrowState::Funded, start, limit)
+                .into_iter()
+                .filter(|escrow_id| {
+                    self.escrows.get(escrow_id).is_some_and(|escrow| {
+                        escrow.terms.deadline_kind == DeadlineKind::Timestamp
+                            && escrow.terms.release_deadline.is_some_and(|deadline| deadline < deadline_ts)
+                    })
+                })
+                .collect()
+        }
+
+        /// Lists the IDs of the escrows completed or canceled within a time range, in settlement order.
+        ///
+        /// Escrows stay listed after they are purged. Escrows that were reopened are listed
+        /// at their latest settlement only.
+        ///
+        /// # Arguments
+        ///
+        /// * `from_ts` - The start of the range, inclusive, as a block timestamp.
+        /// * `to_ts` - The end of the range, exclusive, as a block timestamp.
+        /// * `start` - The first position among the escrows settled within the range.
+        /// * `limit` - The number of positions to read, capped at `MAX_BATCH_SIZE`.
+        ///
+        /// # Returns
+        ///
+        /// * `Vec<EscrowId>` - The settled escrows at the requested positions.
+        #[ink(message)]
+        pub fn get_settled_between(&self, from_ts: u64, to_ts: u64, start: u32, limit: u32) -> Vec<EscrowId> {
+            let end = self.settled_position(to_ts);
+            let first = self.settled_position(from_ts).saturating_add(start).min(end);
+            let last = first.saturating_add(limit.min(MAX_BATCH_SIZE)).min(end);
+            (first..last)
+                .filter_map(|position| self.settled.get(position))
+                .filter(|(settled_at, escrow_id)| {
+                    self.escrows.get(escrow_id).is_none_or(|escrow| escrow.settled_at == Some(*settled_at))
+                })
+                .map(|(_, escrow_id)| escrow_id)
+                .collect()
+        }
+
+        /// Returns the funds a buyer has locked in escrows: the amounts of its funded and disputed
+        /// escrows and the installments deposited into escrows not funded yet.
+        ///
+        /// Reads every escrow listed for the buyer, so the cost grows with the buyer's number of
+        /// escrows; it is meant to be queried off-chain. When amounts are obfuscated, only the
+        /// buyer can read its locked funds.
+        ///
+        /// # Arguments
+        ///
+        /// * `account` - The account ID of the buyer.
+        ///
+        /// # Returns
+        ///
+        /// * `Balance` - The locked funds, or 0 if the caller may not read them.
+        #[ink(message)]
+        pub fn locked_balance_of(&self, account: AccountId) -> Balance {
+            if self.obfuscate_amounts && self.env().caller() != account {
+                return 0;
+            }
+            let mut locked: Balance = 0;
+            for position in 0..self.count_escrows_by_buyer(account) {
+                let Some(escrow) = self
+                    .buyer_escrows
+                    .get((account, position))
+                    .and_then(|escrow_id| self.escrows.get(escrow_id))
+                else {
+                    continue;
+                };
+                if Self::holds_funds(&escrow.state) {
+                    locked = locked.saturating_add(escrow.amount);
+                } else if escrow.state == EscrowState::Created {
+                    locked = locked.saturating_add(escrow.deposited);
+                }
+            }
+            locked
+        }
+
+        /// Lists the IDs of the escrows created for a buyer, oldest first.
+        ///
+        /// Escrows stay listed after they are purged. Discarded escrows are removed, and the
+        /// buyer's last listed escrow takes their position. Escrows whose buyer position was
+        /// transferred are listed for the new buyer only.
+        ///
+        /// # Arguments
+        ///
+        /// * `buyer` - The account ID of the buyer.
+        /// * `start` - The first position in the buyer's escrows.
+        /// * `limit` - The number of positions to read, capped at `MAX_BATCH_SIZE`.
+        ///
+        /// # Returns
+        ///
+        /// * `Vec<EscrowId>` - The escrows at the requested positions.
+        #[ink(message)]
+        pub fn get_escrows_by_buyer(&self, buyer: AccountId, start: u32, limit: u32) -> Vec<EscrowId> {
+            let end = start
+                .saturating_add(limit.min(MAX_BATCH_SIZE))
+                .min(self.count_escrows_by_buyer(buyer));
+            (start..end)
+                .filter_map(|position| self.buyer_escrows.get((buyer, position)))
+                .collect()
+        }
+
+        /// Lists the IDs of the escrows created for a seller, oldest first.
+        ///
+        /// Escrows stay listed after they are purged. Discarded escrows are removed, and the
+        /// seller's last listed escrow takes their position.
+        ///
+        /// # Arguments
+        ///
+        /// * `seller` - The account ID of the seller.
+        /// * `start` - The first position in the seller's escrows.
+        /// * `limit` - The number of positions to read, capped at `MAX_BATCH_SIZE`.
+        ///
+        /// # Returns
+        ///
+        /// * `Vec<EscrowId>` - The escrows at the requested positions.
+        #[ink(message)]
+        pub fn get_escrows_by_seller(&self, seller: AccountId, start: u32, limit: u32) -> Vec<EscrowId> {
+            let end = start
+                .saturating_add(limit.min(MAX_BATCH_SIZE))
+                .min(self.count_escrows_by_seller(seller));
+            (start..end)
+                .filter_map(|position| self.seller_escrows.get((seller, position)))
+                .collect()
+        }
+
+        /// Returns the number of escrows listed by `get_escrows_by_buyer`.
+        ///
+        /// # Arguments
+        ///
+        /// * `buyer` - The account ID of the buyer.
+        ///
+        /// # Returns
+        ///
+        /// * `u32` - The number of escrows listed for the buyer.
+        #[ink(message)]
+        pub fn count_escrows_by_buyer(&self, buyer: AccountId) -> u32 {
+            self.buyer_counts.get(buyer).unwrap_or_default()
+        }
+
+        /// Returns the number of escrows listed by `get_escrows_by_seller`.
+        ///
+        /// # Arguments
+        ///
+        /// * `seller` - The account ID of the seller.
+        ///
+        /// # Returns
+        ///
+        /// * `u32` - The number of escrows listed for the seller.
+        #[ink(message)]
+        pub fn count_escrows_by_seller(&self, seller: AccountId) -> u32 {
+            self.seller_counts.get(seller).unwrap_or_default()
+        }
+
+        /// Lists the escrows in a given state with their data, ordered by ID.
+        ///
+        /// The next page starts after the last ID returned. Escrows hidden from the caller by
+        /// `get_escrow` are left out, so a page may be shorter than `limit`.
+        ///
+        /// # Arguments
+        ///
+        /// * `state` - The state to list.
+        /// * `start` - The lowest ID to list.
+        /// * `limit` - The number of escrows in the state to read, capped at `MAX_BATCH_SIZE`.
+        ///
+        /// # Returns
+        ///
+        /// * `Vec<(EscrowId, Escrow)>` - The escrows in the state from `start` onwards.
+        #[ink(message)]
+        pub fn get_escrows_by_state(
+            &self,
+            state: EscrowState,
+            start: EscrowId,
+            limit: u32
+        ) -> Vec<(EscrowId, Escrow)> {
+            self.state_page(state, start, limit)
+                .into_iter()
+                .filter_map(|escrow_id| self.get_escrow(escrow_id).map(|escrow| (escrow_id, escrow)))
+                .collect()
+        }
+
+        /// Lists completed, canceled and returned escrows that can be passed to `purge_batch`.
+        ///
+        /// Scans the IDs `start..start + limit` one storage read per ID, so the cost grows
+        /// linearly with `limit`, which is capped at `MAX_BATCH_SIZE`.
+        ///
+        /// # Arguments
+        ///
+        /// * `start` - The first escrow ID to scan.
+        /// * `limit` - The number of IDs to scan.
+        ///
+        /// # Returns
+        ///
+        /// * `Vec<EscrowId>` - The purgeable escrows within the scanned range, in ID order.
+        #[ink(message)]
+        pub fn get_purgeable(&self, start: EscrowId, limit: u32) -> Vec<EscrowId> {
+            let end = start
+                .saturating_add(u64::from(limit.min(MAX_BATCH_SIZE)))
+                .min(self.next_id);
+            (start..end)
+                .filter(|escrow_id| {
+                    self.escrows
+                        .get(escrow_id)
+                        .is_some_and(|escrow| self.is_purgeable(&escrow))
+                })
+                .collect()
+        }
+
+        /// Returns the soonest automatic action of an escrow and when it becomes callable.
+        ///
+        /// # Arguments
+        ///
+        /// * `escrow_id` - The ID of the escrow.
+        ///
+        /// # Returns
+        ///
+        /// * `Some((u8, u64))` - The `AutoAction` code and the first moment at which it can be
+        ///   triggered, in the escrow's deadline kind, which may already be in the past.
+        /// * `None` - If the escrow does not exist or has no pending automatic action.
+        #[ink(message)]
+        pub fn next_auto_action_at(&self, escrow_id: EscrowId) -> Option<(u8, u64)> {
+            let escrow = self.escrows.get(escrow_id)?;
+            Self::next_auto_action(&escrow).map(|(action, at)| (action as u8, at))
+        }
+
+        /// Sets the platform fee taken from payouts to the seller.
+        ///
+        /// Once admins are set with `set_admins`, this requires their confirmations through
+        /// `propose_admin_action` instead.
+        ///
+        /// # Arguments
+        ///
+        /// * `fee_bps` - The fee in basis points, at most 10_000.
+        ///
+        /// # Returns
+        ///
+        /// * `Ok(())` - If the fee was set.
+        /// * `Err(Error)` - An error if the operation failed.
+        #[ink(message)]
+        pub fn set_fee(&mut self, fee_bps: u16) -> Result<(), Error> {
+            // Check if the owner may act alone.
+            self.check_sole_owner()?;
+
+            self.apply_fee(fee_bps)
+        }
+
+        /// Sets the maximum number of escrows a buyer may have open at once.
+        ///
+        /// Escrows are open until they are completed or canceled.
+        ///
+        /// Once admins are set with `set_admins`, this requires their confirmations through
+        /// `propose_admin_action` instead.
+        ///
+        /// # Arguments
+        ///
+        /// * `max_open_per_buyer` - The limit, or 0 for no limit.
+        ///
+        /// # Returns
+        ///
+        /// * `Ok(())` - If the limit was set.
+        /// * `Err(Error)` - An error if the operation failed.
+        #[ink(message)]
+        pub fn set_max_open_per_buyer(&mut self, max_open_per_buyer: u32) -> Result<(), Error> {
+            // Check if the owner may act alone.
+            self.check_sole_owner()?;
+
+            self.apply_max_open_per_buyer(max_open_per_buyer)
+        }
+
+        /// Enables or disables reuse of the IDs of discarded escrows for new escrows.
+        ///
+        /// Only IDs removed by `discard_escrow` are reused. Such escrows were never funded and
+        /// their history is deleted with them, so a reused ID never aliases a live escrow.
+        /// IDs already freed stay available for reuse once recycling is enabled again.
+        ///
+        /// Once admins are set with `set_admins`, this requires their confirmations through
+        /// `propose_admin_action` instead.
+        ///
+        /// # Arguments
+        ///
+        /// * `enabled` - Whether discarded IDs are reused.
+        ///
+        /// # Returns
+        ///
+        /// * `Ok(())` - If the setting was changed.
+        /// * `Err(Error)` - An error if the operation failed.
+        #[ink(message)]
+        pub fn set_id_recycling(&mut self, enabled: bool) -> Result<(), Error> {
+            // Check if the owner may act alone.
+            self.check_sole_owner()?;
+
+            self.apply_id_recycling(enabled)
+        }
+
+        /// Sets the amount from which escrows need the buyer's `confirm_high_value` before release.
+        ///
+        /// Once admins are set with `set_admins`, this requires their confirmations through
+        /// `propose_admin_action` instead.
+        ///
+        /// # Arguments
+        ///
+        /// * `threshold` - The threshold, or 0 to disable the extra confirmation.
+        ///
+        /// # Returns
+        ///
+        /// * `Ok(())` - If the threshold was set.
+        /// * `Err(Error)` - An error if the operation failed.
+        #[ink(message)]
+        pub fn set_high_value_threshold(&mut self, threshold: Balance) -> Result<(), Error> {
+            // Check if the owner may act alone.
+            self.check_sole_owner()?;
+
+            self.apply_high_value_threshold(threshold)
+        }
+
+        /// Sets how long after funding the buyer cannot cancel an escrow, giving the seller time
+        /// to deliver.
+        ///
+        /// The period is measured in each escrow's deadline kind.
+        ///
+        /// Once admins are set with `set_admins`, this requires their confirmations through
+        /// `propose_admin_action` instead.
+        ///
+        /// # Arguments
+        ///
+        /// * `period` - The period, or 0 to let buyers cancel at any time.
+        ///
+        /// # Returns
+        ///
+        /// * `Ok(())` - If the period was set.
+        /// * `Err(Error)` - An error if the operation failed.
+        #[ink(message)]
+        pub fn set_refund_lock_period(&mut self, period: u64) -> Result<(), Error> {
+            // Check if the owner may act alone.
+            self.check_sole_owner()?;
+
+            self.apply_refund_lock_period(period)
+        }
+
+        /// Sets the platform fee paid on escrows of a given buyer instead of the global fee.
+        ///
+        /// Once admins are set with `set_admins`, this requires their confirmations through
+        /// `propose_admin_action` instead.
+        ///
+        /// # Arguments
+        ///
+        /// * `account` - The buyer whose fee is overridden.
+        /// * `fee_bps` - The fee in basis points, at most 10_000, or `FEE_OVERRIDE_REMOVED` to
+        ///   remove the override.
+        ///
+        /// # Returns
+        ///
+        /// * `Ok(())` - If the override was set or removed.
+        /// * `Err(Error)` - An error if the operation failed.
+        #[ink(message)]
+        pub fn set_fee_override(&mut self, account: AccountId, fee_bps: u16) -> Result<(), Error> {
+            // Check if the owner may act alone.
+            self.check_sole_owner()?;
+
+            self.apply_fee_override(account, fee_bps)
+        }
+
+        /// Shuts the contract down after a severe exploit, as a last resort.
+        ///
+        /// Escrows can no longer be created, funded or settled, and refunds can no longer be
+        /// claimed. The shutdown cannot be undone; the owner then moves the funds to a safe
+        /// address with `emergency_drain`.
+        ///
+        /// Once admins are set with `set_admins`, this requires their confirmations through
+        /// `propose_admin_action` instead.
+        ///
+        /// # Returns
+        ///
+        /// * `Ok(())` - If the contract was shut down.
+        /// * `Err(Error)` - An error if the operation failed.
+        #[ink(message)]
+        pub fn shutdown(&mut self) -> Result<(), Error> {
+            // Check if the owner may act alone.
+            self.check_sole_owner()?;
+
+            self.apply_shutdown()
+        }
+
+        /// Drains a shut-down contract in batches: marks escrows holding funds `Canceled`, then
+        /// transfers the entire balance to a safe address.
+        ///
+        /// Funded and disputed escrows are canceled first, then unfunded escrows holding
+        /// installments or a seller stake. Each call handles at most `max` escrows, capped at
+        /// `MAX_BATCH_SIZE`, and resumes where the previous call stopped. The balance is
+        /// transferred by the call that leaves no escrows remaining. The buyers and sellers of
+        /// the canceled escrows, and accounts owed refunds, are then paid off-chain from the
+        /// drained funds.
+        ///
+        /// Once admins are set with `set_admins`, this requires their confirmations through
+        /// `propose_admin_action` instead. A single confirmed `EmergencyDrain` proposal covers
+        /// the whole drain: `execute` it again for each batch until it returns with no escrows
+        /// remaining.
+        ///
+        /// # Arguments
+        ///
+        /// * `to` - The account receiving the contract balance.
+        /// * `max` - The maximum number of escrows to handle in this call.
+        ///
+        /// # Returns
+        ///
+        /// * `Ok(u32)` - The number of escrows still to cancel or check; 0 once the balance is transferred.
+        /// * `Err(Error)` - An error if the operation failed.
+        #[ink(message)]
+        pub fn emergency_drain(&mut self, to: AccountId, max: u32) -> Result<u32, Error> {
+            // Check if the owner may act alone.
+            self.check_sole_owner()?;
+
+            self.apply_drain(to, max)
+        }
+
+        /// Sets the admins whose confirmations the sensitive owner actions then require.
+        ///
+        /// Afterwards every owner action, from `set_fee` and the other settings to `shutdown`,
+        /// `emergency_drain` and `set_code_hash`, only runs through `propose_admin_action`,
+        /// `confirm` and `execute`. The admins can be set only once.
+        ///
+        /// # Arguments
+        ///
+        /// * `admins` - The admins, at most `MAX_ADMINS`.
+        /// * `threshold` - The number of confirmations an action needs.
+        ///
+        /// # Returns
+        ///
+        /// * `Ok(())` - If the admins were set.
+        /// * `Err(Error)` - An error if the operation failed.
+        #[ink(message)]
+        pub fn set_admins(&mut self, admins: Vec<AccountId>, threshold: u32) -> Result<(), Error> {
+            // Check if the owner may act alone.
+            self.check_sole_owner()?;
+
+            // Check if the admin set is within bounds.
+            if admins.len() > MAX_ADMINS {
+                return Err(Error::BatchTooLarge);
+            }
+
+            // Check if the threshold can be met by distinct, real admins.
+            let mut distinct = Vec::new();
+            for admin in admins.iter() {
+                Self::ensure_not_zero(*admin)?;
+                if !distinct.contains(admin) {
+                    distinct.push(*admin);
+                }
+            }
+            if threshold == 0 || threshold as usize > distinct.len() {
+                return Err(Error::InvalidThreshold);
+            }
+
+            ink::env::hash_encoded::<Blake2x256, _>(&distinct, &mut self.admins_digest);
+            for admin in distinct {
+                self.admins.insert(admin, &());
+            }
+            self.admin_threshold = threshold;
+
+            // Emit an event to notify about the admins.
+            let seq = self.next_seq()?;
+            self.env().emit_event(AdminsSet { seq, admins, threshold });
+
+            Ok(())
+        }
+
+        /// Proposes a sensitive owner action, counting the proposer's confirmation.
+        ///
+        /// # Arguments
+        ///
+        /// * `action` - The action to propose.
+        ///
+        /// # Returns
+        ///
+        /// * `Ok(ProposalId)` - The ID of the proposal.
+        /// * `Err(Error)` - An error if the operation failed.
+        #[ink(message)]
+        pub fn propose_admin_action(&mut self, action: AdminAction) -> Result<ProposalId, Error> {
+            let caller = self.env().caller();
+
+            // Check if the caller is an admin.
+            if !self.admins.contains(caller) {
+                return Err(Error::Unauthorized);
+            }
+
+            // Store the proposal with the proposer's confirmation.
+            let proposal_id = self.next_proposal_id;
+            self.next_proposal_id = proposal_id.checked_add(1).ok_or(Error::IdOverflow)?;
+            self.proposals.insert(proposal_id, &AdminProposal { action, confirmations: 1, executed: false });
+            self.proposal_confirmations.insert((proposal_id, caller), &());
+
+            // Emit an event to notify about the proposal.
+            let seq = self.next_seq()?;
+            self.env().emit_event(AdminActionProposed { seq, proposal_id, proposer: caller, action });
+
+            Ok(proposal_id)
+        }
+
+        /// Confirms a proposed admin action.
+        ///
+        /// # Arguments
+        ///
+        /// * `proposal_id` - The ID of the proposal.
+        ///
+        /// # Returns
+        ///
+        /// * `Ok(())` - If the confirmation was recorded.
+        /// * `Err(Error)` - An error if the operation failed.
+        #[ink(message)]
+        pub fn confirm(&mut self, proposal_id: ProposalId) -> Result<(), Error> {
+            let caller = self.env().caller();
+            let mut proposal = self.proposals.get(proposal_id).ok_or(Error::NotFound)?;
+
+            // Check if the caller is an admin.
+            if !self.admins.contains(caller) {
+                return Err(Error::Unauthorized);
+            }
+
+            // Check if the proposal is still pending.
+            if proposal.executed {
+                return Err(Error::InvalidState);
+            }
+
+            // Check if the admin has already confirmed.
+            if self.proposal_confirmations.insert((proposal_id, caller), &()).is_some() {
+                return Err(Error::AlreadyApproved);
+            }
+
+            proposal.confirmations += 1;
+
+            // Save changes back to storage
+            self.proposals.insert(proposal_id, &proposal);
+
+            // Emit an event to notify about the confirmation.
+            let seq = self.next_seq()?;
+            self.env().emit_event(AdminActionConfirmed {
+                seq,
+                proposal_id,
+                admin: caller,
+                confirmations: proposal.confirmations,
+            });
+
+            Ok(())
+        }
+
+        /// Executes a proposed admin action once enough admins have confirmed it.
+        ///
+        /// An `EmergencyDrain` proposal stays open while escrows remain, so each further call
+        /// drains the next batch until the balance is transferred.
+        ///
+        /// # Arguments
+        ///
+        /// * `proposal_id` - The ID of the proposal.
+        ///
+        /// # Returns
+        ///
+        /// * `Ok(())` - If the action was executed.
+        /// * `Err(Error)` - `NotEnoughConfirmations` below the threshold, otherwise as in the action.
+        #[ink(message)]
+        pub fn execute(&mut self, proposal_id: ProposalId) -> Result<(), Error> {
+            let mut proposal = self.proposals.get(proposal_id).ok_or(Error::NotFound)?;
+
+            // Check if the caller is an admin.
+            if !self.admins.contains(self.env().caller()) {
+                return Err(Error::Unauthorized);
+            }
+
+            // Check if the proposal is still pending.
+            if proposal.executed {
+                return Err(Error::InvalidState);
+            }
+
+            // Check if enough admins have confirmed.
+            if proposal.confirmations < self.admin_threshold {
+                return Err(Error::NotEnoughConfirmations);
+            }
+
+            proposal.executed = true;
+
+            // Save changes back to storage
+            self.proposals.insert(proposal_id, &proposal);
+
+            // Run the action.
+            match proposal.action {
+                AdminAction::SetFee(fee_bps) => self.apply_fee(fee_bps)?,
+                AdminAction::Shutdown => self.apply_shutdown()?,
+                AdminAction::EmergencyDrain(to, max) => {
+                    // Keep the proposal open until the balance is transferred, so the next batch
+                    // runs without new confirmations.
+                    if self.apply_drain(to, max)? > 0 {
+                        proposal.executed = false;
+                        self.proposals.insert(proposal_id, &proposal);
+                    }
+                }
+                AdminAction::SetCodeHash(code_hash) => self.apply_code_hash(code_hash)?,
+                AdminAction::SetFeeOverride(account, fee_bps) => self.apply_fee_override(account, fee_bps)?,
+                AdminAction::SetMaxOpenPerBuyer(max_open_per_buyer) => self.apply_max_open_per_buyer(max_open_per_buyer)?,
+                AdminAction::SetIdRecycling(enabled) => self.apply_id_recycling(enabled)?,
+                AdminAction::SetHighValueThreshold(threshold) => self.apply_high_value_threshold(threshold)?,
+                AdminAction::SetRefundLockPeriod(period) => self.apply_refund_lock_period(period)?,
+            }
+
+            // Emit an event to notify about the execution.
+            let seq = self.next_seq()?;
+            self.env().emit_event(AdminActionExecuted { seq, proposal_id });
+
+            Ok(())
+        }
+
+        /// Replaces the contract code, keeping its storage and balance, to patch the logic.
+        ///
+        /// The new code must keep the storage layout compatible: existing fields keep their
+        /// order and types, and new fields are only appended. Otherwise existing storage can no
+        /// longer be decoded.
+        ///
+        /// Once admins are set with `set_admins`, this requires their confirmations through
+        /// `propose_admin_action` instead.
+        ///
+        /// # Arguments
+        ///
+        /// * `code_hash` - The hash of the uploaded new code.
+        ///
+        /// # Returns
+        ///
+        /// * `Ok(())` - If the code was replaced.
+        /// * `Err(Error)` - An error if the operation failed.
+        #[ink(message)]
+        pub fn set_code_hash(&mut self, code_hash: Hash) -> Result<(), Error> {
+            // Check if the owner may act alone.
+            self.check_sole_owner()?;
+
+            self.apply_code_hash(code_hash)
+        }
+
+        /// Returns the arbiter resolving disputes of all escrows.
+        ///
+        /// # Returns
+        ///
+        /// * `Option<AccountId>` - The arbiter, or `None` if disputes cannot be raised.
+        #[ink(message)]
+        pub fn get_arbiter(&self) -> Option<AccountId> {
+            self.default_arbiter
+        }
+
+        /// Returns the sum of the amounts held by all funded and disputed escrows, including
+        /// refunds awaiting `claim_refund`.
+        ///
+        /// Obfuscating amounts does not hide this total, which matches the contract's balance
+        /// that anyone can read on-chain.
+        ///
+        /// # Returns
+        ///
+        /// * `Balance` - The total amount the contract holds in custody.
+        #[ink(message)]
+        pub fn get_total_locked(&self) -> Balance {
+            self.total_locked
+        }
+
+        /// Returns the platform fee taken from a payout of `amount` under the current global fee.
+        ///
+        /// The fee is rounded down, so small payouts may carry no fee. Buyer fee overrides
+        /// are not applied.
+        ///
+        /// # Arguments
+        ///
+        /// * `amount` - The payout amount.
+        ///
+        /// # Returns
+        ///
+        /// * `Ok(Balance)` - The fee the owner would receive.
+        /// * `Err(Error)` - `InvalidAmount` if the amount is too large to compute the fee.
+        #[ink(message)]
+        pub fn quote_fee(&self, amount: Balance) -> Result<Balance, Error> {
+            Self::apply_bps(amount, self.fee_bps)
+        }
+
+        /// Returns the version of the deployed contract logic.
+        ///
+        /// # Returns
+        ///
+        /// * `(u16, u16, u16)` - The major, minor and patch version.
+        #[ink(message)]
+        pub fn get_version(&self) -> (u16, u16, u16) {
+            VERSION
+        }
+
+        /// Returns the contract's actual balance, for comparison with `get_total_locked`.
+        ///
+        /// # Returns
+        ///
+        /// * `Balance` - The free balance of the contract account.
+        #[ink(message)]
+        pub fn get_contract_balance(&self) -> Balance {
+            self.env().balance()
+        }
+
+        /// Returns the settlement record of a seller.
+        ///
+        /// # Arguments
+        ///
+        /// * `account` - The account ID of the seller.
+        ///
+        /// # Returns
+        ///
+        /// * `(u64, u64)` - The number of completed escrows and of funded escrows that were canceled.
+        #[ink(message)]
+        pub fn get_reputation(&self, account: AccountId) -> (u64, u64) {
+            self.reputation.get(account).unwrap_or_default()
+        }
+
+        /// Returns the recorded state changes of an escrow, oldest first.
+        ///
+        /// Only the most recent `MAX_HISTORY` changes are kept.
+        ///
+        /// # Arguments
+        ///
+        /// * `escrow_id` - The ID of the escrow.
+        ///
+        /// # Returns
+        ///
+        /// * `Vec<StateChange>` - The state changes, empty if there are none.
+        #[ink(message)]
+        pub fn get_history(&self, escrow_id: EscrowId) -> Vec<StateChange> {
+            self.history.get(escrow_id).unwrap_or_default()
+        }
+
+        /// Returns the amendments applied to an escrow, oldest first.
+        ///
+        /// # Arguments
+        ///
+        /// * `escrow_id` - The ID of the escrow.
+        ///
+        /// # Returns
+        ///
+        /// * `Vec<Amendment>` - The applied amendments, empty if there are none.
+        #[ink(message)]
+        pub fn get_amendment_history(&self, escrow_id: EscrowId) -> Vec<Amendment> {
+            self.amendment_history.get(escrow_id).unwrap_or_default()
+        }
+
+        /// Returns only the state of an escrow, for cheap status polling.
+        ///
+        /// # Arguments
+        ///
+        /// * `escrow_id` - The ID of the escrow.
+        ///
+        /// # Returns
+        ///
+        /// * `Some(EscrowState)` - The current state of the escrow.
+        /// * `None` - If the escrow does not exist.
+        #[ink(message)]
+        pub fn get_escrow_state(&self, escrow_id: EscrowId) -> Option<EscrowState> {
+            self.escrows.get(escrow_id).map(|escrow| escrow.state)
+        }
+    }
+
+    #[cfg(test)]
+    mod tests {
+        use super::*;
+
+        /// Decodes the most recently emitted event as `E`.
+        fn last_event<E: scale::Decode>() -> E {
+            let event = ink::env::test::recorded_events()
+                .last()
+                .expect("An event should have been emitted");
+            E::decode(&mut &event.data[..]).expect("Event should decode")
+        }
+        /// # Escrow Contract Test Suite
+        ///
+        /// This module contains comprehensive tests for the EscrowSmartContract,
+        /// covering core functionality, edge cases, and security requirements.
+        ///
+        /// ## Test Accounts Convention:
+        /// - Alice: Default caller/buyer (ink! default account)
+        /// - Bob: Seller account
+        /// - Charlie: Unauthorized third party
+        #[ink::test]
+        fn test_initiate_escrow() {
+            // Arrange: Initialize contract and test accounts
+            let accounts = ink::env::test::default_accounts::<ink::env::DefaultEnvironment>();
+            let mut contract = EscrowSmartContract::new();
+
+            // Act & Assert: Test successful escrow creation
+            let amount = 100;
+            let result = contract.initiate_escrow(accounts.bob, amount);
+            assert!(result.is_ok(), "Should successfully create escrow");
+            let escrow_id = result.unwrap();
+
+            // Verify stored escrow details
+            let escrow = contract.escrows
+                .get(escrow_id)
+                .expect("Escrow should exist after creation");
+            assert_eq!(escrow.buyer, accounts.alice, "Buyer should be caller");
+            assert_eq!(escrow.seller, accounts.bob, "Seller should match argument");
+            assert_eq!(escrow.amount, amount, "Amount should match initial value");
+            assert_eq!(escrow.state, EscrowState::Created, "Initial state should be Created");
+
+            // Test validation: Buyer cannot be seller
+            let invalid_result = contract.initiate_escrow(accounts.alice, amount);
+            assert_eq!(
+                invalid_result,
+                Err(Error::InvalidParticipants),
+                "Should prevent buyer=seller creation"
+            );
+        }
+
+        #[ink::test]
+        fn test_initiate_escrow_zero_amount() {
+            // Arrange: Initialize contract and test accounts
+            let accounts = ink::env::test::default_accounts::<ink::env::DefaultEnvironment>();
+            let mut contract = EscrowSmartContract::new();
+
+            // Act & Assert: Zero amount is rejected
+            assert_eq!(
+                contract.initiate_escrow(accounts.bob, 0),
+                Err(Error::InvalidAmount),
+                "Should prevent zero-amount escrows"
+            );
+
+            // Valid amounts still work, and the rejected call did not consume an ID
+            assert_eq!(
+                contract.initiate_escrow(accounts.bob, 1),
+                Ok(0),
+                "Should create escrow with a non-zero amount"
+            );
+        }
+
+        #[ink::test]
+        fn test_event_amounts() {
+            // Arrange: One contract publishing raw amounts, one obfuscating them
+            let accounts = ink::env::test::default_accounts::<ink::env::DefaultEnvironment>();
+            let mut clear = EscrowSmartContract::new();
+            let mut obfuscated = EscrowSmartContract::new_with_obfuscated_amounts();
+
+            // Act & Assert: Raw amount when the flag is off
+            clear.initiate_escrow(accounts.bob, 100).unwrap();
+            assert_eq!(
+                last_event::<Initiated>().amount,
+                EventAmount::Clear(100),
+                "Event should carry the raw amount"
+            );
+
+            // Commitment hash when the flag is on
+            let escrow_id = obfuscated.initiate_escrow(accounts.bob, 100).unwrap();
+            let mut commitment = <Blake2x256 as HashOutput>::Type::default();
+            ink::env::hash_encoded::<Blake2x256, _>(&(escrow_id, 100u128), &mut commitment);
+            assert_eq!(
+                last_event::<Initiated>().amount,
+                EventAmount::Commitment(Hash::from(commitment)),
+                "Event should carry the commitment hash"
+            );
+            ink::env::test::set_value_transferred::<ink::env::DefaultEnvironment>(100);
+            obfuscated.deposit_assets(escrow_id).unwrap();
+            assert_eq!(
+                last_event::<Deposited>().amount,
+                EventAmount::Commitment(Hash::from(commitment)),
+                "Deposit event should carry the commitment hash"
+            );
+
+            // Participants still read the real amount, others do not
+            let escrow = obfuscated.get_escrow(escrow_id).expect("Buyer should read the escrow");
+            assert_eq!(escrow.amount, 100, "Buyer should see the real amount");
+            ink::env::test::set_caller::<ink::env::DefaultEnvironment>(accounts.charlie);
+            assert_eq!(obfuscated.get_escrow(escrow_id), None, "Observers should not read it");
+        }
+
+        #[ink::test]
+        fn test_obfuscated_amount_queries() {
+            // Arrange: A funded escrow and an owed refund on a contract obfuscating amounts
+            let accounts = ink::env::test::default_accounts::<ink::env::DefaultEnvironment>();
+            let mut contract = EscrowSmartContract::new_with_obfuscated_amounts();
+            let escrow_id = contract.initiate_escrow(accounts.bob, 100).unwrap();
+            ink::env::test::set_value_transferred::<ink::env::DefaultEnvironment>(100);
+            contract.deposit_assets(escrow_id).unwrap();
+            contract.pending_refunds.insert(accounts.alice, &25);
+
+            // Act & Assert: The buyer reads its amounts
+            assert_eq!(contract.simulate_completion(escrow_id), Ok(vec![(accounts.bob, 100)]), "Buyer should preview payouts");
+            assert_eq!(contract.locked_balance_of(accounts.alice), 100, "Buyer should read its locked funds");
+            assert_eq!(contract.pending_withdrawal_of(accounts.alice), 25, "Buyer should read its owed refunds");
+
+            // Observers read none of them
+            ink::env::test::set_caller::<ink::env::DefaultEnvironment>(accounts.charlie);
+            assert_eq!(contract.simulate_completion(escrow_id), Err(Error::NotFound), "Observers should not preview payouts");
+            assert_eq!(contract.locked_balance_of(accounts.alice), 0, "Observers should not read locked funds");
+            assert_eq!(contract.pending_withdrawal_of(accounts.alice), 0, "Observers should not read owed refunds");
+        }
+
+        #[ink::test]
+        fn test_deposit_assets() {
+            // Arrange: Create escrow and set test environment
+            let accounts = ink::env::test::default_accounts::<ink::env::DefaultEnvironment>();
+            let mut contract = EscrowSmartContract::new();
+            let amount = 100;
+            let escrow_id = contract.initiate_escrow(accounts.bob, amount).unwrap();
+
+            // Test successful deposit
+            ink::env::test::set_value_transferred::<ink::env::DefaultEnvironment>(amount);
+            let deposit_result = contract.deposit_assets(escrow_id);
+            assert!(deposit_result.is_ok(), "Should accept valid deposit");
+
+            // Verify state transition
+            let escrow = contract.escrows
+                .get(escrow_id)
+                .expect("Escrow should exist after deposit");
+            assert_eq!(
+                escrow.state,
+                EscrowState::Funded,
+                "State should transition to Funded after deposit"
+            );
+
+            // Test invalid amount scenario
+            ink::env::test::set_value_transferred::<ink::env::DefaultEnvironment>(amount + 1);
+            let invalid_deposit_result = contract.deposit_assets(escrow_id);
+            assert_eq!(
+                invalid_deposit_result,
+                Err(Error::InvalidState),
+                "Should reject deposit with incorrect amount"
+            );
+        }
+
+        #[ink::test]
+        fn test_deposit_assets_or_return_mismatch() {
+            // Arrange: Create escrow
+            let accounts = ink::env::test::default_accounts::<ink::env::DefaultEnvironment>();
+            let mut contract = EscrowSmartContract::new();
+            let amount = 100;
+            let escrow_id = contract.initiate_escrow(accounts.bob, amount).unwrap();
+
+            // Act & Assert: Overpaying deposit is recorded, not funded
+            ink::env::test::set_value_transferred::<ink::env::DefaultEnvironment>(amount + 1);
+            assert_eq!(
+                contract.deposit_assets_or_return(escrow_id),
+                Ok(false),
+                "Mismatching deposit should be rejected without reverting"
+            );
+            let event = last_event::<DepositRejected>();
+            assert_eq!(event.sent, amount + 1, "Event should carry the sent value");
+            assert_eq!(event.expected, EventAmount::Clear(amount), "Event should carry the amount");
+            let escrow = contract.escrows.get(escrow_id).unwrap();
+            assert_eq!(escrow.deposit_mismatches, 1, "Mismatch should be counted");
+            assert_eq!(escrow.state, EscrowState::Created, "State should remain Created");
+
+            // Correct deposit funds the escrow
+            ink::env::test::set_value_transferred::<ink::env::DefaultEnvironment>(amount);
+            assert_eq!(contract.deposit_assets_or_return(escrow_id), Ok(true), "Should fund escrow");
+            let escrow = contract.escrows.get(escrow_id).unwrap();
+            assert_eq!(escrow.state, EscrowState::Funded, "State should transition to Funded");
+            assert_eq!(escrow.deposit_mismatches, 1, "Counter should persist");
+        }
+
+        #[ink::test]
+        fn test_complete_escrow() {
+            // Arrange: Set up funded escrow
+            let accounts = ink::env::test::default_accounts::<ink::env::DefaultEnvironment>();
+            let mut contract = EscrowSmartContract::new();
+            let amount = 100;
+
+            // Initialize and fund escrow as buyer
+            ink::env::test::set_caller::<ink::env::DefaultEnvironment>(accounts.alice);
+            let escrow_id = contract.initiate_escrow(accounts.bob, amount).unwrap();
+            ink::env::test::set_value_transferred::<ink::env::DefaultEnvironment>(amount);
+            contract.deposit_assets(escrow_id).unwrap();
+
+            // Act & Assert: Buyer approval
+            let buyer_approval_result = contract.complete_escrow(escrow_id);
+            assert!(buyer_approval_result.is_ok(), "Buyer should approve successfully");
+
+            // Verify partial approval state
+            let escrow = contract.escrows
+                .get(escrow_id)
+                .expect("Escrow should exist after approval");
+            assert!(escrow.buyer_approved, "Buyer approval flag should be set");
+            assert!(!escrow.seller_approved, "Seller should not be approved yet");
+            assert_eq!(
+                escrow.state,
+                EscrowState::Funded,
+                "State should remain Funded until both approve"
+            );
+
+            // Seller approval
+            ink::env::test::set_caller::<ink::env::DefaultEnvironment>(accounts.bob);
+            let seller_approval_result = contract.complete_escrow(escrow_id);
+            assert!(seller_approval_result.is_ok(), "Seller should approve successfully");
+
+            // Verify final state
+            let final_escrow = contract.escrows
+                .get(escrow_id)
+                .expect("Escrow should exist after completion");
+            assert!(final_escrow.buyer_approved, "Buyer approval should persist");
+            assert!(final_escrow.seller_approved, "Seller approval should be set");
+            assert_eq!(
+                final_escrow.state,
+                EscrowState::Completed,
+                "State should transition to Completed after dual approval"
+            );
+            assert_eq!(last_event::<Completed>().approver, accounts.bob, "Event should name the final approver");
+        }
+
+        #[ink::test]
+        fn test_complete_escrow_release_after_block() {
+            // Arrange: Set up funded escrow released no earlier than block 2
+            let accounts = ink::env::test::default_accounts::<ink::env::DefaultEnvironment>();
+            let mut contract = EscrowSmartContract::new();
+            let amount = 100;
+            let terms = EscrowTerms { release_after_block: Some(2), ..Default::default() };
+            let escrow_id = contract
+                .initiate_escrow_with_terms(accounts.bob, amount, terms)
+                .unwrap();
+            ink::env::test::set_value_transferred::<ink::env::DefaultEnvironment>(amount);
+            contract.deposit_assets(escrow_id).unwrap();
+
+            // Buyer approval is not gated
+            assert!(contract.complete_escrow(escrow_id).is_ok(), "Buyer should approve");
+
+            // Act & Assert: Final release is blocked before the target height
+            ink::env::test::set_caller::<ink::env::DefaultEnvironment>(accounts.bob);
+            ink::env::test::advance_block::<ink::env::DefaultEnvironment>();
+            assert_eq!(
+                contract.complete_escrow(escrow_id),
+                Err(Error::BlockNotReached),
+                "Should block release before the target height"
+            );
+            let escrow = contract.escrows.get(escrow_id).unwrap();
+            assert!(!escrow.seller_approved, "Rejected approval should not be stored");
+            assert_eq!(escrow.state, EscrowState::Funded, "State should remain Funded");
+
+            // Release is allowed at the target height
+            ink::env::test::advance_block::<ink::env::DefaultEnvironment>();
+            assert!(
+                contract.complete_escrow(escrow_id).is_ok(),
+                "Should release at the target height"
+            );
+            let escrow = contract.escrows.get(escrow_id).unwrap();
+            assert_eq!(escrow.state, EscrowState::Completed, "State should be Completed");
+        }
+
+        #[ink::test]
+        fn test_complete_escrow_approval_modes() {
+            let accounts = ink::env::test::default_accounts::<ink::env::DefaultEnvironment>();
+            let mut contract = EscrowSmartContract::new();
+            let amount = 100;
+            ink::env::test::set_value_transferred::<ink::env::DefaultEnvironment>(amount);
+
+            // Buyer-only: the buyer's approval alone releases
+            let terms = EscrowTerms { approval_mode: ApprovalMode::BuyerOnly, ..Default::default() };
+            let buyer_only = contract
+                .initiate_escrow_with_terms(accounts.bob, amount, terms)
+                .unwrap();
+            contract.deposit_assets(buyer_only).unwrap();
+            contract.complete_escrow(buyer_only).unwrap();
+            assert_eq!(
+                contract.escrows.get(buyer_only).unwrap().state,
+                EscrowState::Completed,
+                "Buyer approval should complete a buyer-only escrow"
+            );
+
+            // Seller-only: the buyer's approval is not enough, the seller's is
+            let terms = EscrowTerms { approval_mode: ApprovalMode::SellerOnly, ..Default::default() };
+            let seller_only = contract
+                .initiate_escrow_with_terms(accounts.bob, amount, terms)
+                .unwrap();
+            contract.deposit_assets(seller_only).unwrap();
+            contract.complete_escrow(seller_only).unwrap();
+            assert_eq!(
+                contract.escrows.get(seller_only).unwrap().state,
+                EscrowState::Funded,
+                "Buyer approval should not complete a seller-only escrow"
+            );
+            ink::env::test::set_caller::<ink::env::DefaultEnvironment>(accounts.bob);
+            contract.complete_escrow(seller_only).unwrap();
+            assert_eq!(
+                contract.escrows.get(seller_only).unwrap().state,
+                EscrowState::Completed,
+                "Seller approval should complete a seller-only escrow"
+            );
+
+            // Default mode still requires both parties
+            assert_eq!(
+                EscrowTerms::default().approval_mode,
+                ApprovalMode::BothParties,
+                "Default mode should require both parties"
+            );
+        }
+
+        #[ink::test]
+        fn test_split_escrow() {
+            // Arrange: Split 100 between Bob and Charlie
+            let accounts = ink::env::test::default_accounts::<ink::env::DefaultEnvironment>();
+            let mut contract = EscrowSmartContract::new();
+            let amount = 100;
+            let escrow_id = contract
+                .initiate_split_escrow(vec![(accounts.bob, 60), (accounts.charlie, 40)], amount)
+                .unwrap();
+            let escrow = contract.escrows.get(escrow_id).unwrap();
+            assert_eq!(escrow.seller, accounts.bob, "First recipient should act as seller");
+
+            // Fund and approve as buyer and lead recipient
+            ink::env::test::set_value_transferred::<ink::env::DefaultEnvironment>(amount);
+            contract.deposit_assets(escrow_id).unwrap();
+            contract.complete_escrow(escrow_id).unwrap();
+            let bob_balance = ink::env::test::get_account_balance::<ink::env::DefaultEnvironment>(
+                accounts.bob
+            ).unwrap();
+            let charlie_balance = ink::env::test::get_account_balance::<ink::env::DefaultEnvironment>(
+                accounts.charlie
+            ).unwrap();
+            ink::env::test::set_caller::<ink::env::DefaultEnvironment>(accounts.bob);
+            contract.complete_escrow(escrow_id).unwrap();
+
+            // Assert: Each recipient received their share
+            assert_eq!(
+                ink::env::test::get_account_balance::<ink::env::DefaultEnvironment>(
+                    accounts.bob
+                ).unwrap(),
+                bob_balance + 60,
+                "Bob should receive his share"
+            );
+            assert_eq!(
+                ink::env::test::get_account_balance::<ink::env::DefaultEnvironment>(
+                    accounts.charlie
+                ).unwrap(),
+                charlie_balance + 40,
+                "Charlie should receive his share"
+            );
+        }
+
+        #[ink::test]
+        fn test_split_escrow_validation() {
+            let accounts = ink::env::test::default_accounts::<ink::env::DefaultEnvironment>();
+            let mut contract = EscrowSmartContract::new_with_arbiter(accounts.eve).unwrap();
+
+            // Empty recipient list
+            assert_eq!(
+                contract.initiate_split_escrow(Vec::new(), 100),
+                Err(Error::NoRecipients),
+                "Should reject an empty recipient list"
+            );
+            // Buyer among the recipients
+            assert_eq!(
+                contract.initiate_split_escrow(vec![(accounts.bob, 50), (accounts.alice, 50)], 100),
+                Err(Error::InvalidParticipants),
+                "Should reject the buyer as a recipient"
+            );
+            // Shares not matching the total
+            assert_eq!(
+                contract.initiate_split_escrow(vec![(accounts.bob, 50), (accounts.charlie, 40)], 100),
+                Err(Error::ShareSumMismatch),
+                "Should reject shares that don't sum to the total"
+            );
+            // Arbiter among the recipients
+            assert_eq!(
+                contract.initiate_split_escrow(vec![(accounts.bob, 50), (accounts.eve, 50)], 100),
+                Err(Error::InvalidParticipants),
+                "Should reject the arbiter as a recipient"
+            );
+        }
+
+        #[ink::test]
+        fn test_cancel_escrow_by_buyer() {
+            // Arrange: Create and fund escrow
+            let accounts = ink::env::test::default_accounts::<ink::env::DefaultEnvironment>();
+            let mut contract = EscrowSmartContract::new();
+            let amount = 100;
+            let escrow_id = contract.initiate_escrow(accounts.bob, amount).unwrap();
+
+            // Fund escrow
+            ink::env::test::set_value_transferred::<ink::env::DefaultEnvironment>(amount);
+            contract.deposit_assets(escrow_id).unwrap();
+
+            // Act: Cancel by buyer
+            let cancel_result = contract.cancel_escrow(escrow_id);
+            assert!(cancel_result.is_ok(), "Buyer should cancel successfully");
+
+            // Assert: State transition and funds handling
+            let escrow = contract.escrows
+                .get(escrow_id)
+                .expect("Escrow should exist after cancellation");
+            assert_eq!(escrow.state, EscrowState::Canceled, "State should transition to Canceled");
+        }
+
+        #[ink::test]
+        fn test_cancel_escrow_refund_address() {
+            // Arrange: Create and fund escrow
+            let accounts = ink::env::test::default_accounts::<ink::env::DefaultEnvironment>();
+            let mut contract = EscrowSmartContract::new();
+            let amount = 100;
+            let escrow_id = contract.initiate_escrow(accounts.bob, amount).unwrap();
+            ink::env::test::set_value_transferred::<ink::env::DefaultEnvironment>(amount);
+            contract.deposit_assets(escrow_id).unwrap();
+
+            // Act & Assert: Only the buyer sets a real refund address
+            assert_eq!(
+                contract.set_refund_address(escrow_id, AccountId::from([0u8; 32])),
+                Err(Error::ZeroAddress),
+                "Should reject the zero account"
+            );
+            ink::env::test::set_caller::<ink::env::DefaultEnvironment>(accounts.bob);
+            assert_eq!(
+                contract.set_refund_address(escrow_id, accounts.bob),
+                Err(Error::NotBuyer),
+                "Seller should not set the refund address"
+            );
+            ink::env::test::set_caller::<ink::env::DefaultEnvironment>(accounts.alice);
+            assert!(
+                contract.set_refund_address(escrow_id, accounts.django).is_ok(),
+                "Buyer should set the refund address"
+            );
+            assert_eq!(
+                last_event::<RefundAddressSet>().refund_address,
+                accounts.django,
+                "Event should carry the refund address"
+            );
+
+            // Cancellation refunds the refund address
+            let django_balance = ink::env::test::get_account_balance::<ink::env::DefaultEnvironment>(
+                accounts.django
+            ).unwrap();
+            contract.cancel_escrow(escrow_id).unwrap();
+            assert_eq!(
+                ink::env::test::get_account_balance::<ink::env::DefaultEnvironment>(
+                    accounts.django
+                ).unwrap(),
+                django_balance + amount,
+                "Refund should go to the refund address"
+            );
+            assert_eq!(
+                contract.set_refund_address(escrow_id, accounts.eve),
+                Err(Error::InvalidState),
+                "Should reject refund address on a settled escrow"
+            );
+        }
+
+        #[ink::test]
+        fn test_cancel_escrow_completed() {
+            // Arrange: Create completed escrow
+            let accounts = ink::env::test::default_accounts::<ink::env::DefaultEnvironment>();
+            let mut contract = EscrowSmartContract::new();
+            let amount = 100;
+            let escrow_id = contract.initiate_escrow(accounts.bob, amount).unwrap();
+
+            // Complete escrow lifecycle
+            ink::env::test::set_value_transferred::<ink::env::DefaultEnvironment>(amount);
+            contract.deposit_assets(escrow_id).unwrap();
+            contract.complete_escrow(escrow_id).unwrap(); // Buyer approval
+            ink::env::test::set_caller::<ink::env::DefaultEnvironment>(accounts.bob);
+            contract.complete_escrow(escrow_id).unwrap(); // Seller approval
+
+            // Act & Assert: Cancel completed escrow
+            ink::env::test::set_caller::<ink::env::DefaultEnvironment>(accounts.alice);
+            let cancel_result = contract.cancel_escrow(escrow_id);
+            assert_eq!(
+                cancel_result,
+                Err(Error::InvalidState),
+                "Should prevent canceling completed escrow"
+            );
+
+            // Verify state persistence
+            let escrow = contract.escrows.get(escrow_id).expect("Escrow should still exist");
+            assert_eq!(escrow.state, EscrowState::Completed, "State should remain Completed");
+        }
+
+        #[ink::test]
+        fn test_unauthorized_actions() {
+            // Arrange: Create escrow and set up unauthorized caller
+            let accounts = ink::env::test::default_accounts::<ink::env::DefaultEnvironment>();
+            let mut contract = EscrowSmartContract::new();
+            let amount = 100;
+            let escrow_id = contract.initiate_escrow(accounts.bob, amount).unwrap();
+
+            // Set unauthorized caller (Charlie)
+            ink::env::test::set_caller::<ink::env::DefaultEnvironment>(accounts.charlie);
+
+            // Test 1: Unauthorized deposit attempt
+            ink::env::test::set_value_transferred::<ink::env::DefaultEnvironment>(amount);
+            let deposit_result = contract.deposit_assets(escrow_id);
+            assert_eq!(
+                deposit_result,
+                Err(Error::NotBuyer),
+                "Should prevent deposits by anyone but the buyer"
+            );
+
+            // Test 2: Unauthorized completion attempt
+            let complete_result = contract.complete_escrow(escrow_id);
+            assert_eq!(
+                complete_result,
+                Err(Error::InvalidState),
+                "Should prevent unauthorized completion"
+            );
+
+            // Test 3: Seller deposit attempt
+            ink::env::test::set_caller::<ink::env::DefaultEnvironment>(accounts.bob);
+            assert_eq!(
+                contract.deposit_assets(escrow_id),
+                Err(Error::NotBuyer),
+                "Should prevent deposits by the seller"
+            );
+            ink::env::test::set_caller::<ink::env::DefaultEnvironment>(accounts.charlie);
+
+            // Test 4: Unauthorized cancellation attempt
+            let cancel_result = contract.cancel_escrow(escrow_id);
+            assert_eq!(
+                cancel_result,
+                Err(Error::Unauthorized),
+                "Should prevent unauthorized cancellation"
+            );
+        }
+
+        #[ink::test]
+        fn test_has_approved() {
+            // Arrange: Set up funded escrow
+            let accounts = ink::env::test::default_accounts::<ink::env::DefaultEnvironment>();
+            let mut contract = EscrowSmartContract::new();
+            let amount = 100;
+            let escrow_id = contract.initiate_escrow(accounts.bob, amount).unwrap();
+            ink::env::test::set_value_transferred::<ink::env::DefaultEnvironment>(amount);
+            contract.deposit_assets(escrow_id).unwrap();
+
+            // Act: Buyer approval
+            contract.complete_escrow(escrow_id).unwrap();
+
+            // Assert: Per-party approval flags
+            assert_eq!(
+                contract.has_approved(escrow_id, accounts.alice),
+                Ok(true),
+                "Buyer approval should be reported"
+            );
+            assert_eq!(
+                contract.has_approved(escrow_id, accounts.bob),
+                Ok(false),
+                "Seller should not be approved yet"
+            );
+            assert_eq!(
+                contract.has_approved(escrow_id, accounts.charlie),
+                Err(Error::Unauthorized),
+                "Non-participants should be rejected"
+            );
+            assert_eq!(
+                contract.has_approved(escrow_id + 1, accounts.alice),
+                Err(Error::NotFound),
+                "Unknown escrows should be rejected"
+            );
+        }
+
+        #[ink::test]
+        fn test_get_participants() {
+            // Arrange: Create escrow
+            let accounts = ink::env::test::default_accounts::<ink::env::DefaultEnvironment>();
+            let mut contract = EscrowSmartContract::new();
+            let escrow_id = contract.initiate_escrow(accounts.bob, 100).unwrap();
+
+            // Act & Assert: Participants of an existing escrow
+            let participants = contract
+                .get_participants(escrow_id)
+                .expect("Participants should exist for a created escrow");
+            assert_eq!(participants.buyer, accounts.alice, "Buyer should be caller");
+            assert_eq!(participants.seller, accounts.bob, "Seller should match argument");
+            assert_eq!(participants.arbiter, None, "No arbiter should be assigned");
+
+            // Unknown escrow
+            assert_eq!(
+                contract.get_participants(escrow_id + 1),
+                None,
+                "Unknown escrows should return None"
+            );
+        }
+
+        #[ink::test]
+        fn test_get_escrow_state() {
+            // Arrange: Create escrow
+            let accounts = ink::env::test::default_accounts::<ink::env::DefaultEnvironment>();
+            let mut contract = EscrowSmartContract::new();
+            let amount = 100;
+            let escrow_id = contract.initiate_escrow(accounts.bob, amount).unwrap();
+
+            // Act & Assert: State follows the lifecycle
+            assert_eq!(
+                contract.get_escrow_state(escrow_id),
+                Some(EscrowState::Created),
+                "New escrow should be Created"
+            );
+            ink::env::test::set_value_transferred::<ink::env::DefaultEnvironment>(amount);
+            contract.deposit_assets(escrow_id).unwrap();
+            assert_eq!(
+                contract.get_escrow_state(escrow_id),
+                Some(EscrowState::Funded),
+                "Deposited escrow should be Funded"
+            );
+            assert_eq!(
+                contract.get_escrow_state(escrow_id + 1),
+                None,
+                "Unknown escrows should return None"
+            );
+        }
+
+        #[ink::test]
+        fn test_get_purgeable_and_purge_batch() {
+            // Arrange: Seed created, funded, completed and canceled escrows
+            let accounts = ink::env::test::default_accounts::<ink::env::DefaultEnvironment>();
+            let mut contract = EscrowSmartContract::new();
+            let amount = 100;
+            let created = contract.initiate_escrow(accounts.bob, amount).unwrap();
+            let funded = contract.initiate_escrow(accounts.bob, amount).unwrap();
+            let completed = contract.initiate_escrow(accounts.bob, amount).unwrap();
+            let canceled = contract.initiate_escrow(accounts.bob, amount).unwrap();
+            ink::env::test::set_value_transferred::<ink::env::DefaultEnvironment>(amount);
+            contract.deposit_assets(funded).unwrap();
+            contract.deposit_assets(completed).unwrap();
+            contract.complete_escrow(completed).unwrap();
+            ink::env::test::set_caller::<ink::env::DefaultEnvironment>(accounts.bob);
+            contract.complete_escrow(completed).unwrap();
+            contract.cancel_escrow(canceled).unwrap();
+
+            // Act & Assert: Only terminal escrows are listed
+            assert_eq!(
+                contract.get_purgeable(0, 10),
+                vec![completed, canceled],
+                "Should list only completed and canceled escrows"
+            );
+            assert_eq!(
+                contract.get_purgeable(completed + 1, 10),
+                vec![canceled],
+                "Should respect the scanned range"
+            );
+
+            // Purge everything; non-terminal escrows are skipped
+            ink::env::test::set_caller::<ink::env::DefaultEnvironment>(accounts.charlie);
+            assert_eq!(
+                contract.purge_batch(vec![created, funded, completed, canceled]),
+                Ok(2),
+                "Should purge only terminal escrows"
+            );
+            assert!(contract.escrows.get(completed).is_none(), "Completed escrow should be purged");
+            assert!(contract.escrows.get(canceled).is_none(), "Canceled escrow should be purged");
+            assert!(contract.escrows.get(created).is_some(), "Created escrow should remain");
+            assert!(contract.escrows.get(funded).is_some(), "Funded escrow should remain");
+            assert!(contract.get_purgeable(0, 10).is_empty(), "Nothing left to purge");
+
+            // Oversized batches are rejected
+            let too_many = (0..u64::from(MAX_BATCH_SIZE) + 1).collect();
+            assert_eq!(
+                contract.purge_batch(too_many),
+                Err(Error::BatchTooLarge),
+                "Should bound the batch size"
+            );
+        }
+
+        #[ink::test]
+        fn test_purge_removes_escrow_data() {
+            // Arrange: an escrow canceled by the seller while the buyer's settlement was pending
+            let accounts = ink::env::test::default_accounts::<ink::env::DefaultEnvironment>();
+            let mut contract = EscrowSmartContract::new();
+            ink::env::test::set_caller::<ink::env::DefaultEnvironment>(accounts.charlie);
+            let escrow_id = contract.initiate_escrow(accounts.bob, 100).unwrap();
+            ink::env::test::set_value_transferred::<ink::env::DefaultEnvironment>(100);
+            contract.deposit_assets(escrow_id).unwrap();
+            contract.propose_settlement(escrow_id, 50, 50).unwrap();
+            ink::env::test::set_caller::<ink::env::DefaultEnvironment>(accounts.bob);
+            contract.cancel_escrow(escrow_id).unwrap();
+            assert!(contract.pending_settlements.get(escrow_id).is_some(), "Settlement should still be pending");
+            assert!(contract.history.get(escrow_id).is_some(), "History should be recorded");
+
+            // Act
+            assert_eq!(contract.purge_batch(ink::prelude::vec![escrow_id]), Ok(1), "Should purge the escrow");
+
+            // Assert
+            assert!(contract.history.get(escrow_id).is_none(), "History should be removed");
+            assert!(contract.pending_settlements.get(escrow_id).is_none(), "Proposals should be removed");
+            assert!(contract.amendment_history.get(escrow_id).is_none(), "Amendments should be removed");
+        }
+
+        #[ink::test]
+        fn test_next_auto_action_at() {
+            // Arrange: Funded escrow with a release deadline and an approval TTL
+            let accounts = ink::env::test::default_accounts::<ink::env::DefaultEnvironment>();
+            let mut contract = EscrowSmartContract::new();
+            let amount = 100;
+            let terms = EscrowTerms {
+                approval_ttl: Some(500),
+                release_deadline: Some(1_000),
+                ..Default::default()
+            };
+            let escrow_id = contract
+                .initiate_escrow_with_terms(accounts.bob, amount, terms)
+                .unwrap();
+            assert_eq!(
+                contract.next_auto_action_at(escrow_id),
+                None,
+                "Unfunded escrows have no automatic action"
+            );
+            ink::env::test::set_value_transferred::<ink::env::DefaultEnvironment>(amount);
+            contract.deposit_assets(escrow_id).unwrap();
+
+            // Act & Assert: Only the sweep timer is running
+            assert_eq!(
+                contract.next_auto_action_at(escrow_id),
+                Some((AutoAction::Sweep as u8, 1_001)),
+                "Sweep should be the only timer"
+            );
+
+            // An early approval expires before the sweep
+            ink::env::test::set_block_timestamp::<ink::env::DefaultEnvironment>(100);
+            contract.complete_escrow(escrow_id).unwrap();
+            assert_eq!(
+                contract.next_auto_action_at(escrow_id),
+                Some((AutoAction::ApprovalExpiry as u8, 601)),
+                "Approval expiry should be the earliest timer"
+            );
+
+            // A late approval expires after the sweep
+            ink::env::test::set_caller::<ink::env::DefaultEnvironment>(accounts.charlie);
+            ink::env::test::set_block_timestamp::<ink::env::DefaultEnvironment>(601);
+            contract.prune_stale_approval(escrow_id, accounts.alice).unwrap();
+            ink::env::test::set_caller::<ink::env::DefaultEnvironment>(accounts.alice);
+            ink::env::test::set_block_timestamp::<ink::env::DefaultEnvironment>(900);
+            contract.complete_escrow(escrow_id).unwrap();
+            assert_eq!(
+                contract.next_auto_action_at(escrow_id),
+                Some((AutoAction::Sweep as u8, 1_001)),
+                "Sweep should be the earliest timer"
+            );
+            assert_eq!(contract.next_auto_action_at(escrow_id + 1), None, "Unknown escrow");
+        }
+
+        #[ink::test]
+        fn test_prune_stale_approval() {
+            // Arrange: Set up funded escrow with a one second approval TTL
+            let accounts = ink::env::test::default_accounts::<ink::env::DefaultEnvironment>();
+            let mut contract = EscrowSmartContract::new();
+            let amount = 100;
+            let terms = EscrowTerms { approval_ttl: Some(1_000), ..Default::default() };
+            let escrow_id = contract
+                .initiate_escrow_with_terms(accounts.bob, amount, terms)
+                .unwrap();
+            ink::env::test::set_value_transferred::<ink::env::DefaultEnvironment>(amount);
+            contract.deposit_assets(escrow_id).unwrap();
+
+            // Buyer approves at t = 0
+            ink::env::test::set_block_timestamp::<ink::env::DefaultEnvironment>(0);
+            contract.complete_escrow(escrow_id).unwrap();
+
+            // Act & Assert: Approval within the TTL cannot be pruned
+            ink::env::test::set_caller::<ink::env::DefaultEnvironment>(accounts.charlie);
+            ink::env::test::set_block_timestamp::<ink::env::DefaultEnvironment>(1_000);
+            assert_eq!(
+                contract.prune_stale_approval(escrow_id, accounts.alice),
+                Err(Error::InvalidState),
+                "Fresh approvals should not be pruned"
+            );
+
+            // Approval past the TTL is pruned by anyone
+            ink::env::test::set_block_timestamp::<ink::env::DefaultEnvironment>(1_001);
+            assert!(
+                contract.prune_stale_approval(escrow_id, accounts.alice).is_ok(),
+                "Stale approval should be pruned"
+            );
+            let event = last_event::<ApprovalExpired>();
+            assert_eq!(event.escrow_id, escrow_id, "Event should reference the escrow");
+            assert_eq!(event.party, accounts.alice, "Event should reference the buyer");
+
+            // Verify cleared flag
+            let escrow = contract.escrows.get(escrow_id).expect("Escrow should still exist");
+            assert!(!escrow.buyer_approved, "Buyer approval flag should be cleared");
+            assert_eq!(escrow.state, EscrowState::Funded, "State should remain Funded");
+
+            // Seller never approved, so there is nothing to prune
+            assert_eq!(
+                contract.prune_stale_approval(escrow_id, accounts.bob),
+                Err(Error::InvalidState),
+                "Missing approvals should not be pruned"
+            );
+            assert_eq!(
+                contract.prune_stale_approval(escrow_id, accounts.charlie),
+                Err(Error::Unauthorized),
+                "Non-participants have no approval to prune"
+            );
+        }
+
+        #[ink::test]
+        fn test_sweep_expired() {
+            // Arrange: Set up funded escrow with a release deadline and keeper reward
+            let accounts = ink::env::test::default_accounts::<ink::env::DefaultEnvironment>();
+            let mut contract = EscrowSmartContract::new();
+            let amount = 100;
+            let terms = EscrowTerms {
+                release_deadline: Some(1_000),
+                keeper_reward: 5,
+                ..Default::default()
+            };
+            let escrow_id = contract
+                .initiate_escrow_with_terms(accounts.bob, amount, terms)
+                .unwrap();
+            ink::env::test::set_value_transferred::<ink::env::DefaultEnvironment>(amount);
+            contract.deposit_assets(escrow_id).unwrap();
+
+            // Act & Assert: Sweeping before the deadline fails
+            ink::env::test::set_caller::<ink::env::DefaultEnvironment>(accounts.charlie);
+            ink::env::test::set_block_timestamp::<ink::env::DefaultEnvironment>(1_000);
+            assert_eq!(
+                contract.sweep_expired(escrow_id),
+                Err(Error::DeadlineNotReached),
+                "Should prevent sweeping before the deadline"
+            );
+
+            // Sweeping after the deadline pays the keeper
+            let keeper_balance = ink::env::test::get_account_balance::<ink::env::DefaultEnvironment>(
+                accounts.charlie
+            ).unwrap();
+            ink::env::test::set_block_timestamp::<ink::env::DefaultEnvironment>(1_001);
+            assert!(contract.sweep_expired(escrow_id).is_ok(), "Keeper should sweep expired escrow");
+            assert_eq!(
+                ink::env::test::get_account_balance::<ink::env::DefaultEnvironment>(
+                    accounts.charlie
+                ).unwrap(),
+                keeper_balance + 5,
+                "Keeper should receive the reward"
+            );
+            let event = last_event::<Swept>();
+            assert_eq!(event.escrow_id, escrow_id, "Event should reference the escrow");
+            assert_eq!(event.keeper, accounts.charlie, "Event should reference the keeper");
+            let events = ink::env::test::recorded_events().collect::<Vec<_>>();
+            let canceled = <Canceled as scale::Decode>::decode(&mut &events[events.len() - 2].data[..]).expect("Event should decode");
+            assert_eq!(canceled.reason, CancelReason::Expired, "Sweep cancels as expired");
+
+            // Verify state transition and double-sweep protection
+            let escrow = contract.escrows.get(escrow_id).expect("Escrow should still exist");
+            assert_eq!(escrow.state, EscrowState::Canceled, "State should transition to Canceled");
+            assert_eq!(
+                contract.sweep_expired(escrow_id),
+                Err(Error::InvalidState),
+                "Should prevent double sweeps"
+            );
+        }
+
+        #[ink::test]
+        fn test_propose_and_accept_amount() {
+            // Arrange: Create escrow
+            let accounts = ink::env::test::default_accounts::<ink::env::DefaultEnvironment>();
+            let mut contract = EscrowSmartContract::new();
+            let escrow_id = contract.initiate_escrow(accounts.bob, 100).unwrap();
+
+            // Act & Assert: Only the seller may propose
+            assert_eq!(
+                contract.propose_amount(escrow_id, 150),
+                Err(Error::NotSeller),
+                "Buyer should not propose amounts"
+            );
+            ink::env::test::set_caller::<ink::env::DefaultEnvironment>(accounts.bob);
+            assert_eq!(
+                contract.propose_amount(escrow_id, 0),
+                Err(Error::InvalidAmount),
+                "Zero amounts should be rejected"
+            );
+            assert!(contract.propose_amount(escrow_id, 150).is_ok(), "Seller should propose");
+            assert_eq!(
+                last_event::<AmountProposed>().amount,
+                EventAmount::Clear(150),
+                "Event should carry proposal"
+            );
+
+            // Proposal does not change the amount until accepted
+            let escrow = contract.escrows.get(escrow_id).unwrap();
+            assert_eq!(escrow.amount, 100, "Amount should be unchanged before acceptance");
+            assert_eq!(escrow.proposed_amount, Some(150), "Proposal should be stored");
+
+            // Only the buyer may accept
+            assert_eq!(
+                contract.accept_amount(escrow_id),
+                Err(Error::NotBuyer),
+                "Seller should not accept own proposal"
+            );
+            ink::env::test::set_caller::<ink::env::DefaultEnvironment>(accounts.alice);
+            assert!(contract.accept_amount(escrow_id).is_ok(), "Buyer should accept");
+            assert_eq!(
+                last_event::<AmountAccepted>().amount,
+                EventAmount::Clear(150),
+                "Event should carry amount"
+            );
+            let escrow = contract.escrows.get(escrow_id).unwrap();
+            assert_eq!(escrow.amount, 150, "Amount should be updated");
+            assert_eq!(escrow.proposed_amount, None, "Proposal should be consumed");
+            assert_eq!(
+                contract.accept_amount(escrow_id),
+                Err(Error::InvalidState),
+                "Nothing left to accept"
+            );
+
+            // Proposals are rejected once funded
+            ink::env::test::set_value_transferred::<ink::env::DefaultEnvironment>(150);
+            contract.deposit_assets(escrow_id).unwrap();
+            ink::env::test::set_caller::<ink::env::DefaultEnvironment>(accounts.bob);
+            assert_eq!(
+                contract.propose_amount(escrow_id, 200),
+                Err(Error::InvalidState),
+                "Should reject proposals once funded"
+            );
+        }
+
+        #[ink::test]
+        fn test_keeper_reward_exceeding_amount() {
+            let accounts = ink::env::test::default_accounts::<ink::env::DefaultEnvironment>();
+            let mut contract = EscrowSmartContract::new();
+            let terms = EscrowTerms { keeper_reward: 101, ..Default::default() };
+            assert_eq!(
+                contract.initiate_escrow_with_terms(accounts.bob, 100, terms),
+                Err(Error::InvalidAmount),
+                "Keeper reward should not exceed the amount"
+            );
+        }
+
+        #[ink::test]
+        fn test_get_liabilities() {
+            // Arrange: a 10% fee, a funded mutual-stake escrow, a funded escrow and an unfunded one
+            let accounts = ink::env::test::default_accounts::<ink::env::DefaultEnvironment>();
+            let mut contract = EscrowSmartContract::new();
+            contract.set_fee(1_000).unwrap();
+            let first = contract.initiate_stake_escrow(accounts.bob, 100, 30).unwrap();
+            ink::env::test::set_value_transferred::<ink::env::DefaultEnvironment>(100);
+            contract.deposit_assets(first).unwrap();
+            ink::env::test::set_caller::<ink::env::DefaultEnvironment>(accounts.bob);
+            ink::env::test::set_value_transferred::<ink::env::DefaultEnvironment>(30);
+            contract.deposit_stake(first).unwrap();
+            ink::env::test::set_caller::<ink::env::DefaultEnvironment>(accounts.alice);
+            let second = contract.initiate_escrow(accounts.bob, 40).unwrap();
+            ink::env::test::set_value_transferred::<ink::env::DefaultEnvironment>(40);
+            contract.deposit_assets(second).unwrap();
+            contract.initiate_escrow(accounts.bob, 10).unwrap();
+            assert_eq!(
+                contract.get_liabilities(),
+                Liabilities { locked_principal: 140, posted_bonds: 30, pending_withdrawals: 0 },
+                "Should report the principal and the bond"
+            );
+
+            // Act: release the staked escrow, paying the fee out and releasing the bond
+            contract.complete_escrow(first).unwrap();
+            ink::env::test::set_caller::<ink::env::DefaultEnvironment>(accounts.bob);
+            contract.complete_escrow(first).unwrap();
+
+            // Assert
+            assert_eq!(
+                contract.get_liabilities(),
+                Liabilities { locked_principal: 40, posted_bonds: 0, pending_withdrawals: 0 },
+                "Fees are paid out on release, so none are held"
+            );
+
+            // Act: cancel the other
+            contract.cancel_escrow(second).unwrap();
+
+            // Assert
+            assert_eq!(
+                contract.get_liabilities(),
+                Liabilities { locked_principal: 0, posted_bonds: 0, pending_withdrawals: 0 },
+                "Settled escrows owe nothing"
+            );
+
+            // Act: a refund owed to django. The off-chain environment cannot make a transfer
+            // fail, so the owed refund is recorded directly.
+            contract.pending_refunds.insert(accounts.django, &25);
+            contract.total_locked += 25;
+            contract.total_pending_refunds += 25;
+            assert_eq!(
+                contract.get_liabilities(),
+                Liabilities { locked_principal: 0, posted_bonds: 0, pending_withdrawals: 25 },
+                "Should report the owed refund"
+            );
+            ink::env::test::set_caller::<ink::env::DefaultEnvironment>(accounts.django);
+            contract.claim_refund().unwrap();
+
+            // Assert
+            assert_eq!(
+                contract.get_liabilities(),
+                Liabilities { locked_principal: 0, posted_bonds: 0, pending_withdrawals: 0 },
+                "Claimed refund should be settled"
+            );
+            assert_eq!(contract.get_total_locked(), 0, "Categories should add up to the total locked");
+        }
+
+        #[ink::test]
+        fn test_config_hash() {
+            // Arrange
+            let accounts = ink::env::test::default_accounts::<ink::env::DefaultEnvironment>();
+            let mut contract = EscrowSmartContract::new();
+            let mut seen = ink::prelude::vec![contract.config_hash()];
+            assert_eq!(contract.config_hash(), seen[0], "Hash should be stable without changes");
+            contract.initiate_escrow(accounts.bob, 100).unwrap();
+            assert_eq!(contract.config_hash(), seen[0], "Escrows are not configuration");
+
+            // Act & Assert: every setting changes the hash
+            let mut assert_changed = |contract: &EscrowSmartContract, setting: &str| {
+                let hash = contract.config_hash();
+                assert!(!seen.contains(&hash), "{setting} should change the hash");
+                seen.push(hash);
+            };
+            contract.set_fee(100).unwrap();
+            assert_changed(&contract, "Fee");
+            let without_override = contract.config_hash();
+            contract.set_fee_override(accounts.charlie, 50).unwrap();
+            assert_changed(&contract, "Fee override");
+            contract.set_fee_override(accounts.charlie, 60).unwrap();
+            assert_changed(&contract, "Fee override update");
+            contract.set_fee_override(accounts.charlie, FEE_OVERRIDE_REMOVED).unwrap();
+            assert_eq!(contract.config_hash(), without_override, "The hash covers the values, so removing the override restores it");
+            contract.set_max_open_per_buyer(5).unwrap();
+            assert_changed(&contract, "Open escrow limit");
+            contract.set_id_recycling(true).unwrap();
+            assert_changed(&contract, "ID recycling");
+            contract.set_high_value_threshold(1_000).unwrap();
+            assert_changed(&contract, "High-value threshold");
+            contract.set_refund_lock_period(1_000).unwrap();
+            assert_changed(&contract, "Refund lock period");
+            // The off-chain environment cannot replace code, so the upgrade is recorded directly.
+            contract.code_hash = Some(Hash::from([1u8; 32]));
+            assert_changed(&contract, "Code hash");
+            contract.shutdown().unwrap();
+            assert_changed(&contract, "Shutdown");
+            contract.set_admins(ink::prelude::vec![accounts.bob, accounts.charlie], 2).unwrap();
+            assert_changed(&contract, "Admins");
+            assert_changed(&EscrowSmartContract::new_with_obfuscated_amounts(), "Amount obfuscation");
+            assert_changed(&EscrowSmartContract::new_with_arbiter(accounts.eve).unwrap(), "Default arbiter");
+        }
+
+        #[ink::test]
+        fn test_amend_escrow() {
+            // Arrange: Create escrow with a release deadline
+            let accounts = ink::env::test::default_accounts::<ink::env::DefaultEnvironment>();
+            let mut contract = EscrowSmartContract::new();
+            let terms = EscrowTerms { release_deadline: Some(1_000), ..Default::default() };
+            let escrow_id = contract.initiate_escrow_with_terms(accounts.bob, 100, terms).unwrap();
+            let signed = Hash::from([1u8; 32]);
+
+            // Act: Buyer signs the amendment
+            assert!(
+                contract.amend_escrow(escrow_id, signed, 150, Some(2_000)).is_ok(),
+                "Buyer should sign the amendment"
+            );
+            assert_eq!(
+                contract.escrows.get(escrow_id).unwrap().amount,
+                100,
+                "Amendment should not apply with a single signature"
+            );
+
+            // Assert: Seller signing a different document is rejected
+            ink::env::test::set_caller::<ink::env::DefaultEnvironment>(accounts.bob);
+            assert_eq!(
+                contract.amend_escrow(escrow_id, Hash::from([2u8; 32]), 150, Some(2_000)),
+                Err(Error::AmendmentMismatch),
+                "Should reject mismatched hashes"
+            );
+            assert_eq!(
+                contract.amend_escrow(escrow_id, signed, 160, Some(2_000)),
+                Err(Error::AmendmentMismatch),
+                "Should reject terms that differ from the signed amendment"
+            );
+
+            // Seller signing the same document applies it
+            assert!(
+                contract.amend_escrow(escrow_id, signed, 150, Some(2_000)).is_ok(),
+                "Seller should countersign the amendment"
+            );
+            assert_eq!(last_event::<Amended>().amendment_hash, signed, "Event should carry hash");
+            let escrow = contract.escrows.get(escrow_id).unwrap();
+            assert_eq!(escrow.amount, 150, "Amount should be amended");
+            assert_eq!(escrow.terms.release_deadline, Some(2_000), "Deadline should be amended");
+            let history = contract.get_amendment_history(escrow_id);
+            assert_eq!(history.len(), 1, "History should record the amendment");
+            assert_eq!(history[0].amendment_hash, signed, "History should keep the hash");
+            assert_eq!(history[0].proposer, accounts.alice, "History should keep the proposer");
+
+            // Third parties cannot amend
+            ink::env::test::set_caller::<ink::env::DefaultEnvironment>(accounts.charlie);
+            assert_eq!(
+                contract.amend_escrow(escrow_id, signed, 150, None),
+                Err(Error::Unauthorized),
+                "Should reject non-participants"
+            );
+        }
+
+        #[ink::test]
+        fn test_get_escrows() {
+            // Arrange: Create two escrows
+            let accounts = ink::env::test::default_accounts::<ink::env::DefaultEnvironment>();
+            let mut contract = EscrowSmartContract::new();
+            let first = contract.initiate_escrow(accounts.bob, 100).unwrap();
+            let second = contract.initiate_escrow(accounts.charlie, 200).unwrap();
+
+            // Act
+            let escrows = contract.get_escrows(vec![second, 42, first]);
+
+            // Assert: Results follow request order with None for unknown IDs
+            assert_eq!(escrows.len(), 3, "Should return one entry per ID");
+            assert_eq!(escrows[0].as_ref().map(|e| e.amount), Some(200), "Second escrow first");
+            assert_eq!(escrows[1], None, "Unknown ID should be None");
+            assert_eq!(escrows[2].as_ref().map(|e| e.amount), Some(100), "First escrow last");
+
+            // Input is capped
+            let too_many = vec![first; MAX_BATCH_SIZE as usize + 1];
+            assert_eq!(
+                contract.get_escrows(too_many).len(),
+                MAX_BATCH_SIZE as usize,
+                "Should cap the number of reads"
+            );
+        }
+
+        #[ink::test]
+        fn test_request_payment() {
+            // Arrange: Seller invoices the buyer
+            let accounts = ink::env::test::default_accounts::<ink::env::DefaultEnvironment>();
+            let mut contract = EscrowSmartContract::new();
+            let amount = 100;
+            ink::env::test::set_caller::<ink::env::DefaultEnvironment>(accounts.bob);
+            let escrow_id = contract.request_payment(accounts.alice, amount).unwrap();
+
+            // Assert: Roles and event
+            let event = last_event::<InvoiceCreated>();
+            assert_eq!(event.escrow_id, escrow_id, "Event should reference the escrow");
+            assert_eq!(event.buyer, accounts.alice, "Event should carry the buyer");
+            assert_eq!(event.seller, accounts.bob, "Event should carry the seller");
+            let escrow = contract.escrows.get(escrow_id).unwrap();
+            assert_eq!(escrow.buyer, accounts.alice, "Buyer should be the invoiced account");
+            assert_eq!(escrow.seller, accounts.bob, "Seller should be the caller");
+            assert_eq!(
+                contract.request_payment(accounts.bob, amount),
+                Err(Error::InvalidParticipants),
+                "Should prevent invoicing oneself"
+            );
+
+            // Act: Only the buyer can fund the invoice
+            ink::env::test::set_value_transferred::<ink::env::DefaultEnvironment>(amount);
+            assert_eq!(
+                contract.deposit_assets(escrow_id),
+                Err(Error::NotBuyer),
+                "Seller should not fund own invoice"
+            );
+            ink::env::test::set_caller::<ink::env::DefaultEnvironment>(accounts.alice);
+            assert!(contract.deposit_assets(escrow_id).is_ok(), "Buyer should fund the invoice");
+        }
+
+        #[ink::test]
+        fn test_finalize_after_auto_release_period() {
+            // Arrange: Funded escrow that auto-releases 1000 ms after the buyer approves
+            let accounts = ink::env::test::default_accounts::<ink::env::DefaultEnvironment>();
+            let mut contract = EscrowSmartContract::new();
+            let amount = 100;
+            let terms = EscrowTerms { auto_release_period: Some(1_000), ..Default::default() };
+            let escrow_id = contract
+                .initiate_escrow_with_terms(accounts.bob, amount, terms)
+                .unwrap();
+            ink::env::test::set_value_transferred::<ink::env::DefaultEnvironment>(amount);
+            contract.deposit_assets(escrow_id).unwrap();
+            ink::env::test::set_caller::<ink::env::DefaultEnvironment>(accounts.charlie);
+            assert_eq!(
+                contract.finalize(escrow_id),
+                Err(Error::InvalidState),
+                "Should require the buyer's approval"
+            );
+
+            // Buyer approves at t = 0
+            ink::env::test::set_caller::<ink::env::DefaultEnvironment>(accounts.alice);
+            ink::env::test::set_block_timestamp::<ink::env::DefaultEnvironment>(0);
+            contract.complete_escrow(escrow_id).unwrap();
+            assert_eq!(
+                contract.next_auto_action_at(escrow_id),
+                Some((AutoAction::Finalize as u8, 1_000)),
+                "Finalize timer should be reported"
+            );
+
+            // Act & Assert: Too soon
+            ink::env::test::set_caller::<ink::env::DefaultEnvironment>(accounts.charlie);
+            ink::env::test::set_block_timestamp::<ink::env::DefaultEnvironment>(999);
+            assert_eq!(
+                contract.finalize(escrow_id),
+                Err(Error::DeadlineNotReached),
+                "Should reject finalizing before the period"
+            );
+
+            // After the period anyone can release to the seller
+            let seller_balance = ink::env::test::get_account_balance::<ink::env::DefaultEnvironment>(
+                accounts.bob
+            ).unwrap();
+            ink::env::test::set_block_timestamp::<ink::env::DefaultEnvironment>(1_000);
+            assert!(contract.finalize(escrow_id).is_ok(), "Should finalize after the period");
+            assert_eq!(
+                ink::env::test::get_account_balance::<ink::env::DefaultEnvironment>(
+                    accounts.bob
+                ).unwrap(),
+                seller_balance + amount,
+                "Seller should receive the funds"
+            );
+            assert_eq!(last_event::<AutoFinalized>().by, accounts.charlie, "Event should carry caller");
+            assert_eq!(
+                contract.escrows.get(escrow_id).unwrap().state,
+                EscrowState::Completed,
+                "State should transition to Completed"
+            );
+        }
+
+        #[ink::test]
+        fn test_cancellation_penalty() {
+            // Arrange: Two funded escrows with a 10% cancellation penalty
+            let accounts = ink::env::test::default_accounts::<ink::env::DefaultEnvironment>();
+            let mut contract = EscrowSmartContract::new();
+            let amount = 100;
+            let terms = EscrowTerms { cancellation_penalty_bps: 1_000, ..Default::default() };
+            let by_buyer = contract
+                .initiate_escrow_with_terms(accounts.bob, amount, terms.clone())
+                .unwrap();
+            let by_seller = contract
+                .initiate_escrow_with_terms(accounts.bob, amount, terms)
+                .unwrap();
+            ink::env::test::set_value_transferred::<ink::env::DefaultEnvironment>(amount);
+            contract.deposit_assets(by_buyer).unwrap();
+            contract.deposit_assets(by_seller).unwrap();
+
+            // Act & Assert: Buyer cancellation pays the penalty to the seller
+            let seller_balance = ink::env::test::get_account_balance::<ink::env::DefaultEnvironment>(
+                accounts.bob
+            ).unwrap();
+            contract.cancel_escrow(by_buyer).unwrap();
+            assert_eq!(
+                ink::env::test::get_account_balance::<ink::env::DefaultEnvironment>(
+                    accounts.bob
+                ).unwrap(),
+                seller_balance + 10,
+                "Seller should receive the penalty"
+            );
+            let event = last_event::<Canceled>();
+            assert_eq!(event.refunded, EventAmount::Clear(90), "Event should carry the refund");
+            assert_eq!(event.penalty, EventAmount::Clear(10), "Event should carry the penalty");
+            assert_eq!(event.by, accounts.alice, "Event should name the buyer as canceler");
+            assert_eq!(event.reason, CancelReason::BuyerWithdrew, "Buyer cancellation reason");
+
+            // Seller cancellation refunds in full
+            ink::env::test::set_caller::<ink::env::DefaultEnvironment>(accounts.bob);
+            contract.cancel_escrow(by_seller).unwrap();
+            let event = last_event::<Canceled>();
+            assert_eq!(event.refunded, EventAmount::Clear(100), "Seller cancel refunds in full");
+            assert_eq!(event.penalty, EventAmount::Clear(0), "Seller cancel has no penalty");
+            assert_eq!(event.by, accounts.bob, "Event should name the seller as canceler");
+            assert_eq!(event.reason, CancelReason::SellerDeclined, "Seller cancellation reason");
+
+            // Penalties above 100% are rejected
+            ink::env::test::set_caller::<ink::env::DefaultEnvironment>(accounts.alice);
+            let terms = EscrowTerms { cancellation_penalty_bps: 10_001, ..Default::default() };
+            assert_eq!(
+                contract.initiate_escrow_with_terms(accounts.bob, amount, terms),
+                Err(Error::InvalidFee),
+                "Should reject bps above 10000"
+            );
+        }
+
+        #[ink::test]
+        fn test_get_active_escrows() {
+            // Arrange: Three funded escrows and one unfunded
+            let accounts = ink::env::test::default_accounts::<ink::env::DefaultEnvironment>();
+            let mut contract = EscrowSmartContract::new();
+            let amount = 100;
+            ink::env::test::set_value_transferred::<ink::env::DefaultEnvironment>(amount);
+            let first = contract.initiate_escrow(accounts.bob, amount).unwrap();
+            let second = contract.initiate_escrow(accounts.bob, amount).unwrap();
+            let third = contract.initiate_escrow(accounts.bob, amount).unwrap();
+            let _unfunded = contract.initiate_escrow(accounts.bob, amount).unwrap();
+            contract.deposit_assets(third).unwrap();
+            contract.deposit_assets(first).unwrap();
+            contract.deposit_assets(second).unwrap();
+
+            // Act & Assert: Only funded escrows are listed, ordered by ID
+            assert_eq!(contract.get_active_escrows(0, 10), vec![first, second, third], "Should list funded escrows");
+            assert_eq!(contract.get_active_escrows(second, 1), vec![second], "Should paginate from an ID");
+
+            // Completing and canceling remove escrows from the index
+            contract.cancel_escrow(first).unwrap();
+            assert_eq!(contract.get_active_escrows(0, 10), vec![second, third], "Should keep ID order");
+            contract.complete_escrow(third).unwrap();
+            ink::env::test::set_caller::<ink::env::DefaultEnvironment>(accounts.bob);
+            contract.complete_escrow(third).unwrap();
+            assert_eq!(contract.get_active_escrows(0, 10), vec![second], "Should drop settled escrows");
+            contract.cancel_escrow(second).unwrap();
+            assert!(contract.get_active_escrows(0, 10).is_empty(), "Index should be empty");
+        }
+
+        #[ink::test]
+        fn test_cancel_escrow_refund_splits() {
+            // Arrange: Create and fund escrow
+            let accounts = ink::env::test::default_accounts::<ink::env::DefaultEnvironment>();
+            let mut contract = EscrowSmartContract::new();
+            let amount = 101;
+            let escrow_id = contract.initiate_escrow(accounts.bob, amount).unwrap();
+            ink::env::test::set_value_transferred::<ink::env::DefaultEnvironment>(amount);
+            contract.deposit_assets(escrow_id).unwrap();
+
+            // Act & Assert: Splits must sum to 10_000 basis points
+            assert_eq!(
+                contract.set_refund_splits(escrow_id, vec![(accounts.django, 6_000), (accounts.eve, 3_000)]),
+                Err(Error::InvalidSplit),
+                "Should reject splits that do not sum to 10_000"
+            );
+            assert_eq!(
+                contract.set_refund_splits(escrow_id, vec![(AccountId::from([0u8; 32]), 10_000)]),
+                Err(Error::ZeroAddress),
+                "Should reject the zero account"
+            );
+            ink::env::test::set_caller::<ink::env::DefaultEnvironment>(accounts.bob);
+            assert_eq!(
+                contract.set_refund_splits(escrow_id, vec![(accounts.bob, 10_000)]),
+                Err(Error::NotBuyer),
+                "Seller should not set refund splits"
+            );
+            ink::env::test::set_caller::<ink::env::DefaultEnvironment>(accounts.alice);
+            contract.set_refund_address(escrow_id, accounts.frank).unwrap();
+            assert!(
+                contract
+                    .set_refund_splits(escrow_id, vec![(accounts.django, 6_000), (accounts.eve, 4_000)])
+                    .is_ok(),
+                "Buyer should set refund splits"
+            );
+
+            // Cancellation splits the refund, rounding in favour of the last account
+            let balance = |account| {
+                ink::env::test::get_account_balance::<ink::env::DefaultEnvironment>(account).unwrap()
+            };
+            let (django_balance, eve_balance, frank_balance) = (
+                balance(accounts.django),
+                balance(accounts.eve),
+                balance(accounts.frank),
+            );
+            contract.cancel_escrow(escrow_id).unwrap();
+            assert_eq!(balance(accounts.django), django_balance + 60, "First split should get 60%");
+            assert_eq!(balance(accounts.eve), eve_balance + 41, "Last split should get the remainder");
+            assert_eq!(balance(accounts.frank), frank_balance, "Splits should override the refund address");
+        }
+
+        #[ink::test]
+        fn test_block_number_deadlines() {
+            // Arrange: Two funded escrows with block number deadlines
+            let accounts = ink::env::test::default_accounts::<ink::env::DefaultEnvironment>();
+            let mut contract = EscrowSmartContract::new();
+            let amount = 100;
+            ink::env::test::set_block_timestamp::<ink::env::DefaultEnvironment>(1_000_000);
+            let terms = EscrowTerms {
+                release_deadline: Some(2),
+                auto_release_period: Some(1),
+                deadline_kind: DeadlineKind::BlockNumber,
+                ..Default::default()
+            };
+            let swept = contract.initiate_escrow_with_terms(accounts.bob, amount, terms.clone()).unwrap();
+            let finalized = contract.initiate_escrow_with_terms(accounts.bob, amount, terms).unwrap();
+            ink::env::test::set_value_transferred::<ink::env::DefaultEnvironment>(amount);
+            contract.deposit_assets(swept).unwrap();
+            contract.deposit_assets(finalized).unwrap();
+            contract.complete_escrow(finalized).unwrap();
+
+            // Act & Assert: Deadlines follow the block number, not the timestamp
+            assert_eq!(
+                contract.next_auto_action_at(finalized),
+                Some((AutoAction::Finalize as u8, 1)),
+                "Finalize should be due one block after the approval"
+            );
+            assert_eq!(contract.finalize(finalized), Err(Error::DeadlineNotReached), "Block 0 is too early");
+            ink::env::test::advance_block::<ink::env::DefaultEnvironment>();
+            assert!(contract.finalize(finalized).is_ok(), "Should finalize at block 1");
+
+            ink::env::test::advance_block::<ink::env::DefaultEnvironment>();
+            assert_eq!(contract.sweep_expired(swept), Err(Error::DeadlineNotReached), "Block 2 is the deadline");
+            ink::env::test::advance_block::<ink::env::DefaultEnvironment>();
+            assert!(contract.sweep_expired(swept).is_ok(), "Should sweep after the deadline block");
+        }
+
+        #[ink::test]
+        fn test_get_history() {
+            // Arrange: Create and fund escrow
+            let accounts = ink::env::test::default_accounts::<ink::env::DefaultEnvironment>();
+            let mut contract = EscrowSmartContract::new();
+            let amount = 100;
+            let escrow_id = contract.initiate_escrow(accounts.bob, amount).unwrap();
+            assert!(contract.get_history(escrow_id).is_empty(), "New escrow should have no history");
+            ink::env::test::set_block_timestamp::<ink::env::DefaultEnvironment>(10);
+            ink::env::test::set_value_transferred::<ink::env::DefaultEnvironment>(amount);
+            contract.deposit_assets(escrow_id).unwrap();
+
+            // Act: Seller cancels
+            ink::env::test::set_block_timestamp::<ink::env::DefaultEnvironment>(20);
+            ink::env::test::set_caller::<ink::env::DefaultEnvironment>(accounts.bob);
+            contract.cancel_escrow(escrow_id).unwrap();
+
+            // Assert: Both transitions are recorded with their actor and time
+            assert_eq!(
+                contract.get_history(escrow_id),
+                vec![
+                    StateChange {
+                        from_state: EscrowState::Created,
+                        to_state: EscrowState::Funded,
+                        timestamp: 10,
+                        actor: accounts.alice,
+                    },
+                    StateChange {
+                        from_state: EscrowState::Funded,
+                        to_state: EscrowState::Canceled,
+                        timestamp: 20,
+                        actor: accounts.bob,
+                    }
+                ],
+                "History should record each transition"
+            );
+        }
+
+        #[ink::test]
+        fn test_process_due() {
+            // Arrange: Escrows with different automatic actions
+            let accounts = ink::env::test::default_accounts::<ink::env::DefaultEnvironment>();
+            let mut contract = EscrowSmartContract::new();
+            let amount = 100;
+            ink::env::test::set_value_transferred::<ink::env::DefaultEnvironment>(amount);
+            let sweep_terms = EscrowTerms { release_deadline: Some(100), ..Default::default() };
+            let swept = contract.initiate_escrow_with_terms(accounts.bob, amount, sweep_terms).unwrap();
+            let later_terms = EscrowTerms { release_deadline: Some(1_000), ..Default::default() };
+            let not_due = contract.initiate_escrow_with_terms(accounts.bob, amount, later_terms).unwrap();
+            let finalize_terms = EscrowTerms { auto_release_period: Some(50), ..Default::default() };
+            let finalized = contract.initiate_escrow_with_terms(accounts.bob, amount, finalize_terms).unwrap();
+            let ttl_terms = EscrowTerms { approval_ttl: Some(50), ..Default::default() };
+            let pruned = contract.initiate_escrow_with_terms(accounts.bob, amount, ttl_terms).unwrap();
+            let unfunded = contract.initiate_escrow(accounts.bob, amount).unwrap();
+            for escrow_id in [swept, not_due, finalized, pruned] {
+                contract.deposit_assets(escrow_id).unwrap();
+            }
+            contract.complete_escrow(finalized).unwrap();
+            contract.complete_escrow(pruned).unwrap();
+
+            // Act: A keeper processes the batch after some time
+            ink::env::test::set_block_timestamp::<ink::env::DefaultEnvironment>(101);
+            ink::env::test::set_caller::<ink::env::DefaultEnvironment>(accounts.charlie);
+            let results = contract
+                .process_due(vec![swept, not_due, finalized, pruned, unfunded, 99])
+                .unwrap();
+
+            // Assert: Each escrow reports its own outcome
+            assert_eq!(
+                results,
+                vec![
+                    (swept, Ok(EscrowState::Canceled)),
+                    (not_due, Err(Error::DeadlineNotReached)),
+                    (finalized, Ok(EscrowState::Completed)),
+                    (pruned, Ok(EscrowState::Funded)),
+                    (unfunded, Err(Error::InvalidState)),
+                    (99, Err(Error::NotFound))
+                ],
+                "Should apply due actions and report the rest"
+            );
+            assert_eq!(contract.has_approved(pruned, accounts.alice), Ok(false), "Stale approval should be pruned");
+            assert_eq!(
+                contract.process_due(vec![0; MAX_BATCH_SIZE as usize + 1]),
+                Err(Error::BatchTooLarge),
+                "Should reject oversized batches"
+            );
+        }
+
+        #[ink::test]
+        fn test_process_due_fails_whole_batch() {
+            // Arrange: Two escrows past their release deadline
+            let accounts = ink::env::test::default_accounts::<ink::env::DefaultEnvironment>();
+            let mut contract = EscrowSmartContract::new();
+            let amount = 100;
+            ink::env::test::set_value_transferred::<ink::env::DefaultEnvironment>(amount);
+            let terms = EscrowTerms { release_deadline: Some(100), ..Default::default() };
+            let first = contract.initiate_escrow_with_terms(accounts.bob, amount, terms.clone()).unwrap();
+            let second = contract.initiate_escrow_with_terms(accounts.bob, amount, terms).unwrap();
+            contract.deposit_assets(first).unwrap();
+            contract.deposit_assets(second).unwrap();
+            ink::env::test::set_block_timestamp::<ink::env::DefaultEnvironment>(101);
+
+            // Act: Transfers cannot fail off-chain, so force a failure after the sweep has started
+            contract.seq = u64::MAX;
+            let result = contract.process_due(vec![first, second, 99]);
+
+            // Assert: The failure is returned for the whole batch instead of per escrow
+            assert_eq!(result, Err(Error::IdOverflow), "A failed action should fail the batch");
+        }
+
+        #[ink::test]
+        fn test_reopen_escrow() {
+            // Arrange: Cancel an unfunded and a funded escrow
+            let accounts = ink::env::test::default_accounts::<ink::env::DefaultEnvironment>();
+            let mut contract = EscrowSmartContract::new();
+            let amount = 100;
+            let unfunded = contract.initiate_escrow(accounts.bob, amount).unwrap();
+            let funded = contract.initiate_escrow(accounts.bob, amount).unwrap();
+            ink::env::test::set_value_transferred::<ink::env::DefaultEnvironment>(amount);
+            contract.deposit_assets(funded).unwrap();
+            contract.cancel_escrow(unfunded).unwrap();
+            contract.cancel_escrow(funded).unwrap();
+
+            // Act & Assert: Only the buyer reopens, and only never-funded escrows
+            assert_eq!(contract.reopen_escrow(funded), Err(Error::InvalidState), "Funded escrow stays canceled");
+            ink::env::test::set_caller::<ink::env::DefaultEnvironment>(accounts.bob);
+            assert_eq!(contract.reopen_escrow(unfunded), Err(Error::NotBuyer), "Seller cannot reopen");
+            ink::env::test::set_caller::<ink::env::DefaultEnvironment>(accounts.alice);
+            assert!(contract.reopen_escrow(unfunded).is_ok(), "Buyer should reopen");
+            assert_eq!(last_event::<Reopened>().escrow_id, unfunded, "Event should carry the escrow ID");
+            assert_eq!(
+                contract.get_escrow_state(unfunded),
+                Some(EscrowState::Created),
+                "Reopened escrow should be Created again"
+            );
+            assert_eq!(contract.reopen_escrow(unfunded), Err(Error::InvalidState), "Only canceled escrows reopen");
+
+            // The reopened escrow can be funded as usual
+            assert!(contract.deposit_assets(unfunded).is_ok(), "Reopened escrow should accept a deposit");
+        }
+
+        #[ink::test]
+        fn test_reopen_rejects_escrow_that_held_deposits() {
+            // Arrange: an escrow canceled after an installment, and one canceled after the seller's stake
+            let accounts = ink::env::test::default_accounts::<ink::env::DefaultEnvironment>();
+            let mut contract = EscrowSmartContract::new();
+            let partial = contract.initiate_escrow(accounts.bob, 100).unwrap();
+            ink::env::test::set_value_transferred::<ink::env::DefaultEnvironment>(40);
+            contract.deposit_assets(partial).unwrap();
+            contract.cancel_escrow(partial).unwrap();
+            let staked = contract.initiate_stake_escrow(accounts.bob, 100, 30).unwrap();
+            ink::env::test::set_caller::<ink::env::DefaultEnvironment>(accounts.bob);
+            ink::env::test::set_value_transferred::<ink::env::DefaultEnvironment>(30);
+            contract.deposit_stake(staked).unwrap();
+            ink::env::test::set_caller::<ink::env::DefaultEnvironment>(accounts.alice);
+            contract.cancel_escrow(staked).unwrap();
+
+            // Act & Assert
+            assert_eq!(contract.reopen_escrow(partial), Err(Error::InvalidState), "An installment moved funds");
+            assert_eq!(contract.reopen_escrow(staked), Err(Error::InvalidState), "A seller stake moved funds");
+        }
+
+        #[ink::test]
+        fn test_get_reputation() {
+            // Arrange: One completed, one canceled funded and one canceled unfunded escrow
+            let accounts = ink::env::test::default_accounts::<ink::env::DefaultEnvironment>();
+            let mut contract = EscrowSmartContract::new();
+            let amount = 100;
+            ink::env::test::set_value_transferred::<ink::env::DefaultEnvironment>(amount);
+            let completed = contract.initiate_escrow(accounts.bob, amount).unwrap();
+            let canceled = contract.initiate_escrow(accounts.bob, amount).unwrap();
+            let unfunded = contract.initiate_escrow(accounts.bob, amount).unwrap();
+            contract.deposit_assets(completed).unwrap();
+            contract.deposit_assets(canceled).unwrap();
+
+            // Act
+            contract.complete_escrow(completed).unwrap();
+            contract.cancel_escrow(canceled).unwrap();
+            contract.cancel_escrow(unfunded).unwrap();
+            ink::env::test::set_caller::<ink::env::DefaultEnvironment>(accounts.bob);
+            contract.complete_escrow(completed).unwrap();
+
+            // Assert: Only settlements of funded escrows count
+            assert_eq!(contract.get_reputation(accounts.bob), (1, 1), "Seller should have one of each");
+            assert_eq!(contract.get_reputation(accounts.alice), (0, 0), "Buyer should have no record");
+        }
+
+        #[ink::test]
+        fn test_settlement() {
+            // Arrange: Create and fund escrow
+            let accounts = ink::env::test::default_accounts::<ink::env::DefaultEnvironment>();
+            let mut contract = EscrowSmartContract::new();
+            let amount = 100;
+            ink::env::test::set_caller::<ink::env::DefaultEnvironment>(accounts.charlie);
+            let escrow_id = contract.initiate_escrow(accounts.bob, amount).unwrap();
+            ink::env::test::set_value_transferred::<ink::env::DefaultEnvironment>(amount);
+            contract.deposit_assets(escrow_id).unwrap();
+
+            // Act & Assert: Shares must add up and the proposer cannot accept
+            assert_eq!(
+                contract.propose_settlement(escrow_id, 30, 60),
+                Err(Error::ShareSumMismatch),
+                "Should reject shares that do not sum to the amount"
+            );
+            assert!(contract.propose_settlement(escrow_id, 30, 70).is_ok(), "Buyer should propose");
+            assert_eq!(
+                last_event::<SettlementProposed>().proposer,
+                accounts.charlie,
+                "Event should carry the proposer"
+            );
+            assert_eq!(
+                contract.accept_settlement(escrow_id),
+                Err(Error::Unauthorized),
+                "Proposer should not accept their own settlement"
+            );
+
+            // The seller accepts and both shares are paid out
+            let balance = |account| {
+                ink::env::test::get_account_balance::<ink::env::DefaultEnvironment>(account).unwrap()
+            };
+            let (charlie_balance, bob_balance) = (balance(accounts.charlie), balance(accounts.bob));
+            ink::env::test::set_caller::<ink::env::DefaultEnvironment>(accounts.bob);
+            assert!(contract.accept_settlement(escrow_id).is_ok(), "Seller should accept");
+            assert_eq!(balance(accounts.charlie), charlie_balance + 30, "Buyer should get their share");
+            assert_eq!(balance(accounts.bob), bob_balance + 70, "Seller should get their share");
+            assert_eq!(
+                contract.get_escrow_state(escrow_id),
+                Some(EscrowState::Completed),
+                "Settled escrow should be Completed"
+            );
+            assert_eq!(
+                contract.accept_settlement(escrow_id),
+                Err(Error::InvalidState),
+                "Settlement should only execute once"
+            );
+        }
+
+        #[ink::test]
+        fn test_get_total_locked() {
+            // Arrange: Two funded escrows
+            let accounts = ink::env::test::default_accounts::<ink::env::DefaultEnvironment>();
+            let mut contract = EscrowSmartContract::new();
+            ink::env::test::set_value_transferred::<ink::env::DefaultEnvironment>(100);
+            let first = contract.initiate_escrow(accounts.bob, 100).unwrap();
+            contract.deposit_assets(first).unwrap();
+            ink::env::test::set_value_transferred::<ink::env::DefaultEnvironment>(40);
+            let second = contract.initiate_escrow(accounts.bob, 40).unwrap();
+            contract.deposit_assets(second).unwrap();
+            let _unfunded = contract.initiate_escrow(accounts.bob, 10).unwrap();
+            assert_eq!(contract.get_total_locked(), 140, "Should sum funded escrows");
+
+            // Act: Cancel one escrow
+            contract.cancel_escrow(first).unwrap();
+
+            // Assert: The refunded amount is no longer locked
+            assert_eq!(contract.get_total_locked(), 40, "Refund should unlock the amount");
+            assert_eq!(
+                contract.get_contract_balance(),
+                ink::env::test::get_account_balance::<ink::env::DefaultEnvironment>(
+                    ink::env::test::callee::<ink::env::DefaultEnvironment>()
+                ).unwrap(),
+                "Should report the contract account's balance"
+            );
+        }
+
+        #[ink::test]
+        fn test_deadline_extension() {
+            // Arrange: Create and fund escrow with a release deadline
+            let accounts = ink::env::test::default_accounts::<ink::env::DefaultEnvironment>();
+            let mut contract = EscrowSmartContract::new();
+            let amount = 100;
+            let terms = EscrowTerms { release_deadline: Some(100), ..Default::default() };
+            let escrow_id = contract.initiate_escrow_with_terms(accounts.bob, amount, terms).unwrap();
+            ink::env::test::set_value_transferred::<ink::env::DefaultEnvironment>(amount);
+            contract.deposit_assets(escrow_id).unwrap();
+
+            // Act & Assert: The new deadline must be later and approved by the other party
+            assert_eq!(
+                contract.request_extension(escrow_id, 100),
+                Err(Error::InvalidState),
+                "Should reject a deadline that is not later"
+            );
+            assert!(contract.request_extension(escrow_id, 200).is_ok(), "Buyer should request");
+            assert_eq!(
+                contract.approve_extension(escrow_id),
+                Err(Error::Unauthorized),
+                "Requester should not approve their own extension"
+            );
+            ink::env::test::set_caller::<ink::env::DefaultEnvironment>(accounts.bob);
+            assert!(contract.approve_extension(escrow_id).is_ok(), "Seller should approve");
+            assert_eq!(last_event::<ExtensionApplied>().new_deadline, 200, "Event should carry the deadline");
+
+            // The escrow can no longer be swept at the old deadline
+            ink::env::test::set_block_timestamp::<ink::env::DefaultEnvironment>(150);
+            assert_eq!(
+                contract.sweep_expired(escrow_id),
+                Err(Error::DeadlineNotReached),
+                "Extended deadline should apply"
+            );
+            assert_eq!(contract.approve_extension(escrow_id), Err(Error::NotFound), "Request should be consumed");
+        }
+
+        #[ink::test]
+        fn test_seller_cancel_delay() {
+            // Arrange: Two funded escrows with a seller cancellation delay
+            let accounts = ink::env::test::default_accounts::<ink::env::DefaultEnvironment>();
+            let mut contract = EscrowSmartContract::new();
+            let amount = 100;
+            let terms = EscrowTerms { seller_cancel_delay: Some(50), ..Default::default() };
+            ink::env::test::set_block_timestamp::<ink::env::DefaultEnvironment>(10);
+            let by_seller = contract.initiate_escrow_with_terms(accounts.bob, amount, terms.clone()).unwrap();
+            let by_buyer = contract.initiate_escrow_with_terms(accounts.bob, amount, terms).unwrap();
+            ink::env::test::set_value_transferred::<ink::env::DefaultEnvironment>(amount);
+            contract.deposit_assets(by_seller).unwrap();
+            contract.deposit_assets(by_buyer).unwrap();
+
+            // Act & Assert: The buyer cancels immediately
+            assert!(contract.cancel_escrow(by_buyer).is_ok(), "Buyer cancellation should be immediate");
+
+            // The seller waits for the delay
+            ink::env::test::set_caller::<ink::env::DefaultEnvironment>(accounts.bob);
+            ink::env::test::set_block_timestamp::<ink::env::DefaultEnvironment>(59);
+            assert_eq!(
+                contract.cancel_escrow(by_seller),
+                Err(Error::DeadlineNotReached),
+                "Seller should not cancel within the delay"
+            );
+            ink::env::test::set_block_timestamp::<ink::env::DefaultEnvironment>(60);
+            assert!(contract.cancel_escrow(by_seller).is_ok(), "Seller should cancel after the delay");
+        }
+
+        #[ink::test]
+        fn test_dispute_default_arbiter() {
+            // Arrange: Deploy with an arbiter and fund two escrows
+            let accounts = ink::env::test::default_accounts::<ink::env::DefaultEnvironment>();
+            assert_eq!(
+                EscrowSmartContract::new_with_arbiter(AccountId::from([0u8; 32])).err(),
+                Some(Error::ZeroAddress),
+                "Should reject the zero account as arbiter"
+            );
+            let mut contract = EscrowSmartContract::new_with_arbiter(accounts.eve).unwrap();
+            let amount = 100;
+            assert_eq!(contract.get_arbiter(), Some(accounts.eve), "Should expose the arbiter");
+            assert_eq!(
+                contract.initiate_escrow(accounts.eve, amount),
+                Err(Error::InvalidParticipants),
+                "Arbiter should not be a party"
+            );
+            ink::env::test::set_value_transferred::<ink::env::DefaultEnvironment>(amount);
+            let released = contract.initiate_escrow(accounts.bob, amount).unwrap();
+            let split = contract.initiate_escrow(accounts.bob, amount).unwrap();
+            contract.deposit_assets(released).unwrap();
+            contract.deposit_assets(split).unwrap();
+            contract.set_refund_address(split, accounts.django).unwrap();
+            assert_eq!(
+                contract.get_participants(released).unwrap().arbiter,
+                Some(accounts.eve),
+                "Escrows should fall back to the default arbiter"
+            );
+
+            // Act & Assert: Disputed escrows wait for the arbiter
+            assert!(contract.raise_dispute(released).is_ok(), "Buyer should raise a dispute");
+            assert!(contract.raise_dispute(split).is_ok(), "Buyer should raise a dispute");
+            assert_eq!(contract.cancel_escrow(released), Err(Error::InvalidState), "Disputed escrow cannot be canceled");
+            assert_eq!(
+                contract.resolve_dispute(released, 0, amount),
+                Err(Error::Unauthorized),
+                "Only the arbiter should resolve"
+            );
+
+            let balance = |account| {
+                ink::env::test::get_account_balance::<ink::env::DefaultEnvironment>(account).unwrap()
+            };
+            let (bob_balance, django_balance) = (balance(accounts.bob), balance(accounts.django));
+            ink::env::test::set_caller::<ink::env::DefaultEnvironment>(accounts.eve);
+            assert_eq!(
+                contract.resolve_dispute(split, 40, 50),
+                Err(Error::InvalidAmount),
+                "Amounts should sum to the escrow amount"
+            );
+            assert!(contract.resolve_dispute(released, 0, amount).is_ok(), "Arbiter should release");
+            assert!(contract.resolve_dispute(split, 40, 60).is_ok(), "Arbiter should split");
+            let resolved = last_event::<DisputeResolved>();
+            assert_eq!(
+                (resolved.buyer_amount, resolved.seller_amount),
+                (EventAmount::Clear(40), EventAmount::Clear(60)),
+                "Event should carry both amounts"
+            );
+            assert_eq!(balance(accounts.bob), bob_balance + amount + 60, "Seller should receive their parts");
+            assert_eq!(balance(accounts.django), django_balance + 40, "Buyer should be partly refunded");
+            assert_eq!(contract.get_escrow_state(released), Some(EscrowState::Completed), "Released escrow completes");
+            assert_eq!(contract.get_escrow_state(split), Some(EscrowState::Completed), "Split escrow completes");
+            assert_eq!(contract.get_total_locked(), 0, "Nothing should remain locked");
+        }
+
+        #[ink::test]
+        fn test_claim_refund() {
+            // Arrange: A canceled escrow whose refund is owed to django. The off-chain
+            // environment cannot make a transfer fail, so the owed refund is recorded directly.
+            let accounts = ink::env::test::default_accounts::<ink::env::DefaultEnvironment>();
+            let mut contract = EscrowSmartContract::new();
+            let amount = 100;
+            let escrow_id = contract.initiate_escrow(accounts.bob, amount).unwrap();
+            ink::env::test::set_value_transferred::<ink::env::DefaultEnvironment>(amount);
+            contract.deposit_assets(escrow_id).unwrap();
+            contract.cancel_escrow(escrow_id).unwrap();
+            assert_eq!(contract.claim_refund(), Err(Error::NotFound), "Transferred refund should not be owed");
+            contract.pending_refunds.insert(accounts.django, &amount);
+            contract.total_locked += amount;
+            contract.total_pending_refunds += amount;
+            assert_eq!(contract.pending_withdrawal_of(accounts.django), amount, "Owed refund should be visible");
+
+            // Act
+            ink::env::test::set_caller::<ink::env::DefaultEnvironment>(accounts.django);
+            let django_balance = ink::env::test::get_account_balance::<ink::env::DefaultEnvironment>(
+                accounts.django
+            ).unwrap();
+            let claimed = contract.claim_refund();
+
+            // Assert: The owed refund is transferred once
+            assert_eq!(claimed, Ok(amount), "Recipient should claim the refund");
+            assert_eq!(
+                ink::env::test::get_account_balance::<ink::env::DefaultEnvironment>(
+                    accounts.django
+                ).unwrap(),
+                django_balance + amount,
+                "Claim should transfer the refund"
+            );
+            assert_eq!(contract.get_total_locked(), 0, "Claimed refund should be unlocked");
+            assert_eq!(contract.pending_withdrawal_of(accounts.django), 0, "Nothing should remain owed");
+            assert_eq!(contract.claim_refund(), Err(Error::NotFound), "Refund should be claimed only once");
+        }
+
+        #[ink::test]
+        fn test_state_index_spans_words() {
+            // Arrange: Escrows on both sides of a word boundary, indexed out of order
+            let mut contract = EscrowSmartContract::new();
+            contract.next_id = 300;
+            for escrow_id in [299, 5, 130, 127, 128] {
+                contract.index_state(escrow_id, EscrowState::Created).unwrap();
+            }
+            contract.unindex_state(127, EscrowState::Created);
+
+            // Act & Assert
+            assert_eq!(contract.state_page(EscrowState::Created, 0, 10), vec![5, 128, 130, 299], "Should order by ID");
+            assert_eq!(contract.state_page(EscrowState::Created, 129, 1), vec![130], "Should start mid-word");
+            assert_eq!(contract.count_in_state_from(EscrowState::Created, 128), 3, "Should count from an ID");
+            assert_eq!(contract.state_counts.get(EscrowState::Created), Some(4), "Should count each escrow once");
+            assert!(contract.state_page(EscrowState::Funded, 0, 10).is_empty(), "Other states are untouched");
+        }
+
+        #[ink::test]
+        fn test_get_escrows_by_state() {
+            // Arrange: Escrows in every terminal and non-terminal state
+            let accounts = ink::env::test::default_accounts::<ink::env::DefaultEnvironment>();
+            let mut contract = EscrowSmartContract::new();
+            let amount = 100;
+            ink::env::test::set_value_transferred::<ink::env::DefaultEnvironment>(amount);
+            let created = contract.initiate_escrow(accounts.bob, amount).unwrap();
+            let funded = contract.initiate_escrow(accounts.bob, amount).unwrap();
+            let canceled = contract.initiate_escrow(accounts.bob, amount).unwrap();
+            contract.deposit_assets(funded).unwrap();
+            contract.deposit_assets(canceled).unwrap();
+            contract.cancel_escrow(canceled).unwrap();
+
+            // Act & Assert: Each state lists its own escrows
+            let ids = |contract: &EscrowSmartContract, state| {
+                contract
+                    .get_escrows_by_state(state, 0, 10)
+                    .into_iter()
+                    .map(|(escrow_id, _)| escrow_id)
+                    .collect::<Vec<_>>()
+            };
+            assert_eq!(ids(&contract, EscrowState::Created), vec![created], "Should list created escrows");
+            assert_eq!(ids(&contract, EscrowState::Funded), vec![funded], "Should list funded escrows");
+            assert_eq!(ids(&contract, EscrowState::Canceled), vec![canceled], "Should list canceled escrows");
+            assert!(ids(&contract, EscrowState::Completed).is_empty(), "No escrow is completed");
+            assert_eq!(
+                contract.get_escrows_by_state(EscrowState::Funded, 0, 10)[0].1.state,
+                EscrowState::Funded,
+                "Should return the escrow data"
+            );
+
+            // Purged escrows leave the index
+            contract.purge_batch(vec![canceled]).unwrap();
+            assert!(ids(&contract, EscrowState::Canceled).is_empty(), "Purged escrow should be unlisted");
+        }
+
+        #[ink::test]
+        fn test_fee_override() {
+            // Arrange: Deploy as django with a 10% fee and a 5% fee for alice
+            let accounts = ink::env::test::default_accounts::<ink::env::DefaultEnvironment>();
+            ink::env::test::set_caller::<ink::env::DefaultEnvironment>(accounts.django);
+            let mut contract = EscrowSmartContract::new();
+            assert_eq!(contract.set_fee(10_001), Err(Error::InvalidFee), "Fee should be at most 10_000");
+            contract.set_fee(1_000).unwrap();
+            assert_eq!(
+                contract.set_fee_override(accounts.alice, 10_001),
+                Err(Error::InvalidFee),
+                "Override should be at most 10_000"
+            );
+            assert!(contract.set_fee_override(accounts.alice, 500).is_ok(), "Owner should set overrides");
+            assert_eq!(last_event::<FeeOverrideSet>().fee_bps, Some(500), "Event should carry the fee");
+            ink::env::test::set_caller::<ink::env::DefaultEnvironment>(accounts.alice);
+            assert_eq!(contract.set_fee_override(accounts.alice, 0), Err(Error::Unauthorized), "Only the owner");
+
+            // Act: Complete one escrow for alice and one for charlie
+            let amount = 100;
+            ink::env::test::set_value_transferred::<ink::env::DefaultEnvironment>(amount);
+            let discounted = contract.initiate_escrow(accounts.bob, amount).unwrap();
+            contract.deposit_assets(discounted).unwrap();
+            ink::env::test::set_caller::<ink::env::DefaultEnvironment>(accounts.charlie);
+            let regular = contract.initiate_escrow(accounts.bob, amount).unwrap();
+            contract.deposit_assets(regular).unwrap();
+            let balance = |account| {
+                ink::env::test::get_account_balance::<ink::env::DefaultEnvironment>(account).unwrap()
+            };
+            let (bob_balance, django_balance) = (balance(accounts.bob), balance(accounts.django));
+            ink::env::test::set_caller::<ink::env::DefaultEnvironment>(accounts.bob);
+            contract.complete_escrow(discounted).unwrap();
+            contract.complete_escrow(regular).unwrap();
+            ink::env::test::set_caller::<ink::env::DefaultEnvironment>(accounts.alice);
+            contract.complete_escrow(discounted).unwrap();
+            ink::env::test::set_caller::<ink::env::DefaultEnvironment>(accounts.charlie);
+            contract.complete_escrow(regular).unwrap();
+
+            // Assert: The override applies to alice's escrow only
+            assert_eq!(balance(accounts.bob), bob_balance + 95 + 90, "Seller should receive amounts minus fees");
+            assert_eq!(balance(accounts.django), django_balance + 5 + 10, "Owner should collect the fees");
+
+            // The sentinel removes the override
+            ink::env::test::set_caller::<ink::env::DefaultEnvironment>(accounts.django);
+            assert!(
+                contract.set_fee_override(accounts.alice, FEE_OVERRIDE_REMOVED).is_ok(),
+                "Owner should remove overrides"
+            );
+            assert_eq!(last_event::<FeeOverrideSet>().fee_bps, None, "Event should report the removal");
+            assert_eq!(contract.fee_overrides.get(accounts.alice), None, "Override should be removed");
+        }
+
+        #[ink::test]
+        fn test_can_complete() {
+            // Arrange: Create escrow
+            let accounts = ink::env::test::default_accounts::<ink::env::DefaultEnvironment>();
+            let mut contract = EscrowSmartContract::new();
+            let amount = 100;
+            let escrow_id = contract.initiate_escrow(accounts.bob, amount).unwrap();
+
+            // Act & Assert: Only funded escrows the caller has not approved can be completed
+            assert_eq!(contract.can_complete(escrow_id, accounts.alice), Ok(false), "Unfunded escrow");
+            ink::env::test::set_value_transferred::<ink::env::DefaultEnvironment>(amount);
+            contract.deposit_assets(escrow_id).unwrap();
+            assert_eq!(contract.can_complete(escrow_id, accounts.alice), Ok(true), "Buyer can approve");
+            contract.complete_escrow(escrow_id).unwrap();
+            assert_eq!(contract.can_complete(escrow_id, accounts.alice), Ok(false), "Buyer already approved");
+            assert_eq!(contract.can_complete(escrow_id, accounts.bob), Ok(true), "Seller can approve");
+            assert_eq!(
+                contract.can_complete(escrow_id, accounts.charlie),
+                Err(Error::Unauthorized),
+                "Should reject non-participants"
+            );
+            assert_eq!(contract.can_complete(99, accounts.alice), Err(Error::NotFound), "Should reject unknown IDs");
+
+            // A stale approval can be given again
+            let terms = EscrowTerms { approval_ttl: Some(50), ..Default::default() };
+            let expiring = contract.initiate_escrow_with_terms(accounts.bob, amount, terms).unwrap();
+            contract.deposit_assets(expiring).unwrap();
+            contract.complete_escrow(expiring).unwrap();
+            assert_eq!(contract.can_complete(expiring, accounts.alice), Ok(false), "Fresh approval");
+            ink::env::test::set_block_timestamp::<ink::env::DefaultEnvironment>(51);
+            assert_eq!(contract.can_complete(expiring, accounts.alice), Ok(true), "Stale approval counts as missing");
+        }
+
+        #[ink::test]
+        fn test_withdrawable_at() {
+            // Arrange: A 50 ms refund lock, an auto-releasing escrow, a staked one and a disputed one
+            let accounts = ink::env::test::default_accounts::<ink::env::DefaultEnvironment>();
+            let mut contract = EscrowSmartContract::new_with_arbiter(accounts.eve).unwrap();
+            contract.set_refund_lock_period(50).unwrap();
+            let release_terms = EscrowTerms { auto_release_period: Some(100), ..Default::default() };
+            let released = contract.initiate_escrow_with_terms(accounts.bob, 100, release_terms).unwrap();
+            let staked = contract.initiate_stake_escrow(accounts.bob, 100, 30).unwrap();
+            let dispute_terms = EscrowTerms { dispute_timeout: Some(30), ..Default::default() };
+            let disputed = contract.initiate_escrow_with_terms(accounts.bob, 100, dispute_terms).unwrap();
+            assert_eq!(contract.withdrawable_at(released, accounts.alice), None, "Nothing deposited yet");
+            ink::env::test::set_value_transferred::<ink::env::DefaultEnvironment>(100);
+            contract.deposit_assets(released).unwrap();
+            contract.deposit_assets(disputed).unwrap();
+            ink::env::test::set_block_timestamp::<ink::env::DefaultEnvironment>(10);
+            contract.deposit_assets(staked).unwrap();
+            assert_eq!(contract.withdrawable_at(staked, accounts.alice), Some(10), "Installments can be taken back at once");
+
+            // Act & Assert: The buyer waits for the refund lock, the seller for an approval
+            assert_eq!(contract.withdrawable_at(released, accounts.alice), Some(50), "Buyer waits for the refund lock");
+            assert_eq!(contract.withdrawable_at(released, accounts.bob), None, "Seller waits for the buyer's approval");
+            assert_eq!(contract.withdrawable_at(released, accounts.charlie), None, "Observers withdraw nothing");
+            ink::env::test::set_block_timestamp::<ink::env::DefaultEnvironment>(60);
+            assert_eq!(contract.withdrawable_at(released, accounts.alice), Some(60), "Buyer can cancel now");
+            contract.complete_escrow(released).unwrap();
+            assert_eq!(contract.withdrawable_at(released, accounts.bob), Some(160), "Seller is paid after the auto-release period");
+
+            // The seller gets its stake back by canceling
+            ink::env::test::set_caller::<ink::env::DefaultEnvironment>(accounts.bob);
+            ink::env::test::set_value_transferred::<ink::env::DefaultEnvironment>(30);
+            contract.deposit_stake(staked).unwrap();
+            assert_eq!(contract.withdrawable_at(staked, accounts.bob), Some(60), "Seller can cancel at once");
+
+            // The buyer reclaims a disputed escrow after the dispute timeout
+            contract.raise_dispute(disputed).unwrap();
+            assert_eq!(contract.withdrawable_at(disputed, accounts.alice), Some(90), "Buyer waits for the dispute timeout");
+            assert_eq!(contract.withdrawable_at(disputed, accounts.bob), None, "Seller waits for the ruling");
+            assert_eq!(contract.withdrawable_at(99, accounts.alice), None, "Unknown escrows");
+        }
+
+        #[ink::test]
+        fn test_templates() {
+            // Arrange: Alice creates a template
+            let accounts = ink::env::test::default_accounts::<ink::env::DefaultEnvironment>();
+            let mut contract = EscrowSmartContract::new();
+            let amount = 100;
+            let template_id = contract.create_template(accounts.bob, amount).unwrap();
+
+            // Act: Initiate two escrows from the template
+            let first = contract.initiate_from_template(template_id).unwrap();
+            let second = contract.initiate_from_template(template_id).unwrap();
+
+            // Assert: Each escrow uses the template's seller and amount
+            assert_ne!(first, second, "Each escrow should get a new ID");
+            let escrow = contract.get_escrow(second).unwrap();
+            assert_eq!(
+                (escrow.buyer, escrow.seller, escrow.amount),
+                (accounts.alice, accounts.bob, amount),
+                "Escrow should follow the template"
+            );
+
+            // Only the owner uses or deletes the template
+            ink::env::test::set_caller::<ink::env::DefaultEnvironment>(accounts.charlie);
+            assert_eq!(contract.initiate_from_template(template_id), Err(Error::Unauthorized), "Not the owner");
+            assert_eq!(contract.delete_template(template_id), Err(Error::Unauthorized), "Not the owner");
+            ink::env::test::set_caller::<ink::env::DefaultEnvironment>(accounts.alice);
+            assert!(contract.delete_template(template_id).is_ok(), "Owner should delete the template");
+            assert_eq!(
+                contract.initiate_from_template(template_id),
+                Err(Error::TemplateNotFound),
+                "Deleted template should be gone"
+            );
+        }
+
+        #[ink::test]
+        fn test_discard_escrow() {
+            // Arrange: An unfunded and a funded escrow
+            let accounts = ink::env::test::default_accounts::<ink::env::DefaultEnvironment>();
+            let mut contract = EscrowSmartContract::new();
+            let amount = 100;
+            let unfunded = contract.initiate_escrow(accounts.bob, amount).unwrap();
+            let funded = contract.initiate_escrow(accounts.bob, amount).unwrap();
+            ink::env::test::set_value_transferred::<ink::env::DefaultEnvironment>(amount);
+            contract.deposit_assets(funded).unwrap();
+
+            // Act & Assert: Only the buyer discards, and only unfunded escrows
+            assert_eq!(contract.discard_escrow(funded), Err(Error::InvalidState), "Funded escrow stays");
+            ink::env::test::set_caller::<ink::env::DefaultEnvironment>(accounts.bob);
+            assert_eq!(contract.discard_escrow(unfunded), Err(Error::NotBuyer), "Seller cannot discard");
+            ink::env::test::set_caller::<ink::env::DefaultEnvironment>(accounts.alice);
+            assert!(contract.discard_escrow(unfunded).is_ok(), "Buyer should discard");
+            assert_eq!(last_event::<Discarded>().escrow_id, unfunded, "Event should carry the escrow ID");
+            assert_eq!(contract.get_escrow(unfunded), None, "Discarded escrow should be gone");
+            assert!(
+                contract.get_escrows_by_state(EscrowState::Created, 0, 10).is_empty(),
+                "Discarded escrow should be unlisted"
+            );
+        }
+
+        #[ink::test]
+        fn test_resolve_dispute_arbiter_fee() {
+            // Arrange: A disputed escrow with a 10% arbiter fee
+            let accounts = ink::env::test::default_accounts::<ink::env::DefaultEnvironment>();
+            let mut contract = EscrowSmartContract::new_with_arbiter(accounts.eve).unwrap();
+            let amount = 100;
+            assert_eq!(
+                contract.initiate_escrow_with_terms(
+                    accounts.bob,
+                    amount,
+                    EscrowTerms { arbiter_fee_bps: 10_001, ..Default::default() }
+                ),
+                Err(Error::InvalidFee),
+                "Should reject arbiter fees above 10_000"
+            );
+            let terms = EscrowTerms { arbiter_fee_bps: 1_000, ..Default::default() };
+            let escrow_id = contract.initiate_escrow_with_terms(accounts.bob, amount, terms).unwrap();
+            ink::env::test::set_value_transferred::<ink::env::DefaultEnvironment>(amount);
+            contract.deposit_assets(escrow_id).unwrap();
+            contract.set_refund_address(escrow_id, accounts.django).unwrap();
+            contract.raise_dispute(escrow_id).unwrap();
+
+            // Act: The arbiter splits the remainder after its fee
+            let balance = |account| {
+                ink::env::test::get_account_balance::<ink::env::DefaultEnvironment>(account).unwrap()
+            };
+            let (bob, django, eve) = (balance(accounts.bob), balance(accounts.django), balance(accounts.eve));
+            ink::env::test::set_caller::<ink::env::DefaultEnvironment>(accounts.eve);
+            assert_eq!(
+                contract.resolve_dispute(escrow_id, 40, 60),
+                Err(Error::InvalidAmount),
+                "Amounts should leave room for the arbiter fee"
+            );
+            assert!(contract.resolve_dispute(escrow_id, 30, 60).is_ok(), "Arbiter should resolve");
+
+            // Assert: The fee goes to the arbiter and is reported
+            assert_eq!(balance(accounts.eve), eve + 10, "Arbiter should receive the fee");
+            assert_eq!(balance(accounts.bob), bob + 60, "Seller should receive their part");
+            assert_eq!(balance(accounts.django), django + 30, "Buyer should receive their part");
+            assert_eq!(last_event::<DisputeResolved>().arbiter_fee, EventAmount::Clear(10), "Event should carry the fee");
+        }
+
+        #[ink::test]
+        fn test_dispute_deposits() {
+            // Arrange: Two funded escrows requiring a dispute deposit of 20, one of them timing out
+            let accounts = ink::env::test::default_accounts::<ink::env::DefaultEnvironment>();
+            let mut contract = EscrowSmartContract::new_with_arbiter(accounts.eve).unwrap();
+            ink::env::test::set_caller::<ink::env::DefaultEnvironment>(accounts.charlie);
+            let terms = EscrowTerms { dispute_deposit: 20, dispute_timeout: Some(10), ..Default::default() };
+            let ruled = contract.initiate_escrow_with_terms(accounts.bob, 100, terms.clone()).unwrap();
+            let timed_out = contract.initiate_escrow_with_terms(accounts.bob, 100, terms).unwrap();
+            ink::env::test::set_value_transferred::<ink::env::DefaultEnvironment>(100);
+            contract.deposit_assets(ruled).unwrap();
+            contract.deposit_assets(timed_out).unwrap();
+            ink::env::test::set_value_transferred::<ink::env::DefaultEnvironment>(20);
+            assert_eq!(contract.post_dispute_deposit(ruled), Err(Error::InvalidState), "Only disputed escrows take deposits");
+
+            // Act: Both parties post their deposits into the disputed escrows
+            contract.raise_dispute(ruled).unwrap();
+            contract.raise_dispute(timed_out).unwrap();
+            ink::env::test::set_value_transferred::<ink::env::DefaultEnvironment>(10);
+            assert_eq!(contract.post_dispute_deposit(ruled), Err(Error::InvalidAmount), "Deposit should be exact");
+            ink::env::test::set_value_transferred::<ink::env::DefaultEnvironment>(20);
+            contract.post_dispute_deposit(ruled).unwrap();
+            assert_eq!(last_event::<DisputeDepositPosted>().party, accounts.charlie, "Event should carry the party");
+            assert_eq!(contract.post_dispute_deposit(ruled), Err(Error::InvalidState), "Deposit is posted once");
+            contract.post_dispute_deposit(timed_out).unwrap();
+            ink::env::test::set_caller::<ink::env::DefaultEnvironment>(accounts.eve);
+            assert_eq!(
+                contract.resolve_dispute(ruled, 100, 0),
+                Err(Error::DisputeDepositMissing),
+                "Arbiter should wait for both deposits"
+            );
+            ink::env::test::set_caller::<ink::env::DefaultEnvironment>(accounts.bob);
+            contract.post_dispute_deposit(ruled).unwrap();
+            contract.post_dispute_deposit(timed_out).unwrap();
+            assert_eq!(
+                contract.get_liabilities(),
+                Liabilities { locked_principal: 200, posted_bonds: 80, pending_withdrawals: 0 },
+                "Deposits should count as bonds"
+            );
+
+            // Assert: The ruling refunds the winner's deposit and forfeits the loser's to the arbiter
+            let balance = |account| {
+                ink::env::test::get_account_balance::<ink::env::DefaultEnvironment>(account).unwrap()
+            };
+            let (bob, charlie, eve) = (balance(accounts.bob), balance(accounts.charlie), balance(accounts.eve));
+            ink::env::test::set_caller::<ink::env::DefaultEnvironment>(accounts.eve);
+            contract.resolve_dispute(ruled, 100, 0).unwrap();
+            assert_eq!(balance(accounts.charlie), charlie + 100 + 20, "Winner should get its deposit back");
+            assert_eq!(balance(accounts.bob), bob, "Loser should forfeit its deposit");
+            assert_eq!(balance(accounts.eve), eve + 20, "Arbiter should receive the forfeited deposit");
+
+            // A dispute timeout returns both deposits
+            ink::env::test::set_block_timestamp::<ink::env::DefaultEnvironment>(10);
+            ink::env::test::set_caller::<ink::env::DefaultEnvironment>(accounts.charlie);
+            contract.claim_dispute_timeout(timed_out).unwrap();
+            assert_eq!(balance(accounts.charlie), charlie + 240, "Buyer should get its refund and deposit back");
+            assert_eq!(balance(accounts.bob), bob + 20, "Seller should get its deposit back");
+            assert_eq!(contract.get_total_locked(), 0, "Nothing should remain locked");
+        }
+
+        #[ink::test]
+        fn test_numeric_codes_round_trip() {
+            // Every known code converts to a variant and back, and codes are contiguous.
+            let states: Vec<u8> = (0..=u8::MAX).filter(|code| EscrowState::try_from(*code).is_ok()).collect();
+            for code in states.iter() {
+                assert_eq!(u8::from(EscrowState::try_from(*code).unwrap()), *code, "State code should round trip");
+            }
+            assert_eq!(states, (0..=5).collect::<Vec<u8>>(), "Every state should have a code");
+            assert_eq!(EscrowState::try_from(6), Err(6), "Unknown state codes should be returned");
+
+            let errors: Vec<u8> = (0..=u8::MAX).filter(|code| Error::try_from(*code).is_ok()).collect();
+            for code in errors.iter() {
+                assert_eq!(u8::from(Error::try_from(*code).unwrap()), *code, "Error code should round trip");
+            }
+            let last = errors.len() as u8;
+            assert_eq!(errors, (0..last).collect::<Vec<u8>>(), "Error codes should be contiguous");
+            assert_eq!(u8::from(Error::TemplateNotFound), 20, "Codes should match the discriminants");
+            assert_eq!(Error::try_from(last), Err(last), "Unknown error codes should be returned");
+        }
+
+        #[ink::test]
+        fn test_deposit_deadline() {
+            // Arrange: Two escrows with a deposit deadline
+            let accounts = ink::env::test::default_accounts::<ink::env::DefaultEnvironment>();
+            let mut contract = EscrowSmartContract::new();
+            let amount = 100;
+            let terms = EscrowTerms { deposit_deadline: Some(100), ..Default::default() };
+            let on_time = contract.initiate_escrow_with_terms(accounts.bob, amount, terms.clone()).unwrap();
+            let late = contract.initiate_escrow_with_terms(accounts.bob, amount, terms).unwrap();
+            ink::env::test::set_value_transferred::<ink::env::DefaultEnvironment>(amount);
+
+            // Act & Assert: Deposits are accepted up to the deadline
+            ink::env::test::set_block_timestamp::<ink::env::DefaultEnvironment>(100);
+            assert!(contract.deposit_assets(on_time).is_ok(), "Deposit at the deadline should succeed");
+            ink::env::test::set_block_timestamp::<ink::env::DefaultEnvironment>(101);
+            assert_eq!(
+                contract.deposit_assets(late),
+                Err(Error::DeadlineExceeded),
+                "Deposit past the deadline should be rejected"
+            );
+        }
+
+        #[ink::test]
+        fn test_deposit_assets_or_return_checks_state_first() {
+            // Arrange: an escrow with a deposit deadline of 1_000
+            let accounts = ink::env::test::default_accounts::<ink::env::DefaultEnvironment>();
+            let mut contract = EscrowSmartContract::new();
+            let terms = EscrowTerms { deposit_deadline: Some(1_000), ..Default::default() };
+            let escrow_id = contract.initiate_escrow_with_terms(accounts.bob, 100, terms).unwrap();
+
+            // Act & Assert: a mismatching payment fails like a matching one
+            ink::env::test::set_block_timestamp::<ink::env::DefaultEnvironment>(1_001);
+            for sent in [99, 100] {
+                ink::env::test::set_value_transferred::<ink::env::DefaultEnvironment>(sent);
+                assert_eq!(contract.deposit_assets_or_return(escrow_id), Err(Error::DeadlineExceeded), "Deadline has passed");
+            }
+            assert_eq!(contract.escrows.get(escrow_id).unwrap().deposit_mismatches, 0, "Nothing should be counted");
+            contract.shutdown().unwrap();
+            for sent in [99, 100] {
+                ink::env::test::set_value_transferred::<ink::env::DefaultEnvironment>(sent);
+                assert_eq!(contract.deposit_assets_or_return(escrow_id), Err(Error::ShutDown), "Contract is shut down");
+            }
+        }
+
+        #[ink::test]
+        fn test_escrows_by_account() {
+            // Arrange: Alice buys from bob twice, and charlie buys from alice once
+            let accounts = ink::env::test::default_accounts::<ink::env::DefaultEnvironment>();
+            let mut contract = EscrowSmartContract::new();
+            let amount = 100;
+            let first = contract.initiate_escrow(accounts.bob, amount).unwrap();
+            let second = contract.initiate_escrow(accounts.bob, amount).unwrap();
+            ink::env::test::set_caller::<ink::env::DefaultEnvironment>(accounts.charlie);
+            let third = contract.initiate_escrow(accounts.alice, amount).unwrap();
+
+            // Act & Assert: Counts and lists follow each role
+            assert_eq!(contract.count_escrows_by_buyer(accounts.alice), 2, "Alice bought twice");
+            assert_eq!(contract.count_escrows_by_seller(accounts.alice), 1, "Alice sold once");
+            assert_eq!(contract.count_escrows_by_seller(accounts.charlie), 0, "Charlie never sold");
+            assert_eq!(
+                contract.get_escrows_by_buyer(accounts.alice, 0, 10),
+                vec![first, second],
+                "Should list the buyer's escrows in creation order"
+            );
+            assert_eq!(contract.get_escrows_by_buyer(accounts.alice, 1, 10), vec![second], "Should paginate");
+            assert_eq!(contract.get_escrows_by_seller(accounts.alice, 0, 10), vec![third], "Should list sales");
+        }
+
+        #[ink::test]
+        fn test_my_role() {
+            // Arrange: Create escrow on a contract with an arbiter
+            let accounts = ink::env::test::default_accounts::<ink::env::DefaultEnvironment>();
+            let mut contract = EscrowSmartContract::new_with_arbiter(accounts.eve).unwrap();
+            let escrow_id = contract.initiate_escrow(accounts.bob, 100).unwrap();
+
+            // Act & Assert: Each caller gets their role
+            let role = |contract: &EscrowSmartContract, caller| {
+                ink::env::test::set_caller::<ink::env::DefaultEnvironment>(caller);
+                contract.my_role(escrow_id)
+            };
+            assert_eq!(role(&contract, accounts.alice), Ok(Role::Buyer), "Alice is the buyer");
+            assert_eq!(role(&contract, accounts.bob), Ok(Role::Seller), "Bob is the seller");
+            assert_eq!(role(&contract, accounts.eve), Ok(Role::Arbiter), "Eve is the arbiter");
+            assert_eq!(role(&contract, accounts.charlie), Ok(Role::Observer), "Charlie is an observer");
+            assert_eq!(contract.my_role(99), Err(Error::NotFound), "Should reject unknown IDs");
+        }
+
+        #[ink::test]
+        fn test_max_open_per_buyer() {
+            // Arrange: Limit buyers to two open escrows
+            let accounts = ink::env::test::default_accounts::<ink::env::DefaultEnvironment>();
+            let mut contract = EscrowSmartContract::new();
+            let amount = 100;
+            ink::env::test::set_caller::<ink::env::DefaultEnvironment>(accounts.bob);
+            assert_eq!(contract.set_max_open_per_buyer(2), Err(Error::Unauthorized), "Only the owner");
+            ink::env::test::set_caller::<ink::env::DefaultEnvironment>(accounts.alice);
+            contract.set_max_open_per_buyer(2).unwrap();
+            let first = contract.initiate_escrow(accounts.bob, amount).unwrap();
+            let _second = contract.initiate_escrow(accounts.bob, amount).unwrap();
+
+            // Act & Assert: A third open escrow is rejected until one settles
+            assert_eq!(
+                contract.initiate_escrow(accounts.bob, amount),
+                Err(Error::TooManyOpenEscrows),
+                "Should reject beyond the limit"
+            );
+            contract.cancel_escrow(first).unwrap();
+            let third = contract.initiate_escrow(accounts.bob, amount);
+            assert!(third.is_ok(), "Settling should free a slot");
+            assert_eq!(contract.reopen_escrow(first), Err(Error::TooManyOpenEscrows), "Reopening counts too");
+
+            // A limit of zero means unlimited
+            contract.set_max_open_per_buyer(0).unwrap();
+            assert!(contract.initiate_escrow(accounts.bob, amount).is_ok(), "Zero should lift the limit");
+        }
+
+        #[ink::test]
+        fn test_invoices_count_as_open_once_funded() {
+            // Arrange: Limit buyers to one open escrow, and have the seller invoice the buyer twice
+            let accounts = ink::env::test::default_accounts::<ink::env::DefaultEnvironment>();
+            let mut contract = EscrowSmartContract::new();
+            let amount = 100;
+            contract.set_max_open_per_buyer(1).unwrap();
+            ink::env::test::set_caller::<ink::env::DefaultEnvironment>(accounts.bob);
+            let invoice = contract.request_payment(accounts.alice, amount).unwrap();
+            let declined = contract.request_payment(accounts.alice, amount).unwrap();
+
+            // Act & Assert: Invoices do not use up the buyer's limit
+            ink::env::test::set_caller::<ink::env::DefaultEnvironment>(accounts.alice);
+            let own = contract.initiate_escrow(accounts.bob, amount).unwrap();
+            assert_eq!(contract.open_counts.get(accounts.alice), Some(1), "Only the buyer's own escrow counts");
+
+            // Funding an invoice counts it, within the limit
+            ink::env::test::set_value_transferred::<ink::env::DefaultEnvironment>(amount);
+            assert_eq!(contract.deposit_assets(invoice), Err(Error::TooManyOpenEscrows), "Funding counts against the limit");
+            contract.cancel_escrow(own).unwrap();
+            assert!(contract.deposit_assets(invoice).is_ok(), "Funding should use the freed slot");
+            assert_eq!(contract.open_counts.get(accounts.alice), Some(1), "The funded invoice counts");
+
+            // Settling an uncounted invoice leaves the count alone
+            contract.cancel_escrow(declined).unwrap();
+            assert_eq!(contract.open_counts.get(accounts.alice), Some(1), "Declined invoice was never counted");
+        }
+
+        #[ink::test]
+        fn test_revoke_approval() {
+            // Arrange: Create and fund escrow, buyer approves
+            let accounts = ink::env::test::default_accounts::<ink::env::DefaultEnvironment>();
+            let mut contract = EscrowSmartContract::new();
+            let amount = 100;
+            let escrow_id = contract.initiate_escrow(accounts.bob, amount).unwrap();
+            ink::env::test::set_value_transferred::<ink::env::DefaultEnvironment>(amount);
+            contract.deposit_assets(escrow_id).unwrap();
+            contract.complete_escrow(escrow_id).unwrap();
+
+            // Act & Assert: Only an existing approval can be revoked
+            assert!(contract.revoke_approval(escrow_id).is_ok(), "Buyer should revoke");
+            assert_eq!(last_event::<ApprovalRevoked>().party, accounts.alice, "Event should name the party");
+            assert_eq!(contract.has_approved(escrow_id, accounts.alice), Ok(false), "Approval should be cleared");
+            assert_eq!(contract.revoke_approval(escrow_id), Err(Error::InvalidState), "Nothing left to revoke");
+
+            // The seller's approval alone no longer completes the escrow
+            ink::env::test::set_caller::<ink::env::DefaultEnvironment>(accounts.bob);
+            contract.complete_escrow(escrow_id).unwrap();
+            assert_eq!(contract.get_escrow_state(escrow_id), Some(EscrowState::Funded), "Should stay funded");
+            ink::env::test::set_caller::<ink::env::DefaultEnvironment>(accounts.charlie);
+            assert_eq!(contract.revoke_approval(escrow_id), Err(Error::Unauthorized), "Only participants");
+        }
+
+        #[ink::test]
+        fn test_event_seq() {
+            // Arrange: Create and fund escrow
+            let accounts = ink::env::test::default_accounts::<ink::env::DefaultEnvironment>();
+            let mut contract = EscrowSmartContract::new();
+            let amount = 100;
+            let escrow_id = contract.initiate_escrow(accounts.bob, amount).unwrap();
+            assert_eq!(last_event::<Initiated>().seq, 0, "First event should get sequence 0");
+            ink::env::test::set_value_transferred::<ink::env::DefaultEnvironment>(amount);
+            contract.deposit_assets(escrow_id).unwrap();
+            assert_eq!(last_event::<Deposited>().seq, 2, "Sequence should follow the state change");
+
+            // Act
+            contract.cancel_escrow(escrow_id).unwrap();
+
+            // Assert: Every event takes the next sequence number
+            assert_eq!(last_event::<Canceled>().seq, 4, "Sequence should order events");
+            contract.seq = u64::MAX;
+            assert_eq!(
+                contract.initiate_escrow(accounts.bob, amount),
+                Err(Error::IdOverflow),
+                "Sequence should not wrap around"
+            );
+        }
+
+        #[ink::test]
+        fn test_pending_approvers() {
+            // Arrange
+            let accounts = ink::env::test::default_accounts::<ink::env::DefaultEnvironment>();
+            let mut contract = EscrowSmartContract::new();
+            let escrow_id = contract.initiate_escrow(accounts.bob, 100).unwrap();
+
+            // Assert: nothing to approve before funding
+            assert_eq!(contract.pending_approvers(escrow_id), Ok(Vec::new()), "Unfunded escrow should have no pending approvers");
+            assert_eq!(contract.pending_approvers(escrow_id + 1), Err(Error::NotFound), "Unknown escrow should not be found");
+
+            // Act
+            ink::env::test::set_value_transferred::<ink::env::DefaultEnvironment>(100);
+            contract.deposit_assets(escrow_id).unwrap();
+
+            // Assert
+            assert_eq!(contract.pending_approvers(escrow_id), Ok(vec![accounts.alice, accounts.bob]), "Both parties should be pending");
+
+            // Act
+            contract.complete_escrow(escrow_id).unwrap();
+
+            // Assert
+            assert_eq!(contract.pending_approvers(escrow_id), Ok(vec![accounts.bob]), "Only the seller should be pending");
+        }
+
+        #[ink::test]
+        fn test_deposit_assets_expecting() {
+            // Arrange
+            let accounts = ink::env::test::default_accounts::<ink::env::DefaultEnvironment>();
+            let mut contract = EscrowSmartContract::new();
+            let escrow_id = contract.initiate_escrow(accounts.bob, 100).unwrap();
+            contract.cancel_escrow(escrow_id).unwrap();
+            let retried_id = contract.initiate_escrow(accounts.bob, 100).unwrap();
+            ink::env::test::set_value_transferred::<ink::env::DefaultEnvironment>(100);
+
+            // Act & Assert: the canceled escrow is rejected
+            assert_eq!(
+                contract.deposit_assets_expecting(escrow_id, EscrowState::Created),
+                Err(Error::StateChanged),
+                "Deposit into a canceled escrow should report a state change"
+            );
+
+            // Act & Assert: the matching escrow is funded once
+            assert_eq!(contract.deposit_assets_expecting(retried_id, EscrowState::Created), Ok(()), "Deposit should succeed");
+            assert_eq!(contract.get_escrow_state(retried_id), Some(EscrowState::Funded), "Escrow should be funded");
+            assert_eq!(
+                contract.deposit_assets_expecting(retried_id, EscrowState::Created),
+                Err(Error::StateChanged),
+                "Retried deposit should report a state change"
+            );
+        }
+
+        #[ink::test]
+        fn test_simulate_completion() {
+            // Arrange: a 2.5% fee collected by django
+            let accounts = ink::env::test::default_accounts::<ink::env::DefaultEnvironment>();
+            ink::env::test::set_caller::<ink::env::DefaultEnvironment>(accounts.django);
+            let mut contract = EscrowSmartContract::new();
+            contract.set_fee(250).unwrap();
+            ink::env::test::set_caller::<ink::env::DefaultEnvironment>(accounts.alice);
+            let escrow_id = contract.initiate_escrow(accounts.bob, 1_000).unwrap();
+
+            // Assert: nothing to preview before funding
+            assert_eq!(contract.simulate_completion(escrow_id), Err(Error::InvalidState), "Unfunded escrow cannot complete");
+
+            // Act
+            ink::env::test::set_value_transferred::<ink::env::DefaultEnvironment>(1_000);
+            contract.deposit_assets(escrow_id).unwrap();
+
+            // Assert
+            assert_eq!(
+                contract.simulate_completion(escrow_id),
+                Ok(vec![(accounts.bob, 975), (accounts.django, 25)]),
+                "Preview should list the seller payout and the fee"
+            );
+            assert_eq!(contract.get_escrow_state(escrow_id), Some(EscrowState::Funded), "Preview should not change state");
+        }
+
+        #[ink::test]
+        fn test_arbiter_by_consent() {
+            // Arrange: a funded escrow without an arbiter
+            let accounts = ink::env::test::default_accounts::<ink::env::DefaultEnvironment>();
+            let mut contract = EscrowSmartContract::new();
+            let escrow_id = contract.initiate_escrow(accounts.bob, 100).unwrap();
+            assert_eq!(contract.propose_arbiter(escrow_id, accounts.eve), Err(Error::InvalidState), "Unfunded escrow cannot get an arbiter");
+            ink::env::test::set_value_transferred::<ink::env::DefaultEnvironment>(100);
+            contract.deposit_assets(escrow_id).unwrap();
+            assert_eq!(contract.raise_dispute(escrow_id), Err(Error::InvalidState), "No dispute without an arbiter");
+
+            // Act & Assert: proposal by the buyer
+            assert_eq!(contract.propose_arbiter(escrow_id, accounts.bob), Err(Error::InvalidParticipants), "Arbiter should not be a party");
+            assert!(contract.propose_arbiter(escrow_id, accounts.eve).is_ok(), "Buyer should propose an arbiter");
+            let proposed = last_event::<ArbiterProposed>();
+            assert_eq!((proposed.arbiter, proposed.proposer), (accounts.eve, accounts.alice), "Event should carry the proposal");
+            assert_eq!(contract.accept_arbiter(escrow_id), Err(Error::Unauthorized), "Proposer cannot accept");
+
+            // Act & Assert: acceptance by the seller
+            ink::env::test::set_caller::<ink::env::DefaultEnvironment>(accounts.bob);
+            assert!(contract.accept_arbiter(escrow_id).is_ok(), "Seller should accept the arbiter");
+            assert_eq!(last_event::<ArbiterSet>().arbiter, accounts.eve, "Event should carry the arbiter");
+            assert_eq!(contract.get_participants(escrow_id).unwrap().arbiter, Some(accounts.eve), "Arbiter should be assigned");
+            assert_eq!(contract.propose_arbiter(escrow_id, accounts.django), Err(Error::InvalidState), "Arbiter cannot be replaced");
+
+            // Act & Assert: the assigned arbiter resolves disputes
+            assert!(contract.raise_dispute(escrow_id).is_ok(), "Seller should raise a dispute");
+            ink::env::test::set_caller::<ink::env::DefaultEnvironment>(accounts.eve);
+            assert_eq!(contract.my_role(escrow_id), Ok(Role::Arbiter), "Assigned arbiter should be recognized");
+            assert!(contract.resolve_dispute(escrow_id, 0, 100).is_ok(), "Assigned arbiter should resolve");
+        }
+
+        #[ink::test]
+        fn test_id_recycling() {
+            // Arrange
+            let accounts = ink::env::test::default_accounts::<ink::env::DefaultEnvironment>();
+            let mut contract = EscrowSmartContract::new();
+            let discarded = contract.initiate_escrow(accounts.bob, 100).unwrap();
+            contract.discard_escrow(discarded).unwrap();
+            assert_eq!(contract.initiate_escrow(accounts.bob, 100), Ok(discarded + 1), "IDs are not reused by default");
+
+            // Act
+            ink::env::test::set_caller::<ink::env::DefaultEnvironment>(accounts.bob);
+            assert_eq!(contract.set_id_recycling(true), Err(Error::Unauthorized), "Only the owner enables recycling");
+            ink::env::test::set_caller::<ink::env::DefaultEnvironment>(accounts.alice);
+            assert!(contract.set_id_recycling(true).is_ok(), "Owner should enable recycling");
+            assert!(last_event::<IdRecyclingSet>().enabled, "Event should carry the setting");
+            let recycled = contract.initiate_escrow(accounts.bob, 100).unwrap();
+            contract.discard_escrow(recycled).unwrap();
+            ink::env::test::set_caller::<ink::env::DefaultEnvironment>(accounts.charlie);
+            let reused = contract.initiate_escrow(accounts.bob, 50).unwrap();
+
+            // Assert
+            assert_eq!(reused, recycled, "Discarded ID should be reused");
+            assert_eq!(contract.initiate_escrow(accounts.bob, 50), Ok(recycled + 1), "Fresh IDs follow once the freelist is empty");
+            assert_eq!(contract.get_escrow(reused).unwrap().buyer, accounts.charlie, "Reused ID should hold the new escrow");
+            assert!(
+                !contract.get_escrows_by_buyer(accounts.alice, 0, 10).contains(&reused),
+                "Reused ID should not be listed for the previous buyer"
+            );
+            assert!(
+                contract.get_escrows_by_buyer(accounts.charlie, 0, 10).contains(&reused),
+                "Reused ID should be listed for the new buyer"
+            );
+        }
+
+        #[ink::test]
+        fn test_discard_unlists_escrow() {
+            // Arrange: recycling on, three escrows between alice and bob
+            let accounts = ink::env::test::default_accounts::<ink::env::DefaultEnvironment>();
+            let mut contract = EscrowSmartContract::new();
+            contract.set_id_recycling(true).unwrap();
+            let first = contract.initiate_escrow(accounts.bob, 100).unwrap();
+            let discarded = contract.initiate_escrow(accounts.bob, 100).unwrap();
+            let last = contract.initiate_escrow(accounts.bob, 100).unwrap();
+
+            // Act: discard the middle escrow and reuse its ID between charlie and django
+            contract.discard_escrow(discarded).unwrap();
+            ink::env::test::set_caller::<ink::env::DefaultEnvironment>(accounts.charlie);
+            let reused = contract.initiate_escrow(accounts.django, 50).unwrap();
+
+            // Assert
+            assert_eq!(reused, discarded, "Discarded ID should be reused");
+            assert_eq!(contract.count_escrows_by_buyer(accounts.alice), 2, "Buyer count should drop the discarded escrow");
+            assert_eq!(contract.count_escrows_by_seller(accounts.bob), 2, "Seller count should drop the discarded escrow");
+            assert_eq!(contract.get_escrows_by_buyer(accounts.alice, 0, 10), vec![first, last], "Last escrow should take the freed position");
+            assert_eq!(contract.get_escrows_by_seller(accounts.bob, 0, 10), vec![first, last], "Last escrow should take the freed position");
+            assert_eq!(contract.get_escrows_by_buyer(accounts.charlie, 0, 10), vec![reused], "Reused ID should be listed for the new buyer");
+            assert_eq!(contract.get_escrows_by_seller(accounts.django, 0, 10), vec![reused], "Reused ID should be listed for the new seller");
+
+            // Act: discarding the last listed escrow leaves the others in place
+            ink::env::test::set_caller::<ink::env::DefaultEnvironment>(accounts.alice);
+            contract.discard_escrow(last).unwrap();
+
+            // Assert
+            assert_eq!(contract.get_escrows_by_buyer(accounts.alice, 0, 10), vec![first], "Only the first escrow should stay listed");
+        }
+
+        #[ink::test]
+        fn test_complete_escrow_expired_approval() {
+            // Arrange: a funded escrow with a 1_000 ms approval validity
+            let accounts = ink::env::test::default_accounts::<ink::env::DefaultEnvironment>();
+            let mut contract = EscrowSmartContract::new();
+            let terms = EscrowTerms { approval_ttl: Some(1_000), ..Default::default() };
+            let escrow_id = contract.initiate_escrow_with_terms(accounts.bob, 100, terms).unwrap();
+            ink::env::test::set_value_transferred::<ink::env::DefaultEnvironment>(100);
+            contract.deposit_assets(escrow_id).unwrap();
+            ink::env::test::set_block_timestamp::<ink::env::DefaultEnvironment>(0);
+            contract.complete_escrow(escrow_id).unwrap();
+
+            // Act: the seller approves after the buyer's approval went stale
+            ink::env::test::set_block_timestamp::<ink::env::DefaultEnvironment>(1_001);
+            assert_eq!(contract.pending_approvers(escrow_id), Ok(vec![accounts.alice, accounts.bob]), "Stale approval should count as missing");
+            ink::env::test::set_caller::<ink::env::DefaultEnvironment>(accounts.bob);
+            contract.complete_escrow(escrow_id).unwrap();
+
+            // Assert
+            assert_eq!(last_event::<ApprovalExpired>().party, accounts.alice, "Buyer's approval should expire");
+            assert_eq!(contract.get_escrow_state(escrow_id), Some(EscrowState::Funded), "Stale approval should not complete");
+            assert_eq!(contract.pending_approvers(escrow_id), Ok(vec![accounts.alice]), "Buyer should confirm again");
+
+            // Act: the buyer confirms again within the seller's validity
+            ink::env::test::set_caller::<ink::env::DefaultEnvironment>(accounts.alice);
+            ink::env::test::set_block_timestamp::<ink::env::DefaultEnvironment>(2_000);
+            contract.complete_escrow(escrow_id).unwrap();
+
+            // Assert
+            assert_eq!(contract.get_escrow_state(escrow_id), Some(EscrowState::Completed), "Fresh approvals should complete");
+        }
+
+        #[ink::test]
+        fn test_deposit_installments() {
+            // Arrange
+            let accounts = ink::env::test::default_accounts::<ink::env::DefaultEnvironment>();
+            let mut contract = EscrowSmartContract::new();
+            let amount = 100;
+            let escrow_id = contract.initiate_escrow(accounts.bob, amount).unwrap();
+
+            // Act: first installment
+            ink::env::test::set_value_transferred::<ink::env::DefaultEnvironment>(40);
+            contract.deposit_assets(escrow_id).unwrap();
+
+            // Assert
+            let event = last_event::<PartialDeposit>();
+            assert_eq!(
+                (event.amount, event.remaining),
+                (EventAmount::Clear(40), EventAmount::Clear(60)),
+                "Event should carry the installment and the remainder"
+            );
+            assert_eq!(contract.get_escrow_state(escrow_id), Some(EscrowState::Created), "Escrow should wait for the rest");
+            assert_eq!(contract.get_total_locked(), 40, "Installment should be locked");
+            assert_eq!(contract.discard_escrow(escrow_id), Err(Error::InvalidState), "Escrow with installments cannot be discarded");
+
+            // Act & Assert: overpaying the remainder is rejected
+            ink::env::test::set_value_transferred::<ink::env::DefaultEnvironment>(61);
+            assert_eq!(contract.deposit_assets(escrow_id), Err(Error::InvalidAmount), "Payment should not exceed the amount");
+
+            // Act: final installment
+            ink::env::test::set_value_transferred::<ink::env::DefaultEnvironment>(60);
+            contract.deposit_assets(escrow_id).unwrap();
+
+            // Assert
+            assert_eq!(last_event::<Deposited>().amount, EventAmount::Clear(amount), "Event should carry the full amount");
+            assert_eq!(contract.get_escrow_state(escrow_id), Some(EscrowState::Funded), "Escrow should be funded");
+            assert_eq!(contract.get_total_locked(), amount, "Full amount should be locked once");
+        }
+
+        #[ink::test]
+        fn test_cancel_refunds_installments() {
+            // Arrange: charlie pays an installment towards an escrow
+            let accounts = ink::env::test::default_accounts::<ink::env::DefaultEnvironment>();
+            let mut contract = EscrowSmartContract::new();
+            ink::env::test::set_caller::<ink::env::DefaultEnvironment>(accounts.charlie);
+            let escrow_id = contract.initiate_escrow(accounts.bob, 100).unwrap();
+            ink::env::test::set_value_transferred::<ink::env::DefaultEnvironment>(30);
+            contract.deposit_assets(escrow_id).unwrap();
+            let balance_before = ink::env::test::get_account_balance::<ink::env::DefaultEnvironment>(accounts.charlie).unwrap();
+
+            // Act
+            contract.cancel_escrow(escrow_id).unwrap();
+
+            // Assert
+            let balance_after = ink::env::test::get_account_balance::<ink::env::DefaultEnvironment>(accounts.charlie).unwrap();
+            assert_eq!(balance_after - balance_before, 30, "Installment should be refunded");
+            assert_eq!(last_event::<Canceled>().refunded, EventAmount::Clear(30), "Event should carry the refund");
+            assert_eq!(contract.get_total_locked(), 0, "Nothing should remain locked");
+        }
+
+        #[ink::test]
+        fn test_settlement_pays_seller_like_a_release() {
+            // Arrange: a 10% fee and a payout address
+            let accounts = ink::env::test::default_accounts::<ink::env::DefaultEnvironment>();
+            let mut contract = EscrowSmartContract::new();
+            contract.set_fee(1_000).unwrap();
+            ink::env::test::set_caller::<ink::env::DefaultEnvironment>(accounts.charlie);
+            let escrow_id = contract.initiate_escrow(accounts.bob, 100).unwrap();
+            ink::env::test::set_value_transferred::<ink::env::DefaultEnvironment>(100);
+            contract.deposit_assets(escrow_id).unwrap();
+            ink::env::test::set_caller::<ink::env::DefaultEnvironment>(accounts.bob);
+            contract.set_payout_address(escrow_id, accounts.django).unwrap();
+            contract.propose_settlement(escrow_id, 30, 70).unwrap();
+
+            // Act
+            let balance = |account| {
+                ink::env::test::get_account_balance::<ink::env::DefaultEnvironment>(account).unwrap()
+            };
+            let (bob_balance, django_balance) = (balance(accounts.bob), balance(accounts.django));
+            ink::env::test::set_caller::<ink::env::DefaultEnvironment>(accounts.charlie);
+            contract.accept_settlement(escrow_id).unwrap();
+
+            // Assert: the seller share goes to the payout address minus the fee
+            assert_eq!(balance(accounts.django), django_balance + 63, "Payout address should get the share minus the fee");
+            assert_eq!(balance(accounts.bob), bob_balance, "Seller should be paid through its payout address");
+        }
+
+        #[ink::test]
+        fn test_get_version() {
+            let contract = EscrowSmartContract::new();
+            assert_eq!(contract.get_version(), (0, 1, 0), "Should report the crate version");
+        }
+
+        #[ink::test]
+        fn test_complete_escrow_payout_address() {
+            // Arrange: Create and fund escrow
+            let accounts = ink::env::test::default_accounts::<ink::env::DefaultEnvironment>();
+            let mut contract = EscrowSmartContract::new();
+            let amount = 100;
+            let escrow_id = contract.initiate_escrow(accounts.bob, amount).unwrap();
+            ink::env::test::set_value_transferred::<ink::env::DefaultEnvironment>(amount);
+            contract.deposit_assets(escrow_id).unwrap();
+
+            // Act & Assert: Only the seller sets a real payout address
+            assert_eq!(
+                contract.set_payout_address(escrow_id, accounts.django),
+                Err(Error::NotSeller),
+                "Buyer should not set the payout address"
+            );
+            ink::env::test::set_caller::<ink::env::DefaultEnvironment>(accounts.bob);
+            assert_eq!(
+                contract.set_payout_address(escrow_id, AccountId::from([0u8; 32])),
+                Err(Error::ZeroAddress),
+                "Should reject the zero account"
+            );
+            assert!(contract.set_payout_address(escrow_id, accounts.django).is_ok(), "Seller should set the payout address");
+            assert_eq!(
+                last_event::<PayoutAddressSet>().payout_address,
+                accounts.django,
+                "Event should carry the payout address"
+            );
+
+            // Completion pays the payout address, while the seller still approves
+            let balance = |account| {
+                ink::env::test::get_account_balance::<ink::env::DefaultEnvironment>(account).unwrap()
+            };
+            let (bob_balance, django_balance) = (balance(accounts.bob), balance(accounts.django));
+            contract.complete_escrow(escrow_id).unwrap();
+            ink::env::test::set_caller::<ink::env::DefaultEnvironment>(accounts.alice);
+            contract.complete_escrow(escrow_id).unwrap();
+            assert_eq!(balance(accounts.django), django_balance + amount, "Payout should go to the payout address");
+            assert_eq!(balance(accounts.bob), bob_balance, "Seller should not be paid");
+        }
+
+        #[ink::test]
+        fn test_initiate_and_fund() {
+            // Arrange
+            let accounts = ink::env::test::default_accounts::<ink::env::DefaultEnvironment>();
+            let mut contract = EscrowSmartContract::new();
+
+            // Act & Assert: a partial payment is rejected
+            ink::env::test::set_value_transferred::<ink::env::DefaultEnvironment>(99);
+            assert_eq!(contract.initiate_and_fund(accounts.bob, 100), Err(Error::InvalidAmount), "Should require the full amount");
+
+            // Act
+            ink::env::test::set_value_transferred::<ink::env::DefaultEnvironment>(100);
+            let escrow_id = contract.initiate_and_fund(accounts.bob, 100).unwrap();
+
+            // Assert
+            let events = ink::env::test::recorded_events().collect::<Vec<_>>();
+            assert_eq!(events.len(), 3, "Should emit Initiated, StateChanged and Deposited");
+            assert_eq!(last_event::<Deposited>().escrow_id, escrow_id, "Deposit event should carry the escrow ID");
+            assert_eq!(contract.get_escrow_state(escrow_id), Some(EscrowState::Funded), "Escrow should be funded");
+            assert_eq!(contract.get_escrow(escrow_id).unwrap().buyer, accounts.alice, "Caller should be the buyer");
+        }
+
+        #[ink::test]
+        fn test_claim_dispute_timeout() {
+            // Arrange: charlie disputes an escrow with a 1_000 ms dispute timeout
+            let accounts = ink::env::test::default_accounts::<ink::env::DefaultEnvironment>();
+            let mut contract = EscrowSmartContract::new_with_arbiter(accounts.eve).unwrap();
+            ink::env::test::set_caller::<ink::env::DefaultEnvironment>(accounts.charlie);
+            let terms = EscrowTerms { dispute_timeout: Some(1_000), ..Default::default() };
+            let escrow_id = contract.initiate_escrow_with_terms(accounts.bob, 100, terms).unwrap();
+            ink::env::test::set_value_transferred::<ink::env::DefaultEnvironment>(100);
+            contract.deposit_assets(escrow_id).unwrap();
+            assert_eq!(contract.claim_dispute_timeout(escrow_id), Err(Error::InvalidState), "Undisputed escrow cannot time out");
+            ink::env::test::set_block_timestamp::<ink::env::DefaultEnvironment>(500);
+            contract.raise_dispute(escrow_id).unwrap();
+
+            // Act & Assert: the timeout has not passed yet
+            ink::env::test::set_block_timestamp::<ink::env::DefaultEnvironment>(1_499);
+            assert_eq!(contract.claim_dispute_timeout(escrow_id), Err(Error::DeadlineNotReached), "Timeout should not have passed");
+            ink::env::test::set_caller::<ink::env::DefaultEnvironment>(accounts.bob);
+            assert_eq!(contract.claim_dispute_timeout(escrow_id), Err(Error::NotBuyer), "Only the buyer reclaims");
+
+            // Act: the buyer reclaims after the timeout
+            ink::env::test::set_caller::<ink::env::DefaultEnvironment>(accounts.charlie);
+            ink::env::test::set_block_timestamp::<ink::env::DefaultEnvironment>(1_500);
+            let balance_before = ink::env::test::get_account_balance::<ink::env::DefaultEnvironment>(accounts.charlie).unwrap();
+            contract.claim_dispute_timeout(escrow_id).unwrap();
+
+            // Assert
+            let balance_after = ink::env::test::get_account_balance::<ink::env::DefaultEnvironment>(accounts.charlie).unwrap();
+            assert_eq!(balance_after - balance_before, 100, "Buyer should be refunded in full");
+            assert_eq!(last_event::<DisputeTimedOut>().refunded, EventAmount::Clear(100), "Event should carry the refund");
+            let events = ink::env::test::recorded_events().collect::<Vec<_>>();
+            let canceled = <Canceled as scale::Decode>::decode(&mut &events[events.len() - 2].data[..]).expect("Event should decode");
+            assert_eq!(canceled.reason, CancelReason::Expired, "Timeout cancels as expired");
+            assert_eq!(contract.get_escrow_state(escrow_id), Some(EscrowState::Canceled), "Escrow should be canceled");
+        }
+
+        #[ink::test]
+        fn test_state_changed_event() {
+            // Arrange
+            let accounts = ink::env::test::default_accounts::<ink::env::DefaultEnvironment>();
+            let mut contract = EscrowSmartContract::new();
+            let escrow_id = contract.initiate_escrow(accounts.bob, 100).unwrap();
+
+            // Act
+            contract.cancel_escrow(escrow_id).unwrap();
+
+            // Assert: the transition event precedes the specific event
+            let events = ink::env::test::recorded_events().collect::<Vec<_>>();
+            let changed = <StateChanged as scale::Decode>::decode(&mut &events[events.len() - 2].data[..]).expect("Event should decode");
+            assert_eq!(changed.escrow_id, escrow_id, "Event should carry the escrow ID");
+            assert_eq!((changed.from, changed.to), (EscrowState::Created, EscrowState::Canceled), "Event should carry both states");
+            assert_eq!(last_event::<Canceled>().escrow_id, escrow_id, "Specific event should still be emitted");
+        }
+
+        #[ink::test]
+        fn test_arbiter_cannot_be_party() {
+            // Arrange
+            let accounts = ink::env::test::default_accounts::<ink::env::DefaultEnvironment>();
+            let mut contract = EscrowSmartContract::new_with_arbiter(accounts.eve).unwrap();
+
+            // Act & Assert: the default arbiter as buyer or seller
+            ink::env::test::set_caller::<ink::env::DefaultEnvironment>(accounts.eve);
+            assert_eq!(contract.initiate_escrow(accounts.bob, 100), Err(Error::InvalidParticipants), "Arbiter should not be the buyer");
+            ink::env::test::set_caller::<ink::env::DefaultEnvironment>(accounts.alice);
+            assert_eq!(contract.initiate_escrow(accounts.eve, 100), Err(Error::InvalidParticipants), "Arbiter should not be the seller");
+
+            // Act & Assert: a per-escrow arbiter as buyer or seller
+            let mut contract = EscrowSmartContract::new();
+            let escrow_id = contract.initiate_escrow(accounts.bob, 100).unwrap();
+            ink::env::test::set_value_transferred::<ink::env::DefaultEnvironment>(100);
+            contract.deposit_assets(escrow_id).unwrap();
+            assert_eq!(contract.propose_arbiter(escrow_id, accounts.alice), Err(Error::InvalidParticipants), "Arbiter should not be the buyer");
+            assert_eq!(contract.propose_arbiter(escrow_id, accounts.bob), Err(Error::InvalidParticipants), "Arbiter should not be the seller");
+        }
+
+        #[ink::test]
+        fn test_confirm_high_value() {
+            // Arrange: escrows of 1_000 or more need confirmation
+            let accounts = ink::env::test::default_accounts::<ink::env::DefaultEnvironment>();
+            let mut contract = EscrowSmartContract::new();
+            contract.set_high_value_threshold(1_000).unwrap();
+            let limits = last_event::<LimitsUpdated>();
+            assert_eq!(
+                (limits.old_high_value_threshold, limits.new_high_value_threshold),
+                (0, 1_000),
+                "Event should carry the old and new threshold"
+            );
+            let small = contract.initiate_escrow(accounts.bob, 999).unwrap();
+            let large = contract.initiate_escrow(accounts.bob, 1_000).unwrap();
+            ink::env::test::set_value_transferred::<ink::env::DefaultEnvironment>(999);
+            contract.deposit_assets(small).unwrap();
+            ink::env::test::set_value_transferred::<ink::env::DefaultEnvironment>(1_000);
+            contract.deposit_assets(large).unwrap();
+            contract.complete_escrow(small).unwrap();
+            contract.complete_escrow(large).unwrap();
+
+            // Act & Assert: the seller's approval releases only the small escrow
+            ink::env::test::set_caller::<ink::env::DefaultEnvironment>(accounts.bob);
+            assert!(contract.complete_escrow(small).is_ok(), "Small escrow should release");
+            assert_eq!(contract.complete_escrow(large), Err(Error::ConfirmationRequired), "Large escrow should need confirmation");
+            assert_eq!(contract.confirm_high_value(large), Err(Error::NotBuyer), "Only the buyer confirms");
+
+            // Act & Assert: the buyer confirms, then the seller's approval releases
+            ink::env::test::set_caller::<ink::env::DefaultEnvironment>(accounts.alice);
+            assert!(contract.confirm_high_value(large).is_ok(), "Buyer should confirm");
+            assert_eq!(last_event::<HighValueConfirmed>().escrow_id, large, "Event should carry the escrow ID");
+            ink::env::test::set_caller::<ink::env::DefaultEnvironment>(accounts.bob);
+            assert!(contract.complete_escrow(large).is_ok(), "Confirmed escrow should release");
+            assert_eq!(contract.get_escrow_state(large), Some(EscrowState::Completed), "Large escrow should be completed");
+        }
+
+        #[ink::test]
+        fn test_transfer_buyer_position() {
+            // Arrange: a funded escrow approved by the buyer
+            let accounts = ink::env::test::default_accounts::<ink::env::DefaultEnvironment>();
+            let mut contract = EscrowSmartContract::new();
+            let escrow_id = contract.initiate_escrow(accounts.bob, 100).unwrap();
+            ink::env::test::set_value_transferred::<ink::env::DefaultEnvironment>(100);
+            contract.deposit_assets(escrow_id).unwrap();
+            contract.complete_escrow(escrow_id).unwrap();
+
+            // Act & Assert: invalid transfers
+            assert_eq!(
+                contract.transfer_buyer_position(escrow_id, accounts.bob),
+                Err(Error::InvalidParticipants),
+                "Seller cannot become the buyer"
+            );
+            ink::env::test::set_caller::<ink::env::DefaultEnvironment>(accounts.bob);
+            assert_eq!(
+                contract.transfer_buyer_position(escrow_id, accounts.charlie),
+                Err(Error::NotBuyer),
+                "Only the buyer transfers the position"
+            );
+
+            // Act
+            ink::env::test::set_caller::<ink::env::DefaultEnvironment>(accounts.alice);
+            contract.transfer_buyer_position(escrow_id, accounts.charlie).unwrap();
+
+            // Assert
+            let event = last_event::<BuyerTransferred>();
+            assert_eq!((event.from, event.to), (accounts.alice, accounts.charlie), "Event should carry both buyers");
+            let escrow = contract.get_escrow(escrow_id).unwrap();
+            assert_eq!(escrow.buyer, accounts.charlie, "Buyer should be reassigned");
+            assert!(!escrow.buyer_approved, "Previous approval should be cleared");
+            assert!(contract.get_escrows_by_buyer(accounts.alice, 0, 10).is_empty(), "Previous buyer should no longer list it");
+            assert_eq!(contract.get_escrows_by_buyer(accounts.charlie, 0, 10), vec![escrow_id], "New buyer should list it");
+            assert_eq!(contract.count_escrows_by_buyer(accounts.alice), 0, "Previous buyer should count nothing");
+            assert_eq!(contract.count_escrows_by_buyer(accounts.charlie), 1, "New buyer should count it");
+            assert_eq!(contract.locked_balance_of(accounts.alice), 0, "Previous buyer should have nothing locked");
+            assert_eq!(contract.locked_balance_of(accounts.charlie), 100, "New buyer should have the amount locked");
+            assert_eq!(contract.complete_escrow(escrow_id), Err(Error::Unauthorized), "Previous buyer cannot approve");
+
+            // Act & Assert: the position comes back to the previous buyer, who lists it once
+            ink::env::test::set_caller::<ink::env::DefaultEnvironment>(accounts.charlie);
+            contract.transfer_buyer_position(escrow_id, accounts.alice).unwrap();
+            assert_eq!(contract.count_escrows_by_buyer(accounts.alice), 1, "Returning buyer should count it once");
+            assert_eq!(contract.locked_balance_of(accounts.alice), 100, "Returning buyer should have the amount locked once");
+            assert_eq!(contract.count_escrows_by_buyer(accounts.charlie), 0, "Second buyer should count nothing");
+            ink::env::test::set_caller::<ink::env::DefaultEnvironment>(accounts.alice);
+            assert!(contract.complete_escrow(escrow_id).is_ok(), "Current buyer should approve");
+        }
+
+        #[ink::test]
+        fn test_admin_events() {
+            // Arrange
+            let accounts = ink::env::test::default_accounts::<ink::env::DefaultEnvironment>();
+            let mut contract = EscrowSmartContract::new_with_arbiter(accounts.eve).unwrap();
+
+            // Assert: deployment reports the arbiter
+            let changed = last_event::<ArbiterChanged>();
+            assert_eq!((changed.old, changed.new), (None, Some(accounts.eve)), "Event should carry the arbiter");
+            assert_eq!(changed.seq, 0, "Arbiter event should be the first event");
+
+            // Act & Assert: every admin change carries the old and new value
+            contract.set_fee(100).unwrap();
+            contract.set_fee(250).unwrap();
+            let fee = last_event::<FeeUpdated>();
+            assert_eq!((fee.old_fee_bps, fee.new_fee_bps), (100, 250), "Event should carry both fees");
+            contract.set_fee_override(accounts.charlie, 50).unwrap();
+            contract.set_fee_override(accounts.charlie, FEE_OVERRIDE_REMOVED).unwrap();
+            let fee_override = last_event::<FeeOverrideSet>();
+            assert_eq!((fee_override.old_fee_bps, fee_override.fee_bps), (Some(50), None), "Event should carry both overrides");
+            contract.set_max_open_per_buyer(3).unwrap();
+            let limits = last_event::<LimitsUpdated>();
+            assert_eq!(
+                (limits.old_max_open_per_buyer, limits.new_max_open_per_buyer),
+                (0, 3),
+                "Event should carry both limits"
+            );
+        }
+
+        #[ink::test]
+        fn test_quote_fee_rounding() {
+            // Arrange: a 2.5% fee
+            let mut contract = EscrowSmartContract::new();
+            assert_eq!(contract.quote_fee(1_000), Ok(0), "No fee by default");
+            contract.set_fee(250).unwrap();
+
+            // Act & Assert: fees round down at small amounts
+            assert_eq!(contract.quote_fee(0), Ok(0), "Zero amount carries no fee");
+            assert_eq!(contract.quote_fee(39), Ok(0), "Fee below one unit rounds to zero");
+            assert_eq!(contract.quote_fee(40), Ok(1), "Exact fee of one unit");
+            assert_eq!(contract.quote_fee(79), Ok(1), "Fee rounds down");
+            assert_eq!(contract.quote_fee(1_000), Ok(25), "Fee of a round amount");
+            assert_eq!(contract.quote_fee(Balance::MAX), Err(Error::InvalidAmount), "Overflow should be reported");
+        }
+
+        #[ink::test]
+        fn test_mutual_stake_escrow() {
+            // Arrange: charlie buys for 100 and bob stakes 30
+            let accounts = ink::env::test::default_accounts::<ink::env::DefaultEnvironment>();
+            let mut contract = EscrowSmartContract::new();
+            ink::env::test::set_caller::<ink::env::DefaultEnvironment>(accounts.charlie);
+            assert_eq!(contract.initiate_stake_escrow(accounts.bob, 100, 0), Err(Error::InvalidAmount), "Stake should be non-zero");
+            let escrow_id = contract.initiate_stake_escrow(accounts.bob, 100, 30).unwrap();
+            assert_eq!(contract.deposit_stake(escrow_id), Err(Error::NotSeller), "Only the seller stakes");
+
+            // Act: the seller stakes first, then the buyer deposits
+            ink::env::test::set_caller::<ink::env::DefaultEnvironment>(accounts.bob);
+            ink::env::test::set_value_transferred::<ink::env::DefaultEnvironment>(29);
+            assert_eq!(contract.deposit_stake(escrow_id), Err(Error::InvalidAmount), "Stake should be exact");
+            ink::env::test::set_value_transferred::<ink::env::DefaultEnvironment>(30);
+            contract.deposit_stake(escrow_id).unwrap();
+            assert_eq!(last_event::<StakeDeposited>().amount, EventAmount::Clear(30), "Event should carry the stake");
+            assert_eq!(contract.get_escrow_state(escrow_id), Some(EscrowState::Created), "Escrow should wait for the buyer");
+            ink::env::test::set_caller::<ink::env::DefaultEnvironment>(accounts.charlie);
+            ink::env::test::set_value_transferred::<ink::env::DefaultEnvironment>(100);
+            contract.deposit_assets(escrow_id).unwrap();
+
+            // Assert
+            assert_eq!(contract.get_escrow_state(escrow_id), Some(EscrowState::Funded), "Both deposits should fund the escrow");
+            assert_eq!(contract.get_total_locked(), 130, "Both deposits should be locked");
+
+            // Act: both parties approve
+            let balance = |account| {
+                ink::env::test::get_account_balance::<ink::env::DefaultEnvironment>(account).unwrap()
+            };
+            let (bob_balance, charlie_balance) = (balance(accounts.bob), balance(accounts.charlie));
+            contract.complete_escrow(escrow_id).unwrap();
+            ink::env::test::set_caller::<ink::env::DefaultEnvironment>(accounts.bob);
+            contract.complete_escrow(escrow_id).unwrap();
+
+            // Assert: each party receives the other's deposit
+            assert_eq!(balance(accounts.bob), bob_balance + 100, "Seller should receive the buyer's amount");
+            assert_eq!(balance(accounts.charlie), charlie_balance + 30, "Buyer should receive the seller's stake");
+            assert_eq!(contract.get_total_locked(), 0, "Nothing should remain locked");
+        }
+
+        #[ink::test]
+        fn test_cancel_mutual_stake_escrow() {
+            // Arrange: a funded mutual-stake escrow
+            let accounts = ink::env::test::default_accounts::<ink::env::DefaultEnvironment>();
+            let mut contract = EscrowSmartContract::new();
+            ink::env::test::set_caller::<ink::env::DefaultEnvironment>(accounts.charlie);
+            let escrow_id = contract.initiate_stake_escrow(accounts.bob, 100, 30).unwrap();
+            ink::env::test::set_value_transferred::<ink::env::DefaultEnvironment>(100);
+            contract.deposit_assets(escrow_id).unwrap();
+            ink::env::test::set_caller::<ink::env::DefaultEnvironment>(accounts.bob);
+            ink::env::test::set_value_transferred::<ink::env::DefaultEnvironment>(30);
+            contract.deposit_stake(escrow_id).unwrap();
+
+            // Act
+            let balance = |account| {
+                ink::env::test::get_account_balance::<ink::env::DefaultEnvironment>(account).unwrap()
+            };
+            let (bob_balance, charlie_balance) = (balance(accounts.bob), balance(accounts.charlie));
+            ink::env::test::set_caller::<ink::env::DefaultEnvironment>(accounts.charlie);
+            contract.cancel_escrow(escrow_id).unwrap();
+
+            // Assert: each party is refunded its own deposit
+            assert_eq!(balance(accounts.charlie), charlie_balance + 100, "Buyer should be refunded");
+            assert_eq!(balance(accounts.bob), bob_balance + 30, "Seller should get the stake back");
+            assert_eq!(contract.get_total_locked(), 0, "Nothing should remain locked");
+        }
+
+        #[ink::test]
+        fn test_cancel_keeps_failed_transfers_claimable() {
+            // Arrange: a contract account without a balance, so that every transfer fails,
+            // with an unfunded escrow holding the seller's stake and a funded one with a penalty
+            let accounts = ink::env::test::default_accounts::<ink::env::DefaultEnvironment>();
+            ink::env::test::set_callee::<ink::env::DefaultEnvironment>(AccountId::from([7; 32]));
+            let mut contract = EscrowSmartContract::new();
+            ink::env::test::set_caller::<ink::env::DefaultEnvironment>(accounts.charlie);
+            let staked = contract.initiate_stake_escrow(accounts.bob, 100, 30).unwrap();
+            let terms = EscrowTerms { cancellation_penalty_bps: 1_000, ..Default::default() };
+            let penalized = contract.initiate_escrow_with_terms(accounts.bob, 100, terms).unwrap();
+            ink::env::test::set_value_transferred::<ink::env::DefaultEnvironment>(100);
+            contract.deposit_assets(penalized).unwrap();
+            ink::env::test::set_caller::<ink::env::DefaultEnvironment>(accounts.bob);
+            ink::env::test::set_value_transferred::<ink::env::DefaultEnvironment>(30);
+            contract.deposit_stake(staked).unwrap();
+
+            // Act
+            assert_eq!(contract.cancel_escrow(staked), Ok(()), "Returning the stake should not block the cancellation");
+            ink::env::test::set_caller::<ink::env::DefaultEnvironment>(accounts.charlie);
+            assert_eq!(contract.cancel_escrow(penalized), Ok(()), "Paying the penalty should not block the cancellation");
+
+            // Assert: the stake and the penalty are owed to the seller, the refund to the buyer
+            assert_eq!(contract.pending_withdrawal_of(accounts.bob), 30 + 10, "Seller should be owed its stake and the penalty");
+            assert_eq!(contract.pending_withdrawal_of(accounts.charlie), 90, "Buyer should be owed its refund");
+            assert_eq!(contract.get_total_locked(), 130, "Owed transfers should stay locked");
+        }
+
+        #[ink::test]
+        fn test_mutual_stake_dispute_and_settlement() {
+            // Arrange: two funded mutual-stake escrows, one disputed and one with a settlement proposal
+            let accounts = ink::env::test::default_accounts::<ink::env::DefaultEnvironment>();
+            let mut contract = EscrowSmartContract::new_with_arbiter(accounts.eve).unwrap();
+            let mut ids = Vec::new();
+            for _ in 0..2 {
+                ink::env::test::set_caller::<ink::env::DefaultEnvironment>(accounts.charlie);
+                let escrow_id = contract.initiate_stake_escrow(accounts.bob, 100, 30).unwrap();
+                ink::env::test::set_value_transferred::<ink::env::DefaultEnvironment>(100);
+                contract.deposit_assets(escrow_id).unwrap();
+                ink::env::test::set_caller::<ink::env::DefaultEnvironment>(accounts.bob);
+                ink::env::test::set_value_transferred::<ink::env::DefaultEnvironment>(30);
+                contract.deposit_stake(escrow_id).unwrap();
+                ids.push(escrow_id);
+            }
+            let (disputed, settled) = (ids[0], ids[1]);
+            ink::env::test::set_caller::<ink::env::DefaultEnvironment>(accounts.charlie);
+            contract.raise_dispute(disputed).unwrap();
+            contract.propose_settlement(settled, 50, 50).unwrap();
+
+            // Act: the arbiter rules for the buyer and the seller accepts the settlement
+            let balance = |account| {
+                ink::env::test::get_account_balance::<ink::env::DefaultEnvironment>(account).unwrap()
+            };
+            let (bob_balance, charlie_balance) = (balance(accounts.bob), balance(accounts.charlie));
+            ink::env::test::set_caller::<ink::env::DefaultEnvironment>(accounts.eve);
+            contract.resolve_dispute(disputed, 100, 0).unwrap();
+            ink::env::test::set_caller::<ink::env::DefaultEnvironment>(accounts.bob);
+            contract.accept_settlement(settled).unwrap();
+
+            // Assert: the buyer's amount is divided and the stakes go back to the seller
+            assert_eq!(balance(accounts.charlie), charlie_balance + 150, "Buyer should get the ruling and its share");
+            assert_eq!(balance(accounts.bob), bob_balance + 50 + 60, "Seller should get its share and both stakes");
+            assert_eq!(contract.get_total_locked(), 0, "Nothing should remain locked");
+        }
+
+        #[ink::test]
+        fn test_refund_lock_period() {
+            // Arrange: buyers cannot cancel for 1_000 ms after funding
+            let accounts = ink::env::test::default_accounts::<ink::env::DefaultEnvironment>();
+            let mut contract = EscrowSmartContract::new();
+            ink::env::test::set_caller::<ink::env::DefaultEnvironment>(accounts.bob);
+            assert_eq!(contract.set_refund_lock_period(1_000), Err(Error::Unauthorized), "Only the owner");
+            ink::env::test::set_caller::<ink::env::DefaultEnvironment>(accounts.alice);
+            contract.set_refund_lock_period(1_000).unwrap();
+            let event = last_event::<RefundLockPeriodUpdated>();
+            assert_eq!((event.old_period, event.new_period), (0, 1_000), "Event should carry both periods");
+            ink::env::test::set_block_timestamp::<ink::env::DefaultEnvironment>(500);
+            let escrow_id = contract.initiate_escrow(accounts.bob, 100).unwrap();
+            ink::env::test::set_value_transferred::<ink::env::DefaultEnvironment>(100);
+            contract.deposit_assets(escrow_id).unwrap();
+
+            // Act & Assert: the buyer waits for the period to pass
+            ink::env::test::set_block_timestamp::<ink::env::DefaultEnvironment>(1_499);
+            assert_eq!(contract.cancel_escrow(escrow_id), Err(Error::DeadlineNotReached), "Buyer should wait for the lock period");
+            ink::env::test::set_block_timestamp::<ink::env::DefaultEnvironment>(1_500);
+            assert!(contract.cancel_escrow(escrow_id).is_ok(), "Buyer should cancel after the lock period");
         }
-    }
 
-    #[cfg(test)]
-    mod tests {
-        use super::*;
-        /// # Escrow Contract Test Suite
-        ///
-        /// This module contains comprehensive tests for the EscrowSmartContract,
-        /// covering core functionality, edge cases, and security requirements.
-        ///
-        /// ## Test Accounts Convention:
-        /// - Alice: Default caller/buyer (ink! default account)
-        /// - Bob: Seller account
-        /// - Charlie: Unauthorized third party
         #[ink::test]
-        fn test_initiate_escrow() {
-            // Arrange: Initialize contract and test accounts
+        fn test_get_settled_between() {
+            // Arrange: escrows canceled at 100, 200 and 300
             let accounts = ink::env::test::default_accounts::<ink::env::DefaultEnvironment>();
             let mut contract = EscrowSmartContract::new();
+            let mut ids = Vec::new();
+            for timestamp in [100, 200, 300] {
+                ink::env::test::set_block_timestamp::<ink::env::DefaultEnvironment>(timestamp);
+                let escrow_id = contract.initiate_escrow(accounts.bob, 100).unwrap();
+                contract.cancel_escrow(escrow_id).unwrap();
+                ids.push(escrow_id);
+            }
 
-            // Act & Assert: Test successful escrow creation
-            let amount = 100;
-            let result = contract.initiate_escrow(accounts.bob, amount);
-            assert!(result.is_ok(), "Should successfully create escrow");
-            let escrow_id = result.unwrap();
+            // Act & Assert: ranges include the start and exclude the end
+            assert_eq!(contract.get_settled_between(100, 300, 0, 10), vec![ids[0], ids[1]], "Should list the range");
+            assert_eq!(contract.get_settled_between(150, 1_000, 1, 10), vec![ids[2]], "Should paginate within the range");
+            assert_eq!(contract.get_settled_between(100, 1_000, 0, 1), vec![ids[0]], "Should cap at the limit");
+            assert!(contract.get_settled_between(301, 1_000, 0, 10).is_empty(), "Empty range");
 
-            // Verify stored escrow details
-            let escrow = contract.escrows
-                .get(escrow_id)
-                .expect("Escrow should exist after creation");
-            assert_eq!(escrow.buyer, accounts.alice, "Buyer should be caller");
-            assert_eq!(escrow.seller, accounts.bob, "Seller should match argument");
-            assert_eq!(escrow.amount, amount, "Amount should match initial value");
-            assert_eq!(escrow.state, EscrowState::Created, "Initial state should be Created");
+            // A reopened escrow is listed at its latest settlement only
+            ink::env::test::set_block_timestamp::<ink::env::DefaultEnvironment>(400);
+            contract.reopen_escrow(ids[0]).unwrap();
+            assert_eq!(contract.get_settled_between(0, 1_000, 0, 10), vec![ids[1], ids[2]], "Reopened escrow is not settled");
+            contract.cancel_escrow(ids[0]).unwrap();
+            assert_eq!(contract.get_settled_between(0, 1_000, 0, 10), vec![ids[1], ids[2], ids[0]], "Should list the new settlement");
+        }
 
-            // Test validation: Buyer cannot be seller
-            let invalid_result = contract.initiate_escrow(accounts.alice, amount);
+        #[ink::test]
+        fn test_emergency_drain_in_batches() {
+            // Arrange: a funded escrow, one with an installment, one with a seller stake and one untouched
+            let accounts = ink::env::test::default_accounts::<ink::env::DefaultEnvironment>();
+            let mut contract = EscrowSmartContract::new();
+            let funded = contract.initiate_escrow(accounts.bob, 100).unwrap();
+            ink::env::test::set_value_transferred::<ink::env::DefaultEnvironment>(100);
+            contract.deposit_assets(funded).unwrap();
+            let untouched = contract.initiate_escrow(accounts.bob, 100).unwrap();
+            let partial = contract.initiate_escrow(accounts.bob, 100).unwrap();
+            ink::env::test::set_value_transferred::<ink::env::DefaultEnvironment>(40);
+            contract.deposit_assets(partial).unwrap();
+            let staked = contract.initiate_stake_escrow(accounts.bob, 100, 30).unwrap();
+            ink::env::test::set_caller::<ink::env::DefaultEnvironment>(accounts.bob);
+            ink::env::test::set_value_transferred::<ink::env::DefaultEnvironment>(30);
+            contract.deposit_stake(staked).unwrap();
+            ink::env::test::set_caller::<ink::env::DefaultEnvironment>(accounts.alice);
+            contract.shutdown().unwrap();
+
+            // Act: drain two escrows at a time
+            let django = ink::env::test::get_account_balance::<ink::env::DefaultEnvironment>(accounts.django).unwrap();
+            assert_eq!(contract.emergency_drain(accounts.django, 2), Ok(2), "Untouched and one deposit escrow should remain");
+            assert_eq!(last_event::<EmergencyDrained>().amount, 0, "Nothing is transferred while escrows remain");
             assert_eq!(
-                invalid_result,
-                Err(Error::InvalidParticipants),
-                "Should prevent buyer=seller creation"
+                ink::env::test::get_account_balance::<ink::env::DefaultEnvironment>(accounts.django).unwrap(),
+                django,
+                "Safe address should wait for the last batch"
+            );
+            let balance = ink::env::test::get_account_balance::<ink::env::DefaultEnvironment>(accounts.alice).unwrap();
+            assert_eq!(contract.emergency_drain(accounts.django, 0), Ok(2), "An empty batch should make no progress");
+            assert_eq!(contract.emergency_drain(accounts.django, 2), Ok(0), "Second batch should finish the drain");
+
+            // Assert
+            let state = |escrow_id| contract.escrows.get(escrow_id).unwrap().state;
+            assert_eq!(state(funded), EscrowState::Canceled, "Funded escrow is canceled");
+            assert_eq!(state(partial), EscrowState::Canceled, "Escrow holding an installment is canceled");
+            assert_eq!(state(staked), EscrowState::Canceled, "Escrow holding a stake is canceled");
+            assert_eq!(state(untouched), EscrowState::Created, "Unfunded escrow is untouched");
+            assert_eq!(contract.state_counts.get(EscrowState::Created), Some(1), "Canceled escrows should leave the created index");
+            assert_eq!(contract.get_total_locked(), 0, "Nothing stays locked");
+            assert_eq!(contract.get_liabilities().posted_bonds, 0, "No bonds stay posted");
+            let event = last_event::<EmergencyDrained>();
+            assert_eq!((event.amount, event.canceled, event.remaining), (balance, 2, 0), "Last batch should transfer the balance");
+            assert_eq!(
+                ink::env::test::get_account_balance::<ink::env::DefaultEnvironment>(accounts.django).unwrap(),
+                django + balance,
+                "Safe address should receive the balance"
             );
         }
 
         #[ink::test]
-        fn test_deposit_assets() {
-            // Arrange: Create escrow and set test environment
+        fn test_shutdown_and_emergency_drain() {
+            // Arrange: a funded escrow
             let accounts = ink::env::test::default_accounts::<ink::env::DefaultEnvironment>();
             let mut contract = EscrowSmartContract::new();
-            let amount = 100;
-            let escrow_id = contract.initiate_escrow(accounts.bob, amount).unwrap();
+            let escrow_id = contract.initiate_escrow(accounts.bob, 100).unwrap();
+            ink::env::test::set_value_transferred::<ink::env::DefaultEnvironment>(100);
+            contract.deposit_assets(escrow_id).unwrap();
+            let pending = contract.initiate_escrow(accounts.bob, 50).unwrap();
 
-            // Test successful deposit
-            ink::env::test::set_value_transferred::<ink::env::DefaultEnvironment>(amount);
-            let deposit_result = contract.deposit_assets(escrow_id);
-            assert!(deposit_result.is_ok(), "Should accept valid deposit");
+            // Act & Assert: the drain is gated behind the owner's shutdown
+            assert_eq!(contract.emergency_drain(accounts.django, 10), Err(Error::NotShutDown), "Not shut down yet");
+            ink::env::test::set_caller::<ink::env::DefaultEnvironment>(accounts.bob);
+            assert_eq!(contract.shutdown(), Err(Error::Unauthorized), "Only the owner shuts down");
+            ink::env::test::set_caller::<ink::env::DefaultEnvironment>(accounts.alice);
+            contract.shutdown().unwrap();
+            assert_eq!(last_event::<Shutdown>().by, accounts.alice, "Event should name the owner");
+            assert_eq!(contract.shutdown(), Err(Error::ShutDown), "Shutdown cannot be repeated");
+            assert_eq!(contract.deposit_assets(pending), Err(Error::ShutDown), "Funding is frozen");
+            assert_eq!(contract.complete_escrow(escrow_id), Err(Error::ShutDown), "Settling is frozen");
+            assert_eq!(contract.initiate_escrow(accounts.bob, 10), Err(Error::ShutDown), "Creation is frozen");
 
-            // Verify state transition
-            let escrow = contract.escrows
-                .get(escrow_id)
-                .expect("Escrow should exist after deposit");
+            // The drain moves the whole balance and cancels the funded escrow
+            let balance = ink::env::test::get_account_balance::<ink::env::DefaultEnvironment>(accounts.alice).unwrap();
+            let django = ink::env::test::get_account_balance::<ink::env::DefaultEnvironment>(accounts.django).unwrap();
+            assert_eq!(contract.emergency_drain(accounts.django, 10), Ok(0), "Should drain the balance");
             assert_eq!(
-                escrow.state,
-                EscrowState::Funded,
-                "State should transition to Funded after deposit"
+                ink::env::test::get_account_balance::<ink::env::DefaultEnvironment>(accounts.django).unwrap(),
+                django + balance,
+                "Safe address should receive the balance"
             );
-
-            // Test invalid amount scenario
-            ink::env::test::set_value_transferred::<ink::env::DefaultEnvironment>(amount + 1);
-            let invalid_deposit_result = contract.deposit_assets(escrow_id);
+            assert_eq!(contract.escrows.get(escrow_id).unwrap().state, EscrowState::Canceled, "Funded escrow is canceled");
+            assert_eq!(contract.escrows.get(pending).unwrap().state, EscrowState::Created, "Unfunded escrow is untouched");
+            assert_eq!(contract.get_total_locked(), 0, "Nothing stays locked");
+            let event = last_event::<EmergencyDrained>();
             assert_eq!(
-                invalid_deposit_result,
-                Err(Error::InvalidState),
-                "Should reject deposit with incorrect amount"
+                (event.to, event.amount, event.canceled, event.remaining),
+                (accounts.django, balance, 1, 0),
+                "Event should describe the drain"
             );
+            let events = ink::env::test::recorded_events().collect::<Vec<_>>();
+            let canceled = <Canceled as scale::Decode>::decode(&mut &events[events.len() - 2].data[..]).expect("Event should decode");
+            assert_eq!((canceled.escrow_id, canceled.reason), (escrow_id, CancelReason::Emergency), "Drain cancels as emergency");
         }
 
         #[ink::test]
-        fn test_complete_escrow() {
-            // Arrange: Set up funded escrow
+        fn test_effective_recipients() {
+            // Arrange
             let accounts = ink::env::test::default_accounts::<ink::env::DefaultEnvironment>();
             let mut contract = EscrowSmartContract::new();
-            let amount = 100;
+            let escrow_id = contract.initiate_escrow(accounts.bob, 100).unwrap();
 
-            // Initialize and fund escrow as buyer
-            ink::env::test::set_caller::<ink::env::DefaultEnvironment>(accounts.alice);
-            let escrow_id = contract.initiate_escrow(accounts.bob, amount).unwrap();
-            ink::env::test::set_value_transferred::<ink::env::DefaultEnvironment>(amount);
-            contract.deposit_assets(escrow_id).unwrap();
+            // Act & Assert: falls back to the participants, then follows the overrides
+            assert_eq!(contract.effective_recipients(escrow_id), Ok((accounts.bob, accounts.alice)), "Should use the participants");
+            contract.set_refund_address(escrow_id, accounts.django).unwrap();
+            ink::env::test::set_caller::<ink::env::DefaultEnvironment>(accounts.bob);
+            contract.set_payout_address(escrow_id, accounts.eve).unwrap();
+            assert_eq!(contract.effective_recipients(escrow_id), Ok((accounts.eve, accounts.django)), "Should use the overrides");
+            assert_eq!(contract.effective_recipients(escrow_id + 1), Err(Error::NotFound), "Unknown escrow");
+        }
 
-            // Act & Assert: Buyer approval
-            let buyer_approval_result = contract.complete_escrow(escrow_id);
-            assert!(buyer_approval_result.is_ok(), "Buyer should approve successfully");
+        #[ink::test]
+        fn test_admin_actions() {
+            // Arrange
+            let accounts = ink::env::test::default_accounts::<ink::env::DefaultEnvironment>();
+            let mut contract = EscrowSmartContract::new();
+            let admins = ink::prelude::vec![accounts.bob, accounts.charlie, accounts.django];
+            assert_eq!(contract.set_admins(admins.clone(), 4), Err(Error::InvalidThreshold), "More than the admins");
+            assert_eq!(contract.set_admins(admins.clone(), 0), Err(Error::InvalidThreshold), "Zero threshold");
+            contract.set_admins(admins.clone(), 2).unwrap();
 
-            // Verify partial approval state
-            let escrow = contract.escrows
-                .get(escrow_id)
-                .expect("Escrow should exist after approval");
-            assert!(escrow.buyer_approved, "Buyer approval flag should be set");
-            assert!(!escrow.seller_approved, "Seller should not be approved yet");
+            // Act & Assert: the owner can no longer act alone
+            assert_eq!(contract.set_fee(100), Err(Error::Unauthorized), "Fee needs confirmations");
+            assert_eq!(contract.set_fee_override(accounts.eve, 10_000), Err(Error::Unauthorized), "Override needs confirmations");
+            assert_eq!(contract.set_max_open_per_buyer(1), Err(Error::Unauthorized), "Limits need confirmations");
+            assert_eq!(contract.set_refund_lock_period(1), Err(Error::Unauthorized), "Lock period needs confirmations");
+            assert_eq!(contract.set_admins(admins, 1), Err(Error::Unauthorized), "Admins are set once");
+            assert_eq!(contract.propose_admin_action(AdminAction::SetFee(100)), Err(Error::Unauthorized), "Owner is not an admin");
+
+            // Two of three admins confirm the fee change
+            ink::env::test::set_caller::<ink::env::DefaultEnvironment>(accounts.bob);
+            let proposal_id = contract.propose_admin_action(AdminAction::SetFee(100)).unwrap();
+            assert_eq!(contract.confirm(proposal_id), Err(Error::AlreadyApproved), "Proposer has confirmed");
+            assert_eq!(contract.execute(proposal_id), Err(Error::NotEnoughConfirmations), "Below the threshold");
+            ink::env::test::set_caller::<ink::env::DefaultEnvironment>(accounts.charlie);
+            contract.confirm(proposal_id).unwrap();
+            assert_eq!(last_event::<AdminActionConfirmed>().confirmations, 2, "Event should count confirmations");
+            contract.execute(proposal_id).unwrap();
+            assert_eq!(contract.fee_bps, 100, "Fee should be set");
+            assert_eq!(contract.execute(proposal_id), Err(Error::InvalidState), "Executed once");
+            assert_eq!(contract.confirm(proposal_id), Err(Error::InvalidState), "No confirmations after execution");
+
+            // Fee overrides run through proposals as well
+            let proposal_id = contract.propose_admin_action(AdminAction::SetFeeOverride(accounts.eve, 50)).unwrap();
+            ink::env::test::set_caller::<ink::env::DefaultEnvironment>(accounts.django);
+            contract.confirm(proposal_id).unwrap();
+            contract.execute(proposal_id).unwrap();
+            assert_eq!(contract.fee_overrides.get(accounts.eve), Some(50), "Override should be set");
+        }
+
+        #[ink::test]
+        fn test_admin_drain_continues_across_batches() {
+            // Arrange: three funded escrows on a contract governed by two admins
+            let accounts = ink::env::test::default_accounts::<ink::env::DefaultEnvironment>();
+            let mut contract = EscrowSmartContract::new();
+            ink::env::test::set_value_transferred::<ink::env::DefaultEnvironment>(100);
+            for _ in 0..3 {
+                let escrow_id = contract.initiate_escrow(accounts.bob, 100).unwrap();
+                contract.deposit_assets(escrow_id).unwrap();
+            }
+            contract.set_admins(ink::prelude::vec![accounts.bob, accounts.charlie], 2).unwrap();
+            ink::env::test::set_caller::<ink::env::DefaultEnvironment>(accounts.bob);
+            let proposal_id = contract.propose_admin_action(AdminAction::Shutdown).unwrap();
+            ink::env::test::set_caller::<ink::env::DefaultEnvironment>(accounts.charlie);
+            contract.confirm(proposal_id).unwrap();
+            contract.execute(proposal_id).unwrap();
+            let proposal_id = contract.propose_admin_action(AdminAction::EmergencyDrain(accounts.django, 2)).unwrap();
+            ink::env::test::set_caller::<ink::env::DefaultEnvironment>(accounts.bob);
+            contract.confirm(proposal_id).unwrap();
+
+            // Act & Assert: the same proposal drains every batch
+            contract.execute(proposal_id).unwrap();
+            assert_eq!(contract.state_counts.get(EscrowState::Funded), Some(1), "First batch should leave one escrow");
+            assert!(!contract.proposals.get(proposal_id).unwrap().executed, "Proposal should stay open");
+            let balance = ink::env::test::get_account_balance::<ink::env::DefaultEnvironment>(accounts.alice).unwrap();
+            let django = ink::env::test::get_account_balance::<ink::env::DefaultEnvironment>(accounts.django).unwrap();
+            ink::env::test::set_caller::<ink::env::DefaultEnvironment>(accounts.charlie);
+            contract.execute(proposal_id).unwrap();
+            assert_eq!(contract.state_counts.get(EscrowState::Funded), Some(0), "Every escrow should be canceled");
             assert_eq!(
-                escrow.state,
-                EscrowState::Funded,
-                "State should remain Funded until both approve"
+                ink::env::test::get_account_balance::<ink::env::DefaultEnvironment>(accounts.django).unwrap(),
+                django + balance,
+                "Last batch should transfer the balance"
             );
+            assert_eq!(contract.execute(proposal_id), Err(Error::InvalidState), "Drain is done");
+        }
 
-            // Seller approval
+        #[ink::test]
+        fn test_confirm_delivery() {
+            // Arrange: a funded escrow gated on an oracle
+            let accounts = ink::env::test::default_accounts::<ink::env::DefaultEnvironment>();
+            let mut contract = EscrowSmartContract::new();
+            let terms = EscrowTerms { oracle: Some(accounts.bob), ..Default::default() };
+            assert_eq!(
+                contract.initiate_escrow_with_terms(accounts.bob, 100, terms),
+                Err(Error::InvalidParticipants),
+                "Oracle cannot be a party"
+            );
+            let terms = EscrowTerms { oracle: Some(accounts.eve), ..Default::default() };
+            let escrow_id = contract.initiate_escrow_with_terms(accounts.bob, 100, terms).unwrap();
+            ink::env::test::set_value_transferred::<ink::env::DefaultEnvironment>(100);
+            contract.deposit_assets(escrow_id).unwrap();
+
+            // Act & Assert: approvals alone do not release the funds
+            contract.complete_escrow(escrow_id).unwrap();
             ink::env::test::set_caller::<ink::env::DefaultEnvironment>(accounts.bob);
-            let seller_approval_result = contract.complete_escrow(escrow_id);
-            assert!(seller_approval_result.is_ok(), "Seller should approve successfully");
+            assert_eq!(contract.complete_escrow(escrow_id), Err(Error::DeliveryNotConfirmed), "Delivery not confirmed");
+            assert_eq!(contract.confirm_delivery(escrow_id), Err(Error::Unauthorized), "Only the oracle confirms");
 
-            // Verify final state
-            let final_escrow = contract.escrows
-                .get(escrow_id)
-                .expect("Escrow should exist after completion");
-            assert!(final_escrow.buyer_approved, "Buyer approval should persist");
-            assert!(final_escrow.seller_approved, "Seller approval should be set");
+            ink::env::test::set_caller::<ink::env::DefaultEnvironment>(accounts.eve);
+            contract.confirm_delivery(escrow_id).unwrap();
+            assert_eq!(last_event::<DeliveryConfirmed>().oracle, accounts.eve, "Event should name the oracle");
+            assert_eq!(contract.confirm_delivery(escrow_id), Err(Error::InvalidState), "Confirmed once");
+
+            ink::env::test::set_caller::<ink::env::DefaultEnvironment>(accounts.bob);
+            contract.complete_escrow(escrow_id).unwrap();
+            assert_eq!(contract.escrows.get(escrow_id).unwrap().state, EscrowState::Completed, "Should complete");
+        }
+
+        #[ink::test]
+        fn test_complete_escrow_batch() {
+            // Arrange: two funded escrows and one unfunded
+            let accounts = ink::env::test::default_accounts::<ink::env::DefaultEnvironment>();
+            let mut contract = EscrowSmartContract::new();
+            let mut ids = Vec::new();
+            for _ in 0..3 {
+                ids.push(contract.initiate_escrow(accounts.bob, 100).unwrap());
+            }
+            ink::env::test::set_value_transferred::<ink::env::DefaultEnvironment>(100);
+            contract.deposit_assets(ids[0]).unwrap();
+            contract.deposit_assets(ids[1]).unwrap();
+            contract.complete_escrow(ids[0]).unwrap();
+
+            // Act: the seller approves all of them at once
+            ink::env::test::set_caller::<ink::env::DefaultEnvironment>(accounts.bob);
+            let results = contract.complete_escrow_batch(ids.clone()).unwrap();
+
+            // Assert
+            assert_eq!(results, ink::prelude::vec![Ok(()), Ok(()), Err(Error::InvalidState)], "Outcome per escrow");
+            assert_eq!(contract.escrows.get(ids[0]).unwrap().state, EscrowState::Completed, "Both approved");
+            assert!(contract.escrows.get(ids[1]).unwrap().seller_approved, "Approval should be recorded");
             assert_eq!(
-                final_escrow.state,
-                EscrowState::Completed,
-                "State should transition to Completed after dual approval"
+                contract.complete_escrow_batch(ink::prelude::vec![0; MAX_BATCH_SIZE as usize + 1]),
+                Err(Error::BatchTooLarge),
+                "Batch should be capped"
             );
         }
 
         #[ink::test]
-        fn test_cancel_escrow_by_buyer() {
-            // Arrange: Create and fund escrow
+        fn test_complete_escrow_batch_fails_whole_batch() {
+            // Arrange: two funded escrows the buyer has approved
             let accounts = ink::env::test::default_accounts::<ink::env::DefaultEnvironment>();
             let mut contract = EscrowSmartContract::new();
-            let amount = 100;
-            let escrow_id = contract.initiate_escrow(accounts.bob, amount).unwrap();
+            ink::env::test::set_value_transferred::<ink::env::DefaultEnvironment>(100);
+            let first = contract.initiate_escrow(accounts.bob, 100).unwrap();
+            let second = contract.initiate_escrow(accounts.bob, 100).unwrap();
+            for escrow_id in [first, second] {
+                contract.deposit_assets(escrow_id).unwrap();
+                contract.complete_escrow(escrow_id).unwrap();
+            }
 
-            // Fund escrow
-            ink::env::test::set_value_transferred::<ink::env::DefaultEnvironment>(amount);
-            contract.deposit_assets(escrow_id).unwrap();
+            // Act: transfers cannot fail off-chain, so force a failure after the completion has started
+            contract.seq = u64::MAX;
+            ink::env::test::set_caller::<ink::env::DefaultEnvironment>(accounts.bob);
+            let result = contract.complete_escrow_batch(ink::prelude::vec![first, second, 99]);
 
-            // Act: Cancel by buyer
-            let cancel_result = contract.cancel_escrow(escrow_id);
-            assert!(cancel_result.is_ok(), "Buyer should cancel successfully");
+            // Assert
+            assert_eq!(result, Err(Error::IdOverflow), "A failed completion should fail the batch");
+        }
 
-            // Assert: State transition and funds handling
-            let escrow = contract.escrows
-                .get(escrow_id)
-                .expect("Escrow should exist after cancellation");
-            assert_eq!(escrow.state, EscrowState::Canceled, "State should transition to Canceled");
+        #[ink::test]
+        fn test_initiate_escrow_with_memo() {
+            // Arrange
+            let accounts = ink::env::test::default_accounts::<ink::env::DefaultEnvironment>();
+            let mut contract = EscrowSmartContract::new();
+            let terms = EscrowTerms { memo: Some(b"invoice 42".to_vec()), ..Default::default() };
+
+            // Act
+            let escrow_id = contract.initiate_escrow_with_terms(accounts.bob, 100, terms).unwrap();
+
+            // Assert
+            assert_eq!(contract.escrows.get(escrow_id).unwrap().terms.memo, Some(b"invoice 42".to_vec()), "Memo should be stored");
+            assert_eq!(last_event::<Initiated>().memo, Some(b"invoice 42".to_vec()), "Event should echo the memo");
+            contract.initiate_escrow(accounts.bob, 100).unwrap();
+            assert_eq!(last_event::<Initiated>().memo, None, "Escrows without a memo echo none");
+            let terms = EscrowTerms { memo: Some(ink::prelude::vec![0; MAX_MEMO_LEN + 1]), ..Default::default() };
+            assert_eq!(
+                contract.initiate_escrow_with_terms(accounts.bob, 100, terms),
+                Err(Error::MemoTooLong),
+                "Memo should be bounded"
+            );
         }
 
         #[ink::test]
-        fn test_cancel_escrow_completed() {
-            // Arrange: Create completed escrow
+        fn test_locked_balance_of() {
+            // Arrange: a funded escrow, a partly funded one and a completed one
             let accounts = ink::env::test::default_accounts::<ink::env::DefaultEnvironment>();
             let mut contract = EscrowSmartContract::new();
-            let amount = 100;
-            let escrow_id = contract.initiate_escrow(accounts.bob, amount).unwrap();
+            let funded = contract.initiate_escrow(accounts.bob, 100).unwrap();
+            let partial = contract.initiate_escrow(accounts.bob, 50).unwrap();
+            let completed = contract.initiate_escrow(accounts.bob, 30).unwrap();
+            ink::env::test::set_value_transferred::<ink::env::DefaultEnvironment>(100);
+            contract.deposit_assets(funded).unwrap();
+            ink::env::test::set_value_transferred::<ink::env::DefaultEnvironment>(20);
+            contract.deposit_assets(partial).unwrap();
+            ink::env::test::set_value_transferred::<ink::env::DefaultEnvironment>(30);
+            contract.deposit_assets(completed).unwrap();
+            contract.complete_escrow(completed).unwrap();
+            ink::env::test::set_caller::<ink::env::DefaultEnvironment>(accounts.bob);
+            contract.complete_escrow(completed).unwrap();
 
-            // Complete escrow lifecycle
-            ink::env::test::set_value_transferred::<ink::env::DefaultEnvironment>(amount);
-            contract.deposit_assets(escrow_id).unwrap();
-            contract.complete_escrow(escrow_id).unwrap(); // Buyer approval
+            // Act & Assert
+            assert_eq!(contract.locked_balance_of(accounts.alice), 120, "Funded amount and installments");
+            assert_eq!(contract.locked_balance_of(accounts.bob), 0, "Seller has nothing locked as buyer");
+        }
+
+        #[ink::test]
+        fn test_set_code_hash_owner_only() {
+            // Arrange
+            let accounts = ink::env::test::default_accounts::<ink::env::DefaultEnvironment>();
+            let mut contract = EscrowSmartContract::new();
+
+            // Act & Assert: the off-chain environment cannot replace code, so only the guard is exercised
             ink::env::test::set_caller::<ink::env::DefaultEnvironment>(accounts.bob);
-            contract.complete_escrow(escrow_id).unwrap(); // Seller approval
+            assert_eq!(contract.set_code_hash(Hash::from([1u8; 32])), Err(Error::Unauthorized), "Only the owner upgrades");
+        }
 
-            // Act & Assert: Cancel completed escrow
-            ink::env::test::set_caller::<ink::env::DefaultEnvironment>(accounts.alice);
-            let cancel_result = contract.cancel_escrow(escrow_id);
+        #[ink::test]
+        fn test_zero_address_rejected() {
+            // Arrange
+            let accounts = ink::env::test::default_accounts::<ink::env::DefaultEnvironment>();
+            let zero = AccountId::from([0u8; 32]);
+            let mut contract = EscrowSmartContract::new();
+
+            // Act & Assert: escrows cannot pay the zero account
+            assert_eq!(contract.initiate_escrow(zero, 100), Err(Error::ZeroAddress), "Seller");
             assert_eq!(
-                cancel_result,
-                Err(Error::InvalidState),
-                "Should prevent canceling completed escrow"
+                contract.initiate_split_escrow(ink::prelude::vec![(accounts.bob, 50), (zero, 50)], 100),
+                Err(Error::ZeroAddress),
+                "Split recipient"
             );
+            let terms = EscrowTerms { oracle: Some(zero), ..Default::default() };
+            assert_eq!(contract.initiate_escrow_with_terms(accounts.bob, 100, terms), Err(Error::ZeroAddress), "Oracle");
+            assert_eq!(contract.create_template(zero, 100), Err(Error::ZeroAddress), "Template seller");
+            ink::env::test::set_caller::<ink::env::DefaultEnvironment>(accounts.bob);
+            assert_eq!(contract.request_payment(zero, 100), Err(Error::ZeroAddress), "Invoiced buyer");
+            assert_eq!(EscrowSmartContract::new_with_arbiter(zero).err(), Some(Error::ZeroAddress), "Default arbiter");
 
-            // Verify state persistence
-            let escrow = contract.escrows.get(escrow_id).expect("Escrow should still exist");
-            assert_eq!(escrow.state, EscrowState::Completed, "State should remain Completed");
+            // Address-setting messages reject it as well
+            ink::env::test::set_caller::<ink::env::DefaultEnvironment>(accounts.alice);
+            let escrow_id = contract.initiate_escrow(accounts.bob, 100).unwrap();
+            ink::env::test::set_value_transferred::<ink::env::DefaultEnvironment>(100);
+            contract.deposit_assets(escrow_id).unwrap();
+            assert_eq!(contract.propose_arbiter(escrow_id, zero), Err(Error::ZeroAddress), "Arbiter");
+            assert_eq!(contract.transfer_buyer_position(escrow_id, zero), Err(Error::ZeroAddress), "New buyer");
+            assert_eq!(contract.set_admins(ink::prelude::vec![zero], 1), Err(Error::ZeroAddress), "Admin");
+            contract.shutdown().unwrap();
+            assert_eq!(contract.emergency_drain(zero, 10), Err(Error::ZeroAddress), "Safe address");
         }
 
         #[ink::test]
-        fn test_unauthorized_actions() {
-            // Arrange: Create escrow and set up unauthorized caller
+        fn test_return_window() {
+            // Arrange: a completed escrow with a return window of 1_000
             let accounts = ink::env::test::default_accounts::<ink::env::DefaultEnvironment>();
             let mut contract = EscrowSmartContract::new();
-            let amount = 100;
-            let escrow_id = contract.initiate_escrow(accounts.bob, amount).unwrap();
+            ink::env::test::set_caller::<ink::env::DefaultEnvironment>(accounts.charlie);
+            let terms = EscrowTerms { return_window: Some(1_000), ..Default::default() };
+            let escrow_id = contract.initiate_escrow_with_terms(accounts.bob, 100, terms).unwrap();
+            ink::env::test::set_value_transferred::<ink::env::DefaultEnvironment>(100);
+            contract.deposit_assets(escrow_id).unwrap();
+            contract.complete_escrow(escrow_id).unwrap();
+            ink::env::test::set_caller::<ink::env::DefaultEnvironment>(accounts.bob);
+            contract.complete_escrow(escrow_id).unwrap();
 
-            // Set unauthorized caller (Charlie)
+            // Act & Assert: the seller approves only a requested return
+            assert_eq!(contract.approve_return(escrow_id), Err(Error::InvalidState), "No request yet");
             ink::env::test::set_caller::<ink::env::DefaultEnvironment>(accounts.charlie);
+            contract.request_return(escrow_id).unwrap();
+            assert_eq!(contract.request_return(escrow_id), Err(Error::InvalidState), "Requested once");
 
-            // Test 1: Unauthorized deposit attempt
-            ink::env::test::set_value_transferred::<ink::env::DefaultEnvironment>(amount);
-            let deposit_result = contract.deposit_assets(escrow_id);
+            ink::env::test::set_caller::<ink::env::DefaultEnvironment>(accounts.bob);
+            ink::env::test::set_value_transferred::<ink::env::DefaultEnvironment>(99);
+            assert_eq!(contract.approve_return(escrow_id), Err(Error::InvalidAmount), "Must repay the amount");
+            let buyer_balance = ink::env::test::get_account_balance::<ink::env::DefaultEnvironment>(accounts.charlie).unwrap();
+            ink::env::test::set_value_transferred::<ink::env::DefaultEnvironment>(100);
+            contract.approve_return(escrow_id).unwrap();
             assert_eq!(
-                deposit_result,
-                Err(Error::Unauthorized),
-                "Should prevent unauthorized deposits"
+                ink::env::test::get_account_balance::<ink::env::DefaultEnvironment>(accounts.charlie).unwrap(),
+                buyer_balance + 100,
+                "Buyer should be repaid"
             );
+            assert_eq!(contract.escrows.get(escrow_id).unwrap().state, EscrowState::Returned, "Should be returned");
+            assert_eq!(last_event::<Returned>().amount, EventAmount::Clear(100), "Event should carry the amount");
 
-            // Test 2: Unauthorized completion attempt
-            let complete_result = contract.complete_escrow(escrow_id);
+            // Returns are disallowed after the window, and the escrow is kept until then
+            ink::env::test::set_caller::<ink::env::DefaultEnvironment>(accounts.charlie);
+            let terms = EscrowTerms { return_window: Some(1_000), ..Default::default() };
+            let late = contract.initiate_escrow_with_terms(accounts.bob, 100, terms).unwrap();
+            contract.deposit_assets(late).unwrap();
+            contract.complete_escrow(late).unwrap();
+            ink::env::test::set_caller::<ink::env::DefaultEnvironment>(accounts.bob);
+            contract.complete_escrow(late).unwrap();
+            assert_eq!(contract.purge_batch(ink::prelude::vec![late]), Ok(0), "Returnable escrow is kept");
+            ink::env::test::set_block_timestamp::<ink::env::DefaultEnvironment>(1_001);
+            ink::env::test::set_caller::<ink::env::DefaultEnvironment>(accounts.charlie);
+            assert_eq!(contract.request_return(late), Err(Error::DeadlineExceeded), "Window has passed");
+        }
+
+        #[ink::test]
+        fn test_return_repays_net_of_fee() {
+            // Arrange: a completed escrow with a return window and a 10% platform fee
+            let accounts = ink::env::test::default_accounts::<ink::env::DefaultEnvironment>();
+            let mut contract = EscrowSmartContract::new();
+            contract.set_fee(1_000).unwrap();
+            ink::env::test::set_caller::<ink::env::DefaultEnvironment>(accounts.charlie);
+            let terms = EscrowTerms { return_window: Some(1_000), ..Default::default() };
+            let escrow_id = contract.initiate_escrow_with_terms(accounts.bob, 100, terms).unwrap();
+            ink::env::test::set_value_transferred::<ink::env::DefaultEnvironment>(100);
+            contract.deposit_assets(escrow_id).unwrap();
+            contract.complete_escrow(escrow_id).unwrap();
+            ink::env::test::set_caller::<ink::env::DefaultEnvironment>(accounts.bob);
+            contract.complete_escrow(escrow_id).unwrap();
+            ink::env::test::set_caller::<ink::env::DefaultEnvironment>(accounts.charlie);
+            contract.request_return(escrow_id).unwrap();
+
+            // Act: the seller received 90 and must send back exactly that
+            ink::env::test::set_caller::<ink::env::DefaultEnvironment>(accounts.bob);
+            ink::env::test::set_value_transferred::<ink::env::DefaultEnvironment>(100);
+            assert_eq!(contract.approve_return(escrow_id), Err(Error::InvalidAmount), "The fee is not owed back");
+            let buyer_balance = ink::env::test::get_account_balance::<ink::env::DefaultEnvironment>(accounts.charlie).unwrap();
+            ink::env::test::set_value_transferred::<ink::env::DefaultEnvironment>(90);
+            contract.approve_return(escrow_id).unwrap();
+
+            // Assert
             assert_eq!(
-                complete_result,
-                Err(Error::InvalidState),
-                "Should prevent unauthorized completion"
+                ink::env::test::get_account_balance::<ink::env::DefaultEnvironment>(accounts.charlie).unwrap(),
+                buyer_balance + 90,
+                "Buyer should be repaid the net amount"
             );
+            assert_eq!(last_event::<Returned>().amount, EventAmount::Clear(90), "Event should carry the net amount");
+        }
 
-            // Test 3: Unauthorized cancellation attempt
-            let cancel_result = contract.cancel_escrow(escrow_id);
+        #[ink::test]
+        fn test_get_expiring_before() {
+            // Arrange: funded escrows with deadlines 100 and 500, one without, one measured in blocks
+            let accounts = ink::env::test::default_accounts::<ink::env::DefaultEnvironment>();
+            let mut contract = EscrowSmartContract::new();
+            let mut ids = Vec::new();
+            for terms in [
+                EscrowTerms { release_deadline: Some(100), ..Default::default() },
+                EscrowTerms { release_deadline: Some(500), ..Default::default() },
+                EscrowTerms::default(),
+                EscrowTerms { release_deadline: Some(10), deadline_kind: DeadlineKind::BlockNumber, ..Default::default() },
+            ] {
+                let escrow_id = contract.initiate_escrow_with_terms(accounts.bob, 100, terms).unwrap();
+                ink::env::test::set_value_transferred::<ink::env::DefaultEnvironment>(100);
+                contract.deposit_assets(escrow_id).unwrap();
+                ids.push(escrow_id);
+            }
+
+            // Act & Assert
+            assert_eq!(contract.get_expiring_before(200, 0, 10), vec![ids[0]], "Only the earlier deadline");
+            assert_eq!(contract.get_expiring_before(1_000, 1, 10), vec![ids[1]], "Should start at the given ID");
+            assert!(contract.get_expiring_before(100, 0, 10).is_empty(), "Deadline must be earlier");
+        }
+
+        #[ink::test]
+        fn test_seller_allowlist() {
+            // Arrange
+            let accounts = ink::env::test::default_accounts::<ink::env::DefaultEnvironment>();
+            let mut contract = EscrowSmartContract::new();
+            contract.add_allowed_seller(accounts.bob).unwrap();
+
+            // Act & Assert: the allowlist only applies once enabled
+            assert!(contract.initiate_escrow(accounts.django, 100).is_ok(), "Disabled allowlist is ignored");
+            contract.set_allowlist_enabled(true).unwrap();
+            assert_eq!(contract.initiate_escrow(accounts.django, 100), Err(Error::SellerNotAllowed), "Seller not allowed");
+            assert!(contract.initiate_escrow(accounts.bob, 100).is_ok(), "Allowed seller");
+            assert_eq!(contract.get_allowed_sellers(accounts.alice), (vec![accounts.bob], true), "Should report the allowlist");
             assert_eq!(
-                cancel_result,
-                Err(Error::Unauthorized),
-                "Should prevent unauthorized cancellation"
+                contract.initiate_split_escrow(vec![(accounts.bob, 50), (accounts.django, 50)], 100),
+                Err(Error::SellerNotAllowed),
+                "Every recipient of a split escrow should be allowed"
             );
+
+            // Removing the seller rejects it again
+            contract.remove_allowed_seller(accounts.bob).unwrap();
+            assert!(!last_event::<AllowedSellerUpdated>().allowed, "Event should report the removal");
+            assert_eq!(contract.remove_allowed_seller(accounts.bob), Err(Error::NotFound), "Already removed");
+            assert_eq!(contract.initiate_escrow(accounts.bob, 100), Err(Error::SellerNotAllowed), "Removed seller");
+
+            // Other buyers are unaffected
+            ink::env::test::set_caller::<ink::env::DefaultEnvironment>(accounts.charlie);
+            assert!(contract.initiate_escrow(accounts.django, 100).is_ok(), "No allowlist by default");
         }
     }
 }